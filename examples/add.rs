@@ -46,14 +46,14 @@ fn main() {
     let c = executor.new_resource().unwrap();
     a.set_data(TokenValue::Vector(ConstantVector::U32(
         (0..N).map(|x| x).collect(),
-    )));
+    ))).unwrap();
     b.set_data(TokenValue::Vector(ConstantVector::U32(
         (0..N).map(|x| N - x).collect(),
-    )));
-    c.set_data(TokenValue::Vector(ConstantVector::U32(vec![0; N as usize])));
-    executable.bind("a", IO::Input, a.clone());
-    executable.bind("b", IO::Input, b.clone());
-    executable.bind("c", IO::Output, c.clone());
+    ))).unwrap();
+    c.set_data(TokenValue::Vector(ConstantVector::U32(vec![0; N as usize]))).unwrap();
+    executable.bind("a", IO::Input, a.clone()).unwrap();
+    executable.bind("b", IO::Input, b.clone()).unwrap();
+    executable.bind("c", IO::Output, c.clone()).unwrap();
     executable.run().unwrap();
     let output = get_vec_u32(c.clone());
     for i in &output {
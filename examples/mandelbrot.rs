@@ -17,11 +17,11 @@ use std::sync::Arc;
 use std::thread;
 use std::time::{Duration, Instant};
 use wyvern::core::builder::ProgramBuilder;
-use wyvern::core::executor::{Executable, Executor, Resource, IO};
+use wyvern::core::executor::IO;
 use wyvern::core::program::{ConstantVector, TokenValue};
 use wyvern::core::types::{Array, Constant, Variable};
-use wyvern::cpu::executor::CpuExecutor;
-use wyvern::vk::executor::VkExecutor;
+use wyvern::cpu::executor::{CpuConfig, CpuExecutor, CpuMode};
+use wyvern::jit::executor::{JitConfig, JitExecutor};
 
 const WIDTH: u32 = 3840;
 const HEIGHT: u32 = 2160;
@@ -33,8 +33,7 @@ const ITERATIONS: usize = 2000;
 
 enum Mode {
     Native,
-    Cpu,
-    Vk,
+    Wyvern(&'static str),
     MtNative,
     MtSimdNative,
     SimdNative,
@@ -60,7 +59,7 @@ fn get_opts() -> (Mode, u32, u32, PathBuf, usize, usize) {
                 .short("m")
                 .long("mode")
                 .help("Compute engine")
-                .possible_values(&["native", "cpu", "vulkan", "mt", "simd", "simd_mt"])
+                .possible_values(&["native", "cpu", "jit", "vulkan", "mt", "simd", "simd_mt"])
                 .required(true)
                 .takes_value(true),
         )
@@ -104,8 +103,9 @@ fn get_opts() -> (Mode, u32, u32, PathBuf, usize, usize) {
         .get_matches();
     let mode = match args.value_of("mode").unwrap() {
         "native" => Mode::Native,
-        "cpu" => Mode::Cpu,
-        "vulkan" => Mode::Vk,
+        "cpu" => Mode::Wyvern("cpu"),
+        "jit" => Mode::Wyvern("jit"),
+        "vulkan" => Mode::Wyvern("vulkan"),
         "simd_mt" => Mode::MtSimdNative,
         "simd" => Mode::SimdNative,
         "mt" => Mode::MtNative,
@@ -140,8 +140,7 @@ fn main() {
     let (data, time) = &match mode {
         Mode::Native => native(width, height, 1, iterations),
         Mode::MtNative => native(width, height, cores, iterations),
-        Mode::Cpu => cpu(width, height, iterations),
-        Mode::Vk => vk(width, height, iterations),
+        Mode::Wyvern(backend) => wyvern(backend, width, height, cores, iterations),
         Mode::SimdNative => simd(width, height, 1, iterations),
         Mode::MtSimdNative => simd(width, height, cores, iterations),
     };
@@ -263,50 +262,39 @@ fn native(width: u32, height: u32, cores: usize, iterations: usize) -> (Vec<f32>
     (v, now.elapsed())
 }
 
-fn cpu(width: u32, height: u32, iterations: usize) -> (Vec<f32>, Duration) {
+fn wyvern(
+    backend: &str,
+    width: u32,
+    height: u32,
+    cores: usize,
+    iterations: usize,
+) -> (Vec<f32>, Duration) {
     let now = Instant::now();
     let builder = ProgramBuilder::new();
     wyvern_program(&builder, iterations);
     let program = builder.finalize().unwrap();
-    let executor = CpuExecutor::new(Default::default()).unwrap();
-    let mut executable = executor.compile(program).unwrap();
-    let input = executor.new_resource().unwrap();
-    let output = executor.new_resource().unwrap();
-    input.set_data(TokenValue::Vector(ConstantVector::U32(vec![width, height])));
-    output.set_data(TokenValue::Vector(ConstantVector::F32(vec![
-        0.0;
-        (width * height)
-            as usize
-    ])));
-    executable.bind("input", IO::Input, input.clone());
-    executable.bind("output", IO::Output, output.clone());
-    executable.run().unwrap();
-    (
-        match output.get_data() {
-            TokenValue::Vector(ConstantVector::F32(x)) => x,
-            _ => unreachable!(),
-        },
-        now.elapsed(),
-    )
-}
-
-fn vk(width: u32, height: u32, iterations: usize) -> (Vec<f32>, Duration) {
-    let now = Instant::now();
-    let builder = ProgramBuilder::new();
-    wyvern_program(&builder, iterations);
-    let program = builder.finalize().unwrap();
-    let executor = VkExecutor::new(Default::default()).unwrap();
+    let mut registry = wyvern::registry();
+    registry.register_executor::<CpuExecutor, _>(
+        "cpu",
+        CpuConfig::default().mode(CpuMode::Parallel).threads(cores),
+    );
+    registry.register_executor::<JitExecutor, _>("jit", JitConfig { threads: cores });
+    let executor = registry.create(backend).unwrap();
     let mut executable = executor.compile(program).unwrap();
     let input = executor.new_resource().unwrap();
     let output = executor.new_resource().unwrap();
-    input.set_data(TokenValue::Vector(ConstantVector::U32(vec![width, height])));
-    output.set_data(TokenValue::Vector(ConstantVector::F32(vec![
-        0.0;
-        (width * height)
-            as usize
-    ])));
-    executable.bind("input", IO::Input, input.clone());
-    executable.bind("output", IO::Output, output.clone());
+    input
+        .set_data(TokenValue::Vector(ConstantVector::U32(vec![width, height])))
+        .unwrap();
+    output
+        .set_data(TokenValue::Vector(ConstantVector::F32(vec![
+            0.0;
+            (width * height)
+                as usize
+        ])))
+        .unwrap();
+    executable.bind("input", IO::Input, input).unwrap();
+    executable.bind("output", IO::Output, output.clone()).unwrap();
     executable.run().unwrap();
     (
         match output.get_data() {
@@ -14,8 +14,27 @@
 
 pub extern crate wyvern_core;
 pub extern crate wyvern_cpu;
+pub extern crate wyvern_jit;
 pub extern crate wyvern_vulkan;
 
 pub use wyvern_core as core;
 pub use wyvern_cpu as cpu;
+pub use wyvern_jit as jit;
 pub use wyvern_vulkan as vk;
+
+use wyvern_core::dynamic::{DynExecutor, Registry};
+use wyvern_cpu::executor::{CpuConfig, CpuExecutor};
+use wyvern_jit::executor::{JitConfig, JitExecutor};
+use wyvern_vulkan::executor::{VkConfig, VkExecutor};
+
+pub fn registry() -> Registry {
+    let mut registry = Registry::new();
+    registry.register("cpu", || {
+        Ok(Box::new(CpuExecutor::new(CpuConfig::from_env()?)?) as Box<dyn DynExecutor>)
+    });
+    registry.register_executor::<JitExecutor, _>("jit", JitConfig::default());
+    registry.register("vulkan", || {
+        Ok(Box::new(VkExecutor::new(VkConfig::from_env()?)?) as Box<dyn DynExecutor>)
+    });
+    registry
+}
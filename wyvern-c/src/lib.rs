@@ -5,22 +5,50 @@ extern crate serde_json;
 extern crate wyvern;
 
 use libc::{free, malloc};
+use std::cell::RefCell;
 use std::ffi::CStr;
+use std::ffi::CString;
 use std::ffi::c_void;
 use std::mem;
 use std::os::raw::c_char;
-use std::ptr::write;
+use std::ptr::{null, null_mut, write};
 use std::slice;
 use std::sync::Arc;
+use wyvern::core::error::Error;
 use wyvern::core::executor::{Executable, Executor, Resource, IO};
-use wyvern::core::program::Program;
+use wyvern::core::program::{DataType, Program};
 use wyvern::core::program::{ConstantScalar, ConstantVector, TokenValue};
 use wyvern::vk::executable::VkExecutable;
-use wyvern::vk::executor::VkExecutor;
+use wyvern::vk::executor::{VkConfig, VkExecutor};
 use wyvern::vk::resource::VkResource;
 
 pub const WYVERN_INPUT: u32 = 0;
 pub const WYVERN_OUTPUT: u32 = 1;
+pub const WYVERN_INOUT: u32 = 2;
+
+pub const WYVERN_OK: u32 = 0;
+pub const WYVERN_ERROR_INVALID_ARGUMENT: u32 = 1;
+pub const WYVERN_ERROR_VALIDATION: u32 = 2;
+pub const WYVERN_ERROR_TYPE_MISMATCH: u32 = 3;
+pub const WYVERN_ERROR_MISSING_BINDING: u32 = 4;
+pub const WYVERN_ERROR_OUT_OF_BOUNDS: u32 = 5;
+pub const WYVERN_ERROR_EXECUTION: u32 = 6;
+pub const WYVERN_ERROR_DEVICE: u32 = 7;
+pub const WYVERN_ERROR_COMPILATION: u32 = 8;
+
+pub const WYVERN_BOOL: u32 = 0;
+pub const WYVERN_INT32: u32 = 1;
+pub const WYVERN_UINT32: u32 = 2;
+pub const WYVERN_FLOAT32: u32 = 3;
+
+#[repr(C)]
+pub struct wyvern_parameter_t {
+    name: *mut c_char,
+    kind: u32,
+    data_type: u32,
+    array: u32,
+    max_size: u32,
+}
 
 #[repr(C)]
 pub struct wyvern_data_array_uint32_t {
@@ -40,14 +68,70 @@ pub struct wyvern_data_array_float_t {
     data: *const f32,
 }
 
+pub type wyvern_status_t = u32;
 pub type wyvern_vk_executor_t = c_void;
 pub type wyvern_vk_executable_t = c_void;
 pub type wyvern_vk_resource_t = c_void;
 
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = RefCell::new(None);
+}
+
+fn fail(status: wyvern_status_t, message: String) -> wyvern_status_t {
+    let message = CString::new(message.replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|x| *x.borrow_mut() = Some(message));
+    status
+}
+
+fn report(error: Error) -> wyvern_status_t {
+    let status = match error {
+        Error::Validation(_) => WYVERN_ERROR_VALIDATION,
+        Error::TypeMismatch(_) => WYVERN_ERROR_TYPE_MISMATCH,
+        Error::MissingBinding(_, _) => WYVERN_ERROR_MISSING_BINDING,
+        Error::OutOfBounds(_) => WYVERN_ERROR_OUT_OF_BOUNDS,
+        Error::Execution(_) => WYVERN_ERROR_EXECUTION,
+        Error::Device(_) => WYVERN_ERROR_DEVICE,
+        Error::Compilation(_) => WYVERN_ERROR_COMPILATION,
+    };
+    fail(status, error.to_string())
+}
+
+unsafe fn to_str<'a>(s: *const c_char) -> Result<&'a str, wyvern_status_t> {
+    CStr::from_ptr(s)
+        .to_str()
+        .map_err(|x| fail(WYVERN_ERROR_INVALID_ARGUMENT, x.to_string()))
+}
+
+fn to_io(kind: u32) -> Result<IO, wyvern_status_t> {
+    match kind {
+        WYVERN_INPUT => Ok(IO::Input),
+        WYVERN_OUTPUT => Ok(IO::Output),
+        WYVERN_INOUT => Ok(IO::InOut),
+        _ => Err(fail(
+            WYVERN_ERROR_INVALID_ARGUMENT,
+            format!("Invalid IO kind {}", kind),
+        )),
+    }
+}
+
+fn wrong_type() -> wyvern_status_t {
+    fail(WYVERN_ERROR_TYPE_MISMATCH, "Wrong type requested".into())
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn wyvern_last_error() -> *const c_char {
+    LAST_ERROR.with(|x| x.borrow().as_ref().map_or(null(), |x| x.as_ptr()))
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn wyvern_vk_executor_new() -> *mut wyvern_vk_executor_t {
-    let executor = Box::new(VkExecutor::new(Default::default()).unwrap());
-    Box::into_raw(executor) as *mut c_void
+    match VkConfig::from_env().and_then(VkExecutor::new) {
+        Ok(executor) => Box::into_raw(Box::new(executor)) as *mut c_void,
+        Err(e) => {
+            report(e);
+            null_mut()
+        }
+    }
 }
 
 #[no_mangle]
@@ -61,10 +145,24 @@ pub unsafe extern "C" fn wyvern_vk_executable_new(
     source: *const c_char,
 ) -> *mut wyvern_vk_executable_t {
     let obj = &mut *(obj as *mut VkExecutor);
-    let source = CStr::from_ptr(source).to_str().unwrap();
-    let program: Program = serde_json::from_str(source).unwrap();
-    let executable = Box::new(obj.compile(program).unwrap());
-    Box::into_raw(executable) as *mut c_void
+    let source = match to_str(source) {
+        Ok(x) => x,
+        Err(_) => return null_mut(),
+    };
+    let program: Program = match serde_json::from_str(source) {
+        Ok(x) => x,
+        Err(e) => {
+            fail(WYVERN_ERROR_INVALID_ARGUMENT, e.to_string());
+            return null_mut();
+        }
+    };
+    match obj.compile(program) {
+        Ok(executable) => Box::into_raw(Box::new(executable)) as *mut c_void,
+        Err(e) => {
+            report(e);
+            null_mut()
+        }
+    }
 }
 
 #[no_mangle]
@@ -75,8 +173,13 @@ pub unsafe extern "C" fn wyvern_vk_executable_destroy(obj: *mut wyvern_vk_execut
 #[no_mangle]
 pub unsafe extern "C" fn wyvern_vk_resource_new(obj: *mut wyvern_vk_executor_t) -> *mut wyvern_vk_resource_t {
     let obj = &mut *(obj as *mut VkExecutor);
-    let resource = Box::new(obj.new_resource().unwrap());
-    Box::into_raw(resource) as *mut c_void
+    match obj.new_resource() {
+        Ok(resource) => Box::into_raw(Box::new(resource)) as *mut c_void,
+        Err(e) => {
+            report(e);
+            null_mut()
+        }
+    }
 }
 
 #[no_mangle]
@@ -90,52 +193,135 @@ pub unsafe extern "C" fn wyvern_vk_executable_bind(
     name: *const c_char,
     kind: u32,
     resource: *mut wyvern_vk_resource_t,
-) {
+) -> wyvern_status_t {
     let obj = &mut *(obj as *mut VkExecutable);
-    let name = CStr::from_ptr(name).to_str().unwrap();
-    let kind = match kind {
-        WYVERN_INPUT => IO::Input,
-        WYVERN_OUTPUT => IO::Output,
-        _ => panic!(),
+    let name = match to_str(name) {
+        Ok(x) => x,
+        Err(status) => return status,
+    };
+    let kind = match to_io(kind) {
+        Ok(x) => x,
+        Err(status) => return status,
     };
     let resource = &mut *(resource as *mut Arc<VkResource>);
-    obj.bind(name, kind, resource.clone());
+    match obj.bind(name, kind, resource.clone()) {
+        Ok(_) => WYVERN_OK,
+        Err(e) => report(e),
+    }
 }
 
 #[no_mangle]
-pub unsafe extern "C" fn wyvern_vk_executable_unbind(obj: *mut wyvern_vk_executable_t, name: *const c_char, kind: u32) {
+pub unsafe extern "C" fn wyvern_vk_executable_unbind(
+    obj: *mut wyvern_vk_executable_t,
+    name: *const c_char,
+    kind: u32,
+) -> wyvern_status_t {
     let obj = &mut *(obj as *mut VkExecutable);
-    let name = CStr::from_ptr(name).to_str().unwrap();
-    let kind = match kind {
-        WYVERN_INPUT => IO::Input,
-        WYVERN_OUTPUT => IO::Output,
-        _ => panic!("Invalid constant!"),
+    let name = match to_str(name) {
+        Ok(x) => x,
+        Err(status) => return status,
+    };
+    let kind = match to_io(kind) {
+        Ok(x) => x,
+        Err(status) => return status,
     };
     obj.unbind(name, kind);
+    WYVERN_OK
 }
 
 #[no_mangle]
-pub unsafe extern "C" fn wyvern_vk_executable_run(obj: *mut wyvern_vk_executable_t) {
+pub unsafe extern "C" fn wyvern_vk_executable_num_parameters(obj: *mut wyvern_vk_executable_t) -> u32 {
     let obj = &mut *(obj as *mut VkExecutable);
-    obj.run().unwrap();
+    obj.parameters().len() as u32
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn wyvern_vk_executable_get_parameter(
+    obj: *mut wyvern_vk_executable_t,
+    index: u32,
+) -> *mut wyvern_parameter_t {
+    let obj = &mut *(obj as *mut VkExecutable);
+    let mut parameters = obj.parameters();
+    if index as usize >= parameters.len() {
+        fail(
+            WYVERN_ERROR_OUT_OF_BOUNDS,
+            format!(
+                "Parameter index {} out of bounds for {} parameters",
+                index,
+                parameters.len()
+            ),
+        );
+        return null_mut();
+    }
+    let parameter = parameters.swap_remove(index as usize);
+    let name = match CString::new(parameter.name) {
+        Ok(x) => x,
+        Err(e) => {
+            fail(WYVERN_ERROR_INVALID_ARGUMENT, e.to_string());
+            return null_mut();
+        }
+    };
+    let param = wyvern_parameter_t {
+        name: name.into_raw(),
+        kind: match parameter.kind {
+            IO::Input => WYVERN_INPUT,
+            IO::Output => WYVERN_OUTPUT,
+            IO::InOut => WYVERN_INOUT,
+        },
+        data_type: match parameter.data_type {
+            DataType::Bool => WYVERN_BOOL,
+            DataType::I32 => WYVERN_INT32,
+            DataType::U32 => WYVERN_UINT32,
+            DataType::F32 => WYVERN_FLOAT32,
+        },
+        array: parameter.array as u32,
+        max_size: parameter.max_size.unwrap_or(0),
+    };
+    let param_p = malloc(mem::size_of::<wyvern_parameter_t>()) as *mut wyvern_parameter_t;
+    write(param_p, param);
+    param_p
 }
 
 #[no_mangle]
-pub unsafe extern "C" fn wyvern_vk_resource_set_data_uint32(obj: *mut wyvern_vk_resource_t, data: u32) {
+pub unsafe extern "C" fn wyvern_vk_parameter_free(data: *mut wyvern_parameter_t) {
+    CString::from_raw((&mut *data).name);
+    free(data as *mut libc::c_void);
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn wyvern_vk_executable_run(obj: *mut wyvern_vk_executable_t) -> wyvern_status_t {
+    let obj = &mut *(obj as *mut VkExecutable);
+    match obj.run() {
+        Ok(_) => WYVERN_OK,
+        Err(e) => report(e),
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn wyvern_vk_resource_set_data_uint32(obj: *mut wyvern_vk_resource_t, data: u32) -> wyvern_status_t {
     let obj = &mut *(obj as *mut Arc<VkResource>);
-    obj.set_data(TokenValue::Scalar(ConstantScalar::U32(data)));
+    match obj.set_data(TokenValue::Scalar(ConstantScalar::U32(data))) {
+        Ok(_) => WYVERN_OK,
+        Err(e) => report(e),
+    }
 }
 
 #[no_mangle]
-pub unsafe extern "C" fn wyvern_vk_resource_set_data_int32(obj: *mut wyvern_vk_resource_t, data: i32) {
+pub unsafe extern "C" fn wyvern_vk_resource_set_data_int32(obj: *mut wyvern_vk_resource_t, data: i32) -> wyvern_status_t {
     let obj = &mut *(obj as *mut Arc<VkResource>);
-    obj.set_data(TokenValue::Scalar(ConstantScalar::I32(data)));
+    match obj.set_data(TokenValue::Scalar(ConstantScalar::I32(data))) {
+        Ok(_) => WYVERN_OK,
+        Err(e) => report(e),
+    }
 }
 
 #[no_mangle]
-pub unsafe extern "C" fn wyvern_vk_resource_set_data_float32(obj: *mut wyvern_vk_resource_t, data: f32) {
+pub unsafe extern "C" fn wyvern_vk_resource_set_data_float32(obj: *mut wyvern_vk_resource_t, data: f32) -> wyvern_status_t {
     let obj = &mut *(obj as *mut Arc<VkResource>);
-    obj.set_data(TokenValue::Scalar(ConstantScalar::F32(data)));
+    match obj.set_data(TokenValue::Scalar(ConstantScalar::F32(data))) {
+        Ok(_) => WYVERN_OK,
+        Err(e) => report(e),
+    }
 }
 
 #[no_mangle]
@@ -143,10 +329,13 @@ pub unsafe extern "C" fn wyvern_vk_resource_set_data_array_uint32(
     obj: *mut wyvern_vk_resource_t,
     data: *const u32,
     n_elem: u32,
-) {
+) -> wyvern_status_t {
     let obj = &mut *(obj as *mut Arc<VkResource>);
     let data = slice::from_raw_parts(data, n_elem as usize);
-    obj.set_data(TokenValue::Vector(ConstantVector::U32(data.to_vec())));
+    match obj.set_data(TokenValue::Vector(ConstantVector::U32(data.to_vec()))) {
+        Ok(_) => WYVERN_OK,
+        Err(e) => report(e),
+    }
 }
 
 #[no_mangle]
@@ -154,10 +343,13 @@ pub unsafe extern "C" fn wyvern_vk_resource_set_data_array_int32(
     obj: *mut wyvern_vk_resource_t,
     data: *const i32,
     n_elem: u32,
-) {
+) -> wyvern_status_t {
     let obj = &mut *(obj as *mut Arc<VkResource>);
     let data = slice::from_raw_parts(data, n_elem as usize);
-    obj.set_data(TokenValue::Vector(ConstantVector::I32(data.to_vec())));
+    match obj.set_data(TokenValue::Vector(ConstantVector::I32(data.to_vec()))) {
+        Ok(_) => WYVERN_OK,
+        Err(e) => report(e),
+    }
 }
 
 #[no_mangle]
@@ -165,39 +357,57 @@ pub unsafe extern "C" fn wyvern_vk_resource_set_data_array_float32(
     obj: *mut wyvern_vk_resource_t,
     data: *const f32,
     n_elem: u32,
-) {
+) -> wyvern_status_t {
     let obj = &mut *(obj as *mut Arc<VkResource>);
     let data = slice::from_raw_parts(data, n_elem as usize);
-    obj.set_data(TokenValue::Vector(ConstantVector::F32(data.to_vec())));
+    match obj.set_data(TokenValue::Vector(ConstantVector::F32(data.to_vec()))) {
+        Ok(_) => WYVERN_OK,
+        Err(e) => report(e),
+    }
 }
 
 #[no_mangle]
-pub unsafe extern "C" fn wyvern_vk_resource_get_data_uint32(obj: *mut wyvern_vk_resource_t) -> u32 {
+pub unsafe extern "C" fn wyvern_vk_resource_get_data_uint32(
+    obj: *mut wyvern_vk_resource_t,
+    data: *mut u32,
+) -> wyvern_status_t {
     let obj = &mut *(obj as *mut Arc<VkResource>);
-    if let TokenValue::Scalar(ConstantScalar::U32(value)) = obj.get_data() {
-        value
-    } else {
-        panic!("Wrong type requested!");
+    match obj.get_data() {
+        TokenValue::Scalar(ConstantScalar::U32(value)) => {
+            write(data, value);
+            WYVERN_OK
+        }
+        _ => wrong_type(),
     }
 }
 
 #[no_mangle]
-pub unsafe extern "C" fn wyvern_vk_resource_get_data_int32(obj: *mut wyvern_vk_resource_t) -> i32 {
+pub unsafe extern "C" fn wyvern_vk_resource_get_data_int32(
+    obj: *mut wyvern_vk_resource_t,
+    data: *mut i32,
+) -> wyvern_status_t {
     let obj = &mut *(obj as *mut Arc<VkResource>);
-    if let TokenValue::Scalar(ConstantScalar::I32(value)) = obj.get_data() {
-        value
-    } else {
-        panic!("Wrong type requested!");
+    match obj.get_data() {
+        TokenValue::Scalar(ConstantScalar::I32(value)) => {
+            write(data, value);
+            WYVERN_OK
+        }
+        _ => wrong_type(),
     }
 }
 
 #[no_mangle]
-pub unsafe extern "C" fn wyvern_vk_resource_get_data_float32(obj: *mut wyvern_vk_resource_t) -> f32 {
+pub unsafe extern "C" fn wyvern_vk_resource_get_data_float32(
+    obj: *mut wyvern_vk_resource_t,
+    data: *mut f32,
+) -> wyvern_status_t {
     let obj = &mut *(obj as *mut Arc<VkResource>);
-    if let TokenValue::Scalar(ConstantScalar::F32(value)) = obj.get_data() {
-        value
-    } else {
-        panic!("Wrong type requested!");
+    match obj.get_data() {
+        TokenValue::Scalar(ConstantScalar::F32(value)) => {
+            write(data, value);
+            WYVERN_OK
+        }
+        _ => wrong_type(),
     }
 }
 
@@ -217,7 +427,8 @@ pub unsafe extern "C" fn wyvern_vk_resource_get_data_array_uint32(obj: *mut wyve
         write(arr_p, arr);
         arr_p
     } else {
-        panic!("Wrong type requested!");
+        wrong_type();
+        null_mut()
     }
 }
 
@@ -237,7 +448,8 @@ pub unsafe extern "C" fn wyvern_vk_resource_get_data_array_int32(obj: *mut wyver
         write(arr_p, arr);
         arr_p
     } else {
-        panic!("Wrong type requested!");
+        wrong_type();
+        null_mut()
     }
 }
 
@@ -257,7 +469,8 @@ pub unsafe extern "C" fn wyvern_vk_resource_get_data_array_float32(obj: *mut wyv
         write(arr_p, arr);
         arr_p
     } else {
-        panic!("Wrong type requested!");
+        wrong_type();
+        null_mut()
     }
 }
 
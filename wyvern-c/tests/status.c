@@ -0,0 +1,90 @@
+#include <stdlib.h>
+#include <stdio.h>
+#include <string.h>
+#include "wyvern.h"
+
+const char* program = ""
+"{\"symbol\":{\"0\":{\"Constant\":\"U32\"},\"1\":{\"Variable\":\"U32\"},"
+"\"2\":{\"Array\":\"U32\"},\"3\":{\"Array\":\"U32\"},\"4\":{\"Constant\":\"U32\"},"
+"\"5\":{\"ArrayPointer\":\"U32\"},\"6\":{\"ArrayPointer\":\"U32\"},"
+"\"7\":{\"Constant\":\"U32\"},\"8\":{\"Constant\":\"U32\"},\"9\":{\"Constant\":\"U32\"}},"
+"\"operation\":[{\"Constant\":[0,{\"U32\":0}]},{\"ArrayNew\":[2,0,\"U32\",4,true]},"
+"{\"ArrayNew\":[3,0,\"U32\",4,true]},{\"WorkerId\":4},{\"ArrayLoad\":[7,2,4]},"
+"{\"Load\":[8,1]},{\"Add\":[9,7,8]},{\"ArrayStore\":[3,4,9]}],"
+"\"storage\":{\"1\":{\"Variable\":\"U32\"},\"2\":{\"SharedArray\":[\"U32\",4]},"
+"\"3\":{\"SharedArray\":[\"U32\",4]}},"
+"\"input\":{\"a\":2,\"k\":1},\"output\":{},\"inout\":{\"out\":3}}";
+
+void expect(wyvern_status_t found, wyvern_status_t expected, const char* what) {
+    if(found != expected) {
+        const char* error = wyvern_last_error();
+        fprintf(stderr, "%s: expected status %u, found %u (%s)\n", what, expected, found,
+                error ? error : "no error");
+        exit(1);
+    }
+}
+
+void check(int condition, const char* what) {
+    if(!condition) {
+        fprintf(stderr, "%s failed\n", what);
+        exit(1);
+    }
+}
+
+int main() {
+    const uint32_t four[] = {1, 2, 3, 4};
+    const uint32_t five[] = {1, 2, 3, 4, 5};
+    const int32_t signed_four[] = {1, 2, 3, 4};
+    uint32_t value;
+    wyvern_vk_executor_t* executor = wyvern_vk_executor_new();
+    if(!executor) {
+        fprintf(stderr, "No Vulkan device: %s\n", wyvern_last_error());
+        return 1;
+    }
+    check(wyvern_vk_executable_new(executor, "{") == NULL, "compile of invalid JSON");
+    check(wyvern_last_error() != NULL, "error message of invalid JSON");
+    wyvern_vk_executable_t* executable = wyvern_vk_executable_new(executor, program);
+    check(executable != NULL, "compile");
+    check(wyvern_vk_executable_num_parameters(executable) == 3, "parameter count");
+    wyvern_parameter_t* parameter = wyvern_vk_executable_get_parameter(executable, 2);
+    check(parameter != NULL, "parameter lookup");
+    check(strcmp(parameter->name, "out") == 0, "parameter name");
+    check(parameter->kind == WYVERN_INOUT, "parameter kind");
+    check(parameter->data_type == WYVERN_UINT32, "parameter data type");
+    check(parameter->array == 1 && parameter->max_size == 4, "parameter size");
+    wyvern_vk_parameter_free(parameter);
+    check(wyvern_vk_executable_get_parameter(executable, 3) == NULL, "parameter out of range");
+    wyvern_vk_resource_t* k = wyvern_vk_resource_new(executor);
+    wyvern_vk_resource_t* a = wyvern_vk_resource_new(executor);
+    expect(wyvern_vk_resource_set_data_uint32(k, 3), WYVERN_OK, "set k");
+    expect(wyvern_vk_executable_bind(executable, "nope", WYVERN_INPUT, k),
+           WYVERN_ERROR_VALIDATION, "bind of an unknown name");
+    expect(wyvern_vk_executable_bind(executable, "k", 7, k),
+           WYVERN_ERROR_INVALID_ARGUMENT, "bind with an invalid kind");
+    expect(wyvern_vk_resource_set_data_array_int32(a, signed_four, 4), WYVERN_OK, "set a");
+    expect(wyvern_vk_executable_bind(executable, "a", WYVERN_INPUT, a),
+           WYVERN_ERROR_TYPE_MISMATCH, "bind of a signed array");
+    expect(wyvern_vk_resource_set_data_array_uint32(a, five, 5), WYVERN_OK, "set a");
+    expect(wyvern_vk_executable_bind(executable, "a", WYVERN_INPUT, a),
+           WYVERN_ERROR_OUT_OF_BOUNDS, "bind of an oversized array");
+    expect(wyvern_vk_resource_set_data_array_uint32(a, four, 4), WYVERN_OK, "set a");
+    expect(wyvern_vk_executable_bind(executable, "a", WYVERN_INPUT, a), WYVERN_OK, "bind a");
+    expect(wyvern_vk_executable_bind(executable, "k", WYVERN_INPUT, k), WYVERN_OK, "bind k");
+    expect(wyvern_vk_executable_run(executable), WYVERN_ERROR_MISSING_BINDING,
+           "run without out");
+    wyvern_vk_resource_t* out = wyvern_vk_resource_new(executor);
+    expect(wyvern_vk_executable_bind(executable, "out", WYVERN_INOUT, out), WYVERN_OK,
+           "bind of an empty out");
+    expect(wyvern_vk_executable_run(executable), WYVERN_ERROR_TYPE_MISMATCH,
+           "run with an empty out");
+    expect(wyvern_vk_resource_get_data_uint32(a, &value), WYVERN_ERROR_TYPE_MISMATCH,
+           "scalar read of an array");
+    expect(wyvern_vk_resource_get_data_uint32(k, &value), WYVERN_OK, "read k");
+    check(value == 3, "value of k");
+    wyvern_vk_resource_destroy(out);
+    wyvern_vk_resource_destroy(a);
+    wyvern_vk_resource_destroy(k);
+    wyvern_vk_executable_destroy(executable);
+    wyvern_vk_executor_destroy(executor);
+    return 0;
+}
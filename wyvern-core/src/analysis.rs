@@ -0,0 +1,108 @@
+// Copyright 2018 | Dario Ostuni <dario.ostuni@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use program::{Op, Program, TokenId};
+use std::collections::HashSet;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UninitializedLoad {
+    pub op: u32,
+    pub token: TokenId,
+}
+
+struct Analysis<'a> {
+    program: &'a Program,
+    next_op: u32,
+    loads: Vec<UninitializedLoad>,
+}
+
+pub fn uninitialized_loads(program: &Program) -> Vec<UninitializedLoad> {
+    let mut analysis = Analysis {
+        program,
+        next_op: 0,
+        loads: Vec::new(),
+    };
+    let mut initialized: HashSet<TokenId> = program
+        .input
+        .values()
+        .chain(program.output.values())
+        .chain(program.inout.values())
+        .cloned()
+        .collect();
+    analysis.block(&program.operation, &mut initialized);
+    analysis.loads
+}
+
+impl<'a> Analysis<'a> {
+    fn block(&mut self, block: &[Op], initialized: &mut HashSet<TokenId>) {
+        for op in block {
+            self.op(op, initialized);
+        }
+    }
+
+    fn op(&mut self, op: &Op, initialized: &mut HashSet<TokenId>) {
+        let id = self.next_op;
+        self.next_op += 1;
+        match *op {
+            Op::If(ref c, _, _, ref a, _) => {
+                self.block(c, initialized);
+                let mut then = initialized.clone();
+                self.block(a, &mut then);
+                initialized.extend(then);
+            }
+            Op::IfElse(ref c, _, _, ref a, _, ref b, _) => {
+                self.block(c, initialized);
+                let mut then = initialized.clone();
+                self.block(a, &mut then);
+                self.block(b, initialized);
+                initialized.extend(then);
+            }
+            Op::While(_, ref c, _, _, ref a, _) => {
+                writes(c, initialized);
+                writes(a, initialized);
+                self.block(c, initialized);
+                self.block(a, initialized);
+            }
+            Op::Load(_, v) | Op::ArrayLoad(_, v, _)
+                if self.program.storage.contains_key(&v) && !initialized.contains(&v) =>
+            {
+                self.loads.push(UninitializedLoad { op: id, token: v });
+            }
+            Op::Store(v, _) | Op::ArrayStore(v, _, _) => {
+                initialized.insert(v);
+            }
+            _ => {}
+        }
+    }
+}
+
+fn writes(block: &[Op], initialized: &mut HashSet<TokenId>) {
+    for op in block {
+        match *op {
+            Op::If(ref c, _, _, ref a, _) | Op::While(_, ref c, _, _, ref a, _) => {
+                writes(c, initialized);
+                writes(a, initialized);
+            }
+            Op::IfElse(ref c, _, _, ref a, _, ref b, _) => {
+                writes(c, initialized);
+                writes(a, initialized);
+                writes(b, initialized);
+            }
+            Op::Store(v, _) | Op::ArrayStore(v, _, _) => {
+                initialized.insert(v);
+            }
+            _ => {}
+        }
+    }
+}
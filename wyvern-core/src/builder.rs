@@ -41,6 +41,7 @@ pub(crate) enum WorkerMessage {
     AddOperation(Op),
     MarkInput(TokenId, String),
     MarkOutput(TokenId, String),
+    MarkInOut(TokenId, String),
     NextLabel(SyncSender<LabelId>),
 }
 
@@ -67,13 +68,17 @@ impl<'a> ProgramBuilder {
         self.add_operation(Op::ControlBarrier);
     }
 
+    pub fn global_barrier(&self) {
+        self.add_operation(Op::GlobalBarrier);
+    }
+
     pub fn worker_id(&'a self) -> Constant<'a, u32> {
         let result = Constant::generate(self);
         self.add_operation(Op::WorkerId(result.info.token.id));
         result
     }
 
-    pub fn if_then<T: Fn(&ProgramBuilder) -> Constant<'a, bool>, U: Fn(&ProgramBuilder) -> ()>(
+    pub fn if_then<T: Fn(&ProgramBuilder) -> Constant<'a, bool>, U: Fn(&ProgramBuilder)>(
         &self,
         cond: T,
         body: U,
@@ -99,8 +104,8 @@ impl<'a> ProgramBuilder {
 
     pub fn if_then_else<
         T: Fn(&ProgramBuilder) -> Constant<'a, bool>,
-        U: Fn(&ProgramBuilder) -> (),
-        V: Fn(&ProgramBuilder) -> (),
+        U: Fn(&ProgramBuilder),
+        V: Fn(&ProgramBuilder),
     >(
         &self,
         cond: T,
@@ -135,7 +140,7 @@ impl<'a> ProgramBuilder {
 
     pub fn while_loop<
         T: Fn(&ProgramBuilder) -> Constant<'a, bool>,
-        U: Fn(&ProgramBuilder) -> (),
+        U: Fn(&ProgramBuilder),
     >(
         &self,
         cond: T,
@@ -265,6 +270,9 @@ fn worker(queue: Receiver<WorkerMessage>) -> Program {
             WorkerMessage::MarkOutput(id, name) => {
                 prog.output.insert(name, id);
             }
+            WorkerMessage::MarkInOut(id, name) => {
+                prog.inout.insert(name, id);
+            }
             WorkerMessage::NextLabel(tx) => {
                 tx.send(label_id.next()).unwrap();
             }
@@ -0,0 +1,1173 @@
+// Copyright 2018 | Dario Ostuni <dario.ostuni@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use builder::ProgramBuilder;
+use error::Error;
+use executor::{Executable, Executor, Overrides, Report, Resource, WorkSize, IO};
+use pipeline::Pipeline;
+use program::{
+    ConstantScalar, ConstantVector, DataType, Parameter, Program, TokenType, TokenValue,
+};
+use semantics;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll, Waker};
+use std::thread;
+use submission::Submission;
+use types::{Array, Constant, Variable};
+
+pub fn work_size<E>(executor: &E)
+where
+    E: Executor<Error = Error>,
+    E::Executable: Executable<Resource = E::Resource, Error = Error, Report = Report>,
+{
+    let b = ProgramBuilder::new();
+    Variable::<u32>::new(&b)
+        .mark_as_output("n")
+        .store(b.num_workers());
+    let mut executable = executor.compile(b.finalize().unwrap()).unwrap();
+    let n = executor.new_resource().unwrap();
+    executable.bind("n", IO::Output, n.clone()).unwrap();
+    let default = executable.work_size();
+    let report = executable.run().unwrap();
+    assert_eq!(Some(report.workers), default.num_workers());
+    assert_eq!(report.work_size, default);
+    assert_eq!(n.get_data(), u32_scalar(report.workers));
+    for &work_size in &[
+        WorkSize::new(1, 1),
+        WorkSize::new(3, 2),
+        WorkSize::new(2, 8),
+    ] {
+        executable.set_work_size(Some(work_size));
+        assert_eq!(executable.work_size(), work_size);
+        let report = executable.run().unwrap();
+        assert_eq!(report.work_size, work_size);
+        assert_eq!(n.get_data(), u32_scalar(work_size.num_workers().unwrap()));
+    }
+    executable.set_work_size(None);
+    assert_eq!(executable.work_size(), default);
+    executable.set_work_size(Some(WorkSize::new(0, 4)));
+    match executable.run() {
+        Err(Error::Validation(_)) => {}
+        x => panic!("Expected a validation error, got {:?}", x),
+    }
+}
+
+pub fn control_flow<E>(executor: &E)
+where
+    E: Executor<Error = Error>,
+    E::Executable: Executable<Resource = E::Resource, Error = Error, Report = Report>,
+{
+    let b = ProgramBuilder::new();
+    let zero = Constant::new(0_u32, &b);
+    let one = Constant::new(1_u32, &b);
+    let two = Constant::new(2_u32, &b);
+    let a = Array::<u32>::new(zero, 0, true, &b).mark_as_inout("a");
+    let f = Array::<f32>::new(zero, 0, true, &b).mark_as_inout("f");
+    let private = Array::<u32>::new(Constant::new(4_u32, &b), 4, false, &b);
+    let id = b.worker_id();
+    let i = Variable::<u32>::new(&b);
+    let sum = Variable::<u32>::new(&b);
+    i.store(zero);
+    sum.store(zero);
+    b.while_loop(
+        |_| i.load().le(id),
+        |_| {
+            sum.store(sum.load() + i.load());
+            private
+                .at(i.load() % Constant::new(4_u32, &b))
+                .store(i.load());
+            i.store(i.load() + one);
+        },
+    );
+    b.if_then_else(
+        |_| (id % two).eq(zero),
+        |_| a.at(id).store(sum.load()),
+        |_| {
+            a.at(id)
+                .store(sum.load() * Constant::new(10_u32, &b) + private.at(one).load())
+        },
+    );
+    f.at(id)
+        .store(Constant::from(sum.load()) / Constant::new(2.0_f32, &b));
+    let mut executable = executor.compile(b.finalize().unwrap()).unwrap();
+    executable.set_work_size(Some(WorkSize::new(3, 5)));
+    let n = 15;
+    let a = executor.new_resource().unwrap();
+    let f = executor.new_resource().unwrap();
+    a.write_slice(&vec![0_u32; n]).unwrap();
+    f.write_slice(&vec![0_f32; n]).unwrap();
+    executable.bind("a", IO::InOut, a.clone()).unwrap();
+    executable.bind("f", IO::InOut, f.clone()).unwrap();
+    executable.run().unwrap();
+    let sums: Vec<u32> = (0..n as u32).map(|i| i * (i + 1) / 2).collect();
+    let expected: Vec<u32> = (0..n as u32)
+        .map(|i| {
+            let sum = sums[i as usize];
+            if i % 2 == 0 {
+                sum
+            } else {
+                sum * 10 + (0..=i).filter(|x| x % 4 == 1).max().unwrap()
+            }
+        })
+        .collect();
+    assert_eq!(a.map(|x: &[u32]| x.to_vec()).unwrap(), expected);
+    let halves: Vec<f32> = sums.iter().map(|&x| x as f32 / 2.0).collect();
+    assert_eq!(f.map(|x: &[f32]| x.to_vec()).unwrap(), halves);
+}
+
+pub fn semantics<E>(executor: &E)
+where
+    E: Executor<Error = Error>,
+    E::Executable: Executable<Resource = E::Resource, Error = Error, Report = Report>,
+{
+    let ua = [7, 7, u32::MAX, 1, 0, 5];
+    let ub = [2, 0, 1, 33, 0, 32];
+    let ia = [7, -7, i32::MIN, i32::MIN, 9, i32::MAX];
+    let ib = [2, 0, -1, 1, -4, 1];
+    let fa = [3.7, -1.0, f32::NAN, 5e9, -5e9, 7.5];
+    let fb = [0.0, 2.0, 1.0, 1.0, 3.0, 2.0];
+    let b = ProgramBuilder::new();
+    let zero = Constant::new(0_u32, &b);
+    let array = |name: &str| Array::<u32>::new(zero, 0, true, &b).mark_as_inout(name);
+    let (ua_, ub_) = (array("ua"), array("ub"));
+    let ia_ = Array::<i32>::new(zero, 0, true, &b).mark_as_inout("ia");
+    let ib_ = Array::<i32>::new(zero, 0, true, &b).mark_as_inout("ib");
+    let fa_ = Array::<f32>::new(zero, 0, true, &b).mark_as_inout("fa");
+    let fb_ = Array::<f32>::new(zero, 0, true, &b).mark_as_inout("fb");
+    let u = array("u");
+    let i = Array::<i32>::new(zero, 0, true, &b).mark_as_inout("i");
+    let f = Array::<f32>::new(zero, 0, true, &b).mark_as_inout("f");
+    let id = b.worker_id();
+    let at = |offset: u32| id * Constant::new(8_u32, &b) + Constant::new(offset, &b);
+    let (x, y) = (ua_.at(id).load(), ub_.at(id).load());
+    u.at(at(0)).store(x / y);
+    u.at(at(1)).store(x % y);
+    u.at(at(2)).store(x << y);
+    u.at(at(3)).store(x >> y);
+    u.at(at(4)).store(x + y);
+    u.at(at(5)).store(x.saturating_add(y));
+    u.at(at(6)).store(Constant::from(fa_.at(id).load()));
+    u.at(at(7)).store(x * y);
+    let (x, y) = (ia_.at(id).load(), ib_.at(id).load());
+    i.at(at(0)).store(x / y);
+    i.at(at(1)).store(x % y);
+    i.at(at(2)).store(x >> Constant::new(31_u32, &b));
+    i.at(at(3)).store(x - y);
+    i.at(at(4)).store(x.saturating_sub(y));
+    i.at(at(5)).store(Constant::from(fa_.at(id).load()));
+    i.at(at(6)).store(-x);
+    i.at(at(7)).store(x.saturating_mul(y));
+    let (x, y) = (fa_.at(id).load(), fb_.at(id).load());
+    f.at(at(0)).store(x / y);
+    f.at(at(1)).store(x % y);
+    f.at(at(2)).store(x * y);
+    f.at(at(3)).store(Constant::from(ia_.at(id).load()));
+    let mut executable = executor.compile(b.finalize().unwrap()).unwrap();
+    executable.set_work_size(Some(WorkSize::new(2, 3)));
+    let bind = |executable: &mut E::Executable, name: &str, resource: Arc<E::Resource>| {
+        executable.bind(name, IO::InOut, resource).unwrap();
+    };
+    let inputs = [("ua", &ua[..]), ("ub", &ub[..])];
+    for &(name, data) in &inputs {
+        let resource = executor.new_resource().unwrap();
+        resource.write_slice(data).unwrap();
+        bind(&mut executable, name, resource);
+    }
+    for &(name, data) in &[("ia", &ia[..]), ("ib", &ib[..])] {
+        let resource = executor.new_resource().unwrap();
+        resource.write_slice(data).unwrap();
+        bind(&mut executable, name, resource);
+    }
+    for &(name, data) in &[("fa", &fa[..]), ("fb", &fb[..])] {
+        let resource = executor.new_resource().unwrap();
+        resource.write_slice(data).unwrap();
+        bind(&mut executable, name, resource);
+    }
+    let (u, i, f) = (
+        executor.new_resource().unwrap(),
+        executor.new_resource().unwrap(),
+        executor.new_resource().unwrap(),
+    );
+    u.write_slice(&[0_u32; 48]).unwrap();
+    i.write_slice(&[0_i32; 48]).unwrap();
+    f.write_slice(&[0_f32; 48]).unwrap();
+    bind(&mut executable, "u", u.clone());
+    bind(&mut executable, "i", i.clone());
+    bind(&mut executable, "f", f.clone());
+    executable.run().unwrap();
+    let u = u.map(|x: &[u32]| x.to_vec()).unwrap();
+    let i = i.map(|x: &[i32]| x.to_vec()).unwrap();
+    let f = f.map(|x: &[f32]| x.to_vec()).unwrap();
+    for w in 0..6 {
+        let (x, y) = (ua[w], ub[w]);
+        let expected = [
+            semantics::div_u32(x, y),
+            semantics::rem_u32(x, y),
+            semantics::shl_u32(x, y),
+            semantics::shr_u32(x, y),
+            x.wrapping_add(y),
+            x.saturating_add(y),
+            semantics::u32_from_f32(fa[w]),
+            x.wrapping_mul(y),
+        ];
+        assert_eq!(&u[w * 8..w * 8 + 8], &expected[..], "u32 case {}", w);
+        let (x, y) = (ia[w], ib[w]);
+        let expected = [
+            semantics::div_i32(x, y),
+            semantics::rem_i32(x, y),
+            semantics::shr_i32(x, 31),
+            x.wrapping_sub(y),
+            x.saturating_sub(y),
+            semantics::i32_from_f32(fa[w]),
+            x.wrapping_neg(),
+            x.saturating_mul(y),
+        ];
+        assert_eq!(&i[w * 8..w * 8 + 8], &expected[..], "i32 case {}", w);
+        let (x, y) = (fa[w], fb[w]);
+        let expected = [x / y, semantics::rem_f32(x, y), x * y, ia[w] as f32];
+        for (k, (&found, &expected)) in f[w * 8..w * 8 + 4].iter().zip(&expected).enumerate() {
+            assert!(
+                found.to_bits() == expected.to_bits() || (found.is_nan() && expected.is_nan()),
+                "f32 case {} op {}: {} != {}",
+                w,
+                k,
+                found,
+                expected
+            );
+        }
+    }
+}
+
+pub fn report<E>(executor: &E)
+where
+    E: Executor<Error = Error>,
+    E::Executable: Executable<Resource = E::Resource, Error = Error, Report = Report>,
+{
+    let b = ProgramBuilder::new();
+    let zero = Constant::new(0_u32, &b);
+    let data = Array::<u32>::new(zero, 0, true, &b).mark_as_inout("data");
+    let k = Variable::<u32>::new(&b).mark_as_input("k");
+    let id = b.worker_id();
+    data.at(id).store(data.at(id).load() * k.load());
+    let mut executable = executor.compile(b.finalize().unwrap()).unwrap();
+    executable.set_work_size(Some(WorkSize::new(2, 4)));
+    let (data, k) = (
+        executor.new_resource().unwrap(),
+        executor.new_resource().unwrap(),
+    );
+    data.write_slice(&[1_u32, 2, 3, 4, 5, 6, 7, 8]).unwrap();
+    k.set_data(u32_scalar(3)).unwrap();
+    executable.bind("data", IO::InOut, data.clone()).unwrap();
+    executable.bind("k", IO::Input, k).unwrap();
+    let report = executable.run().unwrap();
+    assert_eq!(report.work_size, WorkSize::new(2, 4));
+    assert_eq!(report.workers, 8);
+    assert_eq!(report.uploaded, 36);
+    assert_eq!(report.downloaded, 32);
+    assert!(report.run_time <= report.wall_time);
+    assert!(report.to_string().starts_with("Completed in "));
+    assert_eq!(
+        data.map(|x: &[u32]| x.to_vec()).unwrap(),
+        vec![3, 6, 9, 12, 15, 18, 21, 24]
+    );
+}
+
+pub fn errors<E>(executor: &E)
+where
+    E: Executor<Error = Error>,
+    E::Executable: Executable<Resource = E::Resource, Error = Error, Report = Report>,
+{
+    let mut executable = executor.compile(signature()).unwrap();
+    let resource = || executor.new_resource().unwrap();
+    let (k, a, out) = (resource(), resource(), resource());
+    k.set_data(u32_scalar(3)).unwrap();
+    match bind_error(&mut executable, "nope", IO::Input, k.clone()) {
+        Error::Validation(message) => {
+            assert_eq!(message, "The program has no input named nope")
+        }
+        x => panic!("Expected a validation error, got {:?}", x),
+    }
+    match bind_error(&mut executable, "k", IO::Output, k.clone()) {
+        Error::Validation(_) => {}
+        x => panic!("Expected a validation error, got {:?}", x),
+    }
+    a.write_slice(&[1_i32, 2, 3, 4]).unwrap();
+    match bind_error(&mut executable, "a", IO::Input, a.clone()) {
+        Error::TypeMismatch(message) => assert!(message.starts_with("a: expected ")),
+        x => panic!("Expected a type mismatch, got {:?}", x),
+    }
+    match bind_error(&mut executable, "a", IO::Input, k.clone()) {
+        Error::TypeMismatch(_) => {}
+        x => panic!("Expected a type mismatch, got {:?}", x),
+    }
+    a.write_slice(&[1_u32, 2, 3, 4, 5]).unwrap();
+    match bind_error(&mut executable, "a", IO::Input, a.clone()) {
+        Error::OutOfBounds(message) => assert_eq!(
+            message,
+            "Array bound to a has 5 elements but at most 4 are allowed"
+        ),
+        x => panic!("Expected an out of bounds error, got {:?}", x),
+    }
+    a.write_slice(&[1_u32, 2, 3, 4]).unwrap();
+    executable.bind("k", IO::Input, k.clone()).unwrap();
+    executable.bind("a", IO::Input, a.clone()).unwrap();
+    match executable.run() {
+        Err(Error::MissingBinding(IO::InOut, ref name)) if name == "out" => {}
+        x => panic!("Expected a missing binding, got {:?}", x),
+    }
+    out.write_slice(&[0_u32; 4]).unwrap();
+    executable.bind("out", IO::InOut, out.clone()).unwrap();
+    a.write_slice(&[1_u32, 2, 3, 4, 5]).unwrap();
+    match executable.run() {
+        Err(Error::OutOfBounds(_)) => {}
+        x => panic!("Expected an out of bounds error, got {:?}", x),
+    }
+    a.write_slice(&[1_u32, 2, 3, 4]).unwrap();
+    executable.check_bindings().unwrap();
+    executable.set_work_size(Some(WorkSize::new(1, 4)));
+    executable.run().unwrap();
+    assert_eq!(out.map(|x: &[u32]| x.to_vec()).unwrap(), vec![4, 5, 6, 7]);
+    match out.map(|x: &[f32]| x.len()) {
+        Err(Error::TypeMismatch(_)) => {}
+        x => panic!("Expected a type mismatch, got {:?}", x),
+    }
+    match out.read_into(&mut [0_u32; 3]) {
+        Err(Error::OutOfBounds(_)) => {}
+        x => panic!("Expected an out of bounds error, got {:?}", x),
+    }
+    match out.update(2, &[1_u32, 2, 3]) {
+        Err(Error::OutOfBounds(_)) => {}
+        x => panic!("Expected an out of bounds error, got {:?}", x),
+    }
+}
+
+pub fn inout<E>(executor: &E)
+where
+    E: Executor<Error = Error>,
+    E::Executable: Executable<Resource = E::Resource, Error = Error, Report = Report>,
+{
+    let b = ProgramBuilder::new();
+    let zero = Constant::new(0_u32, &b);
+    let data = Array::<u32>::new(zero, 0, true, &b).mark_as_inout("data");
+    let total = Variable::<u32>::new(&b).mark_as_inout("total");
+    let id = b.worker_id();
+    data.at(id)
+        .store(data.at(id).load() * Constant::new(2_u32, &b) + id);
+    b.if_then(|_| id.eq(zero), |_| total.store(total.load() + data.len()));
+    let mut executable = executor.compile(b.finalize().unwrap()).unwrap();
+    executable.set_work_size(Some(WorkSize::new(2, 2)));
+    let (data, total) = (
+        executor.new_resource().unwrap(),
+        executor.new_resource().unwrap(),
+    );
+    data.write_slice(&[1_u32, 1, 1, 1]).unwrap();
+    total.set_data(u32_scalar(10)).unwrap();
+    executable.bind("data", IO::InOut, data.clone()).unwrap();
+    executable.bind("total", IO::InOut, total.clone()).unwrap();
+    let mut expected = vec![1_u32, 1, 1, 1];
+    for run in 1..4 {
+        executable.run().unwrap();
+        for (i, x) in expected.iter_mut().enumerate() {
+            *x = *x * 2 + i as u32;
+        }
+        assert_eq!(data.map(|x: &[u32]| x.to_vec()).unwrap(), expected);
+        assert_eq!(total.get_data(), u32_scalar(10 + 4 * run));
+    }
+    assert!(executable.binding("data", IO::Input).is_none());
+    assert!(executable.binding("data", IO::Output).is_none());
+    match bind_error(&mut executable, "data", IO::Input, data.clone()) {
+        Error::Validation(message) => {
+            assert_eq!(message, "The program has no input named data")
+        }
+        x => panic!("Expected a validation error, got {:?}", x),
+    }
+    assert!(IO::InOut.reads() && IO::InOut.writes());
+    assert!(IO::Input.reads() && !IO::Input.writes());
+    assert!(!IO::Output.reads() && IO::Output.writes());
+}
+
+pub fn parameters<E>(executor: &E)
+where
+    E: Executor<Error = Error>,
+    E::Executable: Executable<Resource = E::Resource, Error = Error, Report = Report>,
+{
+    let parameter = |name: &str, kind, data_type, array, max_size| Parameter {
+        name: name.to_string(),
+        kind,
+        token_type: if array {
+            TokenType::Array(data_type)
+        } else {
+            TokenType::Variable(data_type)
+        },
+        data_type,
+        array,
+        max_size,
+    };
+    let executable = executor.compile(signature()).unwrap();
+    assert_eq!(
+        executable.parameters(),
+        vec![
+            parameter("a", IO::Input, DataType::U32, true, Some(4)),
+            parameter("k", IO::Input, DataType::U32, false, None),
+            parameter("out", IO::InOut, DataType::U32, true, Some(4)),
+        ]
+    );
+    let b = ProgramBuilder::new();
+    let zero = Constant::new(0_u32, &b);
+    let x = Variable::<f32>::new(&b).mark_as_output("x");
+    let data = Array::<i32>::new(zero, 0, true, &b).mark_as_inout("data");
+    data.at(b.worker_id()).store(Constant::new(1_i32, &b));
+    b.global_barrier();
+    x.store(Constant::from(data.at(zero).load()));
+    let program = b.finalize().unwrap();
+    let expected = vec![
+        parameter("x", IO::Output, DataType::F32, false, None),
+        parameter("data", IO::InOut, DataType::I32, true, None),
+    ];
+    assert_eq!(program.parameters(), expected);
+    assert_eq!(executor.compile(program).unwrap().parameters(), expected);
+}
+
+pub fn null_inputs<E>(executor: &E)
+where
+    E: Executor<Error = Error>,
+    E::Executable: Executable<Resource = E::Resource, Error = Error, Report = Report>,
+{
+    let mut executable = executor.compile(signature()).unwrap();
+    executable.set_work_size(Some(WorkSize::new(1, 4)));
+    let resource = || executor.new_resource().unwrap();
+    let (k, a, out) = (resource(), resource(), resource());
+    executable.bind("k", IO::Input, k.clone()).unwrap();
+    executable.bind("a", IO::Input, a.clone()).unwrap();
+    executable.bind("out", IO::InOut, out.clone()).unwrap();
+    a.write_slice(&[1_u32, 2, 3, 4]).unwrap();
+    out.write_slice(&[0_u32; 4]).unwrap();
+    match executable.run() {
+        Err(Error::TypeMismatch(message)) => {
+            assert_eq!(message, "k: expected Variable(U32), found Null")
+        }
+        x => panic!("Expected a type mismatch, got {:?}", x),
+    }
+    k.set_data(u32_scalar(1)).unwrap();
+    out.clear();
+    match executable.check_bindings() {
+        Err(Error::TypeMismatch(message)) => {
+            assert_eq!(message, "out: expected Array(U32), found Null")
+        }
+        x => panic!("Expected a type mismatch, got {:?}", x),
+    }
+    out.write_slice(&[0_u32; 4]).unwrap();
+    let overrides = vec![("k".to_string(), IO::Input, TokenValue::Null)];
+    match executable.run_batch(&[overrides]) {
+        Err(Error::TypeMismatch(_)) => {}
+        Err(e) => panic!("Expected a type mismatch, got {:?}", e),
+        Ok(_) => panic!("Expected the null override to be rejected"),
+    }
+    executable.run().unwrap();
+    assert_eq!(out.map(|x: &[u32]| x.to_vec()).unwrap(), vec![2, 3, 4, 5]);
+    let b = ProgramBuilder::new();
+    Variable::<u32>::new(&b)
+        .mark_as_output("n")
+        .store(b.num_workers());
+    let mut executable = executor.compile(b.finalize().unwrap()).unwrap();
+    executable.set_work_size(Some(WorkSize::new(1, 3)));
+    let n = resource();
+    executable.bind("n", IO::Output, n.clone()).unwrap();
+    executable.run().unwrap();
+    assert_eq!(n.get_data(), u32_scalar(3));
+}
+
+pub fn typed_access<E>(executor: &E)
+where
+    E: Executor<Error = Error>,
+    E::Executable: Executable<Resource = E::Resource, Error = Error, Report = Report>,
+{
+    let resource = executor.new_resource().unwrap();
+    assert_eq!(resource.token_type(), TokenType::Null);
+    match resource.map(|x: &[u32]| x.len()) {
+        Err(Error::TypeMismatch(_)) => {}
+        x => panic!("Expected a type mismatch, got {:?}", x),
+    }
+    resource.write_slice(&[1_u32, 2, 3]).unwrap();
+    assert_eq!(resource.token_type(), TokenType::Array(DataType::U32));
+    assert_eq!(resource.map(|x: &[u32]| x.iter().sum::<u32>()).unwrap(), 6);
+    for result in &[
+        resource.map(|_: &[i32]| ()),
+        resource.map(|_: &[f32]| ()),
+        resource.map_mut(|_: &mut [i32]| ()),
+    ] {
+        match *result {
+            Err(Error::TypeMismatch(_)) => {}
+            ref x => panic!("Expected a type mismatch, got {:?}", x),
+        }
+    }
+    resource.map_mut(|x: &mut [u32]| x[1] = 7).unwrap();
+    let mut data = [0_u32; 3];
+    resource.read_into(&mut data).unwrap();
+    assert_eq!(data, [1, 7, 3]);
+    assert_eq!(
+        resource.get_data(),
+        TokenValue::Vector(ConstantVector::U32(vec![1, 7, 3]))
+    );
+    resource.write_slice(&[-1.5_f32, 2.5]).unwrap();
+    assert_eq!(resource.token_type(), TokenType::Array(DataType::F32));
+    assert_eq!(
+        resource.map(|x: &[f32]| x.to_vec()).unwrap(),
+        vec![-1.5, 2.5]
+    );
+    resource
+        .set_data(TokenValue::Scalar(ConstantScalar::I32(-4)))
+        .unwrap();
+    assert_eq!(resource.token_type(), TokenType::Variable(DataType::I32));
+    assert_eq!(resource.map(|x: &[i32]| x.to_vec()).unwrap(), vec![-4]);
+    resource.map_mut(|x: &mut [i32]| x[0] *= 2).unwrap();
+    assert_eq!(
+        resource.get_data(),
+        TokenValue::Scalar(ConstantScalar::I32(-8))
+    );
+    resource.clear();
+    assert_eq!(resource.get_data(), TokenValue::Null);
+}
+
+pub fn updates<E>(executor: &E)
+where
+    E: Executor<Error = Error>,
+    E::Executable: Executable<Resource = E::Resource, Error = Error, Report = Report>,
+{
+    let resource = executor.new_resource().unwrap();
+    resource.reserve::<u32>(16).unwrap();
+    resource.write_slice(&[1_u32, 2, 3, 4]).unwrap();
+    let buffer = resource.map(|x: &[u32]| x.as_ptr()).unwrap();
+    resource.update(1, &[5_u32, 6]).unwrap();
+    assert_eq!(
+        resource.map(|x: &[u32]| x.to_vec()).unwrap(),
+        vec![1, 5, 6, 4]
+    );
+    resource.resize::<u32>(10).unwrap();
+    assert_eq!(
+        resource.map(|x: &[u32]| x.to_vec()).unwrap(),
+        vec![1, 5, 6, 4, 0, 0, 0, 0, 0, 0]
+    );
+    resource.update(8, &[9_u32, 9]).unwrap();
+    resource.resize::<u32>(2).unwrap();
+    resource.resize::<u32>(16).unwrap();
+    let data = resource.map(|x: &[u32]| x.to_vec()).unwrap();
+    assert_eq!(&data[..2], &[1, 5]);
+    assert!(data[2..].iter().all(|&x| x == 0));
+    resource.write_slice(&[7_u32; 12]).unwrap();
+    assert_eq!(resource.map(|x: &[u32]| x.as_ptr()).unwrap(), buffer);
+    match resource.update(10, &[1_u32, 2, 3]) {
+        Err(Error::OutOfBounds(message)) => assert_eq!(
+            message,
+            "Update of 3 elements at offset 10 exceeds the resource length 12"
+        ),
+        x => panic!("Expected an out of bounds error, got {:?}", x),
+    }
+    match resource.update(0, &[1_i32]) {
+        Err(Error::TypeMismatch(_)) => {}
+        x => panic!("Expected a type mismatch, got {:?}", x),
+    }
+    assert_eq!(resource.map(|x: &[u32]| x.to_vec()).unwrap(), vec![7; 12]);
+    let scalar = executor.new_resource().unwrap();
+    scalar.set_data(u32_scalar(1)).unwrap();
+    scalar.update(0, &[2_u32]).unwrap();
+    assert_eq!(scalar.get_data(), u32_scalar(2));
+}
+
+pub fn pipeline<E>(executor: &E)
+where
+    E: Executor<Error = Error>,
+    E::Executable: Executable<Resource = E::Resource, Error = Error, Report = Report>,
+{
+    let b = ProgramBuilder::new();
+    let zero = Constant::new(0_u32, &b);
+    let x = Array::<u32>::new(zero, 0, true, &b).mark_as_input("x");
+    let y = Array::<u32>::new(zero, 0, true, &b).mark_as_inout("y");
+    let id = b.worker_id();
+    y.at(id).store(x.at(id).load() * Constant::new(2_u32, &b));
+    let double = b.finalize().unwrap();
+    let resource = |data: &[u32]| {
+        let resource = executor.new_resource().unwrap();
+        resource.write_slice(data).unwrap();
+        resource
+    };
+    let scalar = |value: u32| {
+        let resource = executor.new_resource().unwrap();
+        resource.set_data(u32_scalar(value)).unwrap();
+        resource
+    };
+    let (a, middle, result, other) = (
+        resource(&[1, 2, 3, 4]),
+        resource(&[0; 4]),
+        resource(&[0; 4]),
+        resource(&[0; 4]),
+    );
+    let node = |program: Program, bindings: Vec<(&str, IO, Arc<E::Resource>)>| {
+        let mut executable = executor.compile(program).unwrap();
+        executable.set_work_size(Some(WorkSize::new(1, 4)));
+        for (name, kind, resource) in bindings {
+            executable.bind(name, kind, resource).unwrap();
+        }
+        executable
+    };
+    let mut pipeline = Pipeline::new();
+    pipeline.add(node(
+        signature(),
+        vec![
+            ("a", IO::Input, a.clone()),
+            ("k", IO::Input, scalar(1)),
+            ("out", IO::InOut, middle.clone()),
+        ],
+    ));
+    pipeline.add(node(
+        double,
+        vec![
+            ("x", IO::Input, middle.clone()),
+            ("y", IO::InOut, result.clone()),
+        ],
+    ));
+    pipeline.add(node(
+        signature(),
+        vec![
+            ("a", IO::Input, a.clone()),
+            ("k", IO::Input, scalar(5)),
+            ("out", IO::InOut, other.clone()),
+        ],
+    ));
+    pipeline.add(node(
+        signature(),
+        vec![
+            ("a", IO::Input, a.clone()),
+            ("k", IO::Input, scalar(10)),
+            ("out", IO::InOut, middle.clone()),
+        ],
+    ));
+    let dependencies: Vec<Vec<usize>> = pipeline
+        .dependencies()
+        .into_iter()
+        .map(|x| x.into_iter().collect())
+        .collect();
+    assert_eq!(dependencies, vec![vec![], vec![0], vec![], vec![0, 1]]);
+    assert_eq!(pipeline.stages(), vec![vec![0, 2], vec![1], vec![3]]);
+    for _ in 0..2 {
+        let reports = pipeline.run().unwrap();
+        assert_eq!(reports.len(), 4);
+        assert!(reports.iter().all(|x| x.workers == 4));
+        assert_eq!(
+            result.map(|x: &[u32]| x.to_vec()).unwrap(),
+            vec![4, 6, 8, 10]
+        );
+        assert_eq!(other.map(|x: &[u32]| x.to_vec()).unwrap(), vec![6, 7, 8, 9]);
+        assert_eq!(
+            middle.map(|x: &[u32]| x.to_vec()).unwrap(),
+            vec![11, 12, 13, 14]
+        );
+    }
+    assert!(Pipeline::<E::Executable>::new().run().unwrap().is_empty());
+}
+
+pub fn run_until<E>(executor: &E)
+where
+    E: Executor<Error = Error>,
+    E::Executable: Executable<Resource = E::Resource, Error = Error, Report = Report>,
+{
+    let b = ProgramBuilder::new();
+    let zero = Constant::new(0_u32, &b);
+    let one = Constant::new(1_u32, &b);
+    let k = Variable::<u32>::new(&b).mark_as_input("k");
+    let data = Array::<u32>::new(zero, 0, true, &b).mark_as_inout("data");
+    let count = Variable::<u32>::new(&b).mark_as_inout("count");
+    let id = b.worker_id();
+    data.at(id).store(data.at(id).load() + k.load());
+    b.if_then(|_| id.eq(zero), |_| count.store(count.load() + one));
+    let mut executable = executor.compile(b.finalize().unwrap()).unwrap();
+    executable.set_work_size(Some(WorkSize::new(2, 2)));
+    let (k, data, count) = (
+        executor.new_resource().unwrap(),
+        executor.new_resource().unwrap(),
+        executor.new_resource().unwrap(),
+    );
+    k.set_data(u32_scalar(3)).unwrap();
+    data.write_slice(&[0_u32; 4]).unwrap();
+    count.set_data(u32_scalar(0)).unwrap();
+    executable.bind("k", IO::Input, k).unwrap();
+    executable.bind("data", IO::InOut, data.clone()).unwrap();
+    executable.bind("count", IO::InOut, count.clone()).unwrap();
+    let mut seen = Vec::new();
+    let convergence = executable
+        .run_until("count", Some(100), |x: u32| {
+            seen.push(x);
+            x >= 5
+        })
+        .unwrap();
+    assert_eq!((convergence.iterations, convergence.converged), (5, true));
+    assert_eq!(convergence.report.workers, 4);
+    assert_eq!(seen, vec![1, 2, 3, 4, 5]);
+    assert_eq!(count.get_data(), u32_scalar(5));
+    assert_eq!(data.map(|x: &[u32]| x.to_vec()).unwrap(), vec![15; 4]);
+    let convergence = executable
+        .run_until("count", Some(3), |_: u32| false)
+        .unwrap();
+    assert_eq!((convergence.iterations, convergence.converged), (3, false));
+    assert_eq!(count.get_data(), u32_scalar(8));
+    assert_eq!(data.map(|x: &[u32]| x.to_vec()).unwrap(), vec![24; 4]);
+    let convergence = executable.run_until("count", None, |x: u32| x > 0).unwrap();
+    assert_eq!((convergence.iterations, convergence.converged), (1, true));
+    assert_eq!(count.get_data(), u32_scalar(9));
+    match executable.run_until("count", None, |_: f32| true) {
+        Err(Error::TypeMismatch(message)) => {
+            assert_eq!(
+                message,
+                "count: expected Variable(F32), found Variable(U32)"
+            )
+        }
+        x => panic!(
+            "Expected a type mismatch, got {:?}",
+            x.map(|x| x.iterations)
+        ),
+    }
+    match executable.run_until("data", None, |_: u32| true) {
+        Err(Error::TypeMismatch(message)) => {
+            assert_eq!(message, "data: expected Variable(U32), found Array(U32)")
+        }
+        x => panic!(
+            "Expected a type mismatch, got {:?}",
+            x.map(|x| x.iterations)
+        ),
+    }
+    for flag in &["k", "nope"] {
+        match executable.run_until(flag, None, |_: u32| true) {
+            Err(Error::Validation(message)) => {
+                assert_eq!(message, format!("The program has no output named {}", flag))
+            }
+            x => panic!(
+                "Expected a validation error, got {:?}",
+                x.map(|x| x.iterations)
+            ),
+        }
+    }
+    assert_eq!(count.get_data(), u32_scalar(9));
+    executable.unbind("count", IO::InOut);
+    match executable.run_until("count", None, |_: u32| true) {
+        Err(Error::MissingBinding(IO::InOut, name)) => assert_eq!(name, "count"),
+        x => panic!(
+            "Expected a missing binding, got {:?}",
+            x.map(|x| x.iterations)
+        ),
+    }
+}
+
+pub fn batch<E>(executor: &E)
+where
+    E: Executor<Error = Error>,
+    E::Executable: Executable<Resource = E::Resource, Error = Error, Report = Report>,
+{
+    let mut executable = executor.compile(signature()).unwrap();
+    executable.set_work_size(Some(WorkSize::new(2, 2)));
+    let (k, a, out) = (
+        executor.new_resource().unwrap(),
+        executor.new_resource().unwrap(),
+        executor.new_resource().unwrap(),
+    );
+    k.set_data(u32_scalar(1)).unwrap();
+    a.write_slice(&[1_u32, 2, 3, 4]).unwrap();
+    out.write_slice(&[0_u32; 4]).unwrap();
+    executable.bind("k", IO::Input, k.clone()).unwrap();
+    executable.bind("a", IO::Input, a.clone()).unwrap();
+    executable.bind("out", IO::InOut, out.clone()).unwrap();
+    let wide = TokenValue::Vector(ConstantVector::U32(vec![10, 20, 30, 40]));
+    let runs: Vec<_> = (0..17_u32)
+        .map(|i| {
+            let mut overrides = vec![("k".to_string(), IO::Input, u32_scalar(i))];
+            if i % 2 == 1 {
+                overrides.push(("a".to_string(), IO::Input, wide.clone()));
+            }
+            overrides
+        })
+        .collect();
+    let results = executable.run_batch(&runs).unwrap();
+    assert_eq!(results.len(), runs.len());
+    for (i, result) in (0..17_u32).zip(&results) {
+        let a: &[u32] = if i % 2 == 1 {
+            &[10, 20, 30, 40]
+        } else {
+            &[1, 2, 3, 4]
+        };
+        let expected = a.iter().map(|x| x + i).collect();
+        assert_eq!(result.outputs.len(), 1);
+        assert_eq!(
+            result.outputs["out"],
+            TokenValue::Vector(ConstantVector::U32(expected))
+        );
+        assert_eq!(result.report.workers, 4);
+    }
+    assert_eq!(k.get_data(), u32_scalar(1));
+    assert_eq!(a.map(|x: &[u32]| x.to_vec()).unwrap(), vec![1, 2, 3, 4]);
+    assert_eq!(out.map(|x: &[u32]| x.to_vec()).unwrap(), vec![0; 4]);
+    assert!(executable.run_batch(&[]).unwrap().is_empty());
+    let overrides = |name: &str, kind, value| vec![vec![], vec![(name.to_string(), kind, value)]];
+    let batch_error =
+        |executable: &mut E::Executable, runs: Vec<Overrides>| match executable.run_batch(&runs) {
+            Err(e) => e,
+            Ok(_) => panic!("Expected the batch {:?} to fail", runs),
+        };
+    match batch_error(&mut executable, overrides("nope", IO::Input, u32_scalar(1))) {
+        Error::Validation(message) => assert_eq!(message, "The program has no input named nope"),
+        x => panic!("Expected a validation error, got {:?}", x),
+    }
+    match batch_error(&mut executable, overrides("out", IO::Output, u32_scalar(1))) {
+        Error::Validation(message) => assert_eq!(message, "Cannot override output out"),
+        x => panic!("Expected a validation error, got {:?}", x),
+    }
+    let float = TokenValue::Scalar(ConstantScalar::F32(1.0));
+    match batch_error(&mut executable, overrides("k", IO::Input, float)) {
+        Error::TypeMismatch(message) => {
+            assert_eq!(message, "k: expected Variable(U32), found Variable(F32)")
+        }
+        x => panic!("Expected a type mismatch, got {:?}", x),
+    }
+    let five = TokenValue::Vector(ConstantVector::U32(vec![1, 2, 3, 4, 5]));
+    match batch_error(&mut executable, overrides("a", IO::Input, five)) {
+        Error::OutOfBounds(message) => assert_eq!(
+            message,
+            "Array bound to a has 5 elements but at most 4 are allowed"
+        ),
+        x => panic!("Expected an out of bounds error, got {:?}", x),
+    }
+    executable.unbind("k", IO::Input);
+    match batch_error(&mut executable, runs) {
+        Error::MissingBinding(IO::Input, name) => assert_eq!(name, "k"),
+        x => panic!("Expected a missing binding, got {:?}", x),
+    }
+}
+
+pub fn submit<E>(executor: &E)
+where
+    E: Executor<Error = Error>,
+    E::Executable: Executable<Resource = E::Resource, Error = Error, Report = Report>,
+{
+    let mut executable = executor.compile(signature()).unwrap();
+    executable.set_work_size(Some(WorkSize::new(2, 2)));
+    let (k, a, out) = (
+        executor.new_resource().unwrap(),
+        executor.new_resource().unwrap(),
+        executor.new_resource().unwrap(),
+    );
+    k.set_data(u32_scalar(1)).unwrap();
+    a.write_slice(&[1_u32, 2, 3, 4]).unwrap();
+    out.write_slice(&[0_u32; 4]).unwrap();
+    executable.bind("a", IO::Input, a.clone()).unwrap();
+    executable.bind("out", IO::InOut, out.clone()).unwrap();
+    match executable.submit() {
+        Err(Error::MissingBinding(IO::Input, name)) => assert_eq!(name, "k"),
+        x => panic!("Expected a missing binding, got {:?}", x.map(|_| ())),
+    }
+    executable.bind("k", IO::Input, k.clone()).unwrap();
+    let submission = executable.submit().unwrap();
+    k.set_data(u32_scalar(100)).unwrap();
+    assert_eq!(submission.wait().unwrap().workers, 4);
+    assert_eq!(out.map(|x: &[u32]| x.to_vec()).unwrap(), vec![2, 3, 4, 5]);
+    let mut submission = executable.submit().unwrap();
+    let report = loop {
+        if let Some(result) = submission.try_wait() {
+            break result.unwrap();
+        }
+        thread::yield_now();
+    };
+    assert_eq!(report.workers, 4);
+    assert_eq!(
+        out.map(|x: &[u32]| x.to_vec()).unwrap(),
+        vec![101, 102, 103, 104]
+    );
+    a.write_slice(&[5_u32, 6, 7, 8]).unwrap();
+    let mut submission = executable.submit().unwrap();
+    let mut context = Context::from_waker(Waker::noop());
+    let report = loop {
+        if let Poll::Ready(result) = Pin::new(&mut submission).poll(&mut context) {
+            break result.unwrap();
+        }
+        thread::yield_now();
+    };
+    assert_eq!(report.workers, 4);
+    assert_eq!(
+        out.map(|x: &[u32]| x.to_vec()).unwrap(),
+        vec![105, 106, 107, 108]
+    );
+    let submissions: Vec<_> = (0..4).map(|_| executable.submit().unwrap()).collect();
+    for submission in submissions {
+        submission.wait().unwrap();
+    }
+    assert_eq!(
+        out.map(|x: &[u32]| x.to_vec()).unwrap(),
+        vec![105, 106, 107, 108]
+    );
+    let ready: Submission<u32, Error> = Submission::ready(Ok(7));
+    assert!(ready.is_done());
+    assert_eq!(ready.wait().unwrap(), 7);
+
+    let b = ProgramBuilder::new();
+    let a = Array::<u32>::new(Constant::new(0_u32, &b), 0, true, &b).mark_as_inout("a");
+    a.at(a.len()).store(a.len());
+    let mut executable = executor.compile(b.finalize().unwrap()).unwrap();
+    executable.set_work_size(Some(WorkSize::new(1, 1)));
+    let resource = executor.new_resource().unwrap();
+    resource.write_slice(&[1_u32, 2]).unwrap();
+    executable.bind("a", IO::InOut, resource.clone()).unwrap();
+    match executable.submit().unwrap().wait() {
+        Err(Error::OutOfBounds(message)) => assert!(message.starts_with("Index 2 out of bounds")),
+        x => panic!("Expected an out of bounds error, got {:?}", x.map(|_| ())),
+    }
+    assert_eq!(resource.map(|x: &[u32]| x.to_vec()).unwrap(), vec![1, 2]);
+}
+
+pub fn group_barrier<E>(executor: &E)
+where
+    E: Executor<Error = Error>,
+    E::Executable: Executable<Resource = E::Resource, Error = Error, Report = Report>,
+{
+    let b = ProgramBuilder::new();
+    let zero = Constant::new(0_u32, &b);
+    let one = Constant::new(1_u32, &b);
+    let two = Constant::new(2_u32, &b);
+    let a = Array::<u32>::new(zero, 0, true, &b).mark_as_inout("a");
+    let c = Array::<u32>::new(zero, 0, true, &b).mark_as_inout("c");
+    let id = b.worker_id();
+    a.at(id).store(id * two);
+    b.barrier();
+    c.at(id).store(a.at(id + one - id % two * two).load());
+    let mut executable = executor.compile(b.finalize().unwrap()).unwrap();
+    for &work_size in &[WorkSize::new(8, 4), WorkSize::new(16, 64)] {
+        let n = work_size.num_workers().unwrap();
+        executable.set_work_size(Some(work_size));
+        let a = executor.new_resource().unwrap();
+        let c = executor.new_resource().unwrap();
+        a.write_slice(&vec![0_u32; n as usize]).unwrap();
+        c.write_slice(&vec![0_u32; n as usize]).unwrap();
+        executable.bind("a", IO::InOut, a).unwrap();
+        executable.bind("c", IO::InOut, c.clone()).unwrap();
+        executable.run().unwrap();
+        let expected: Vec<u32> = (0..n).map(|i| (i ^ 1) * 2).collect();
+        assert_eq!(c.map(|x: &[u32]| x.to_vec()).unwrap(), expected);
+    }
+}
+
+pub fn global_barrier<E>(executor: &E)
+where
+    E: Executor<Error = Error>,
+    E::Executable: Executable<Resource = E::Resource, Error = Error, Report = Report>,
+{
+    const WORKERS: u32 = 32;
+    const ROUNDS: u32 = 3;
+    let b = ProgramBuilder::new();
+    let zero = Constant::new(0_u32, &b);
+    let one = Constant::new(1_u32, &b);
+    let a = Array::<u32>::new(zero, 0, true, &b).mark_as_inout("a");
+    let id = b.worker_id();
+    let n = b.num_workers();
+    let round = Variable::<u32>::new(&b);
+    round.store(zero);
+    b.while_loop(
+        |_| round.load().lt(Constant::new(ROUNDS, &b)),
+        |_| {
+            let v = a.at((id + one) % n).load();
+            b.global_barrier();
+            a.at(id).store(v + one);
+            b.global_barrier();
+            round.store(round.load() + one);
+        },
+    );
+    let expected: Vec<u32> = (0..WORKERS)
+        .map(|i| (i + ROUNDS) % WORKERS + ROUNDS)
+        .collect();
+    assert_eq!(
+        run_twice(executor, b.finalize().unwrap()).unwrap(),
+        expected
+    );
+
+    let b = ProgramBuilder::new();
+    let zero = Constant::new(0_u32, &b);
+    let one = Constant::new(1_u32, &b);
+    let two = Constant::new(2_u32, &b);
+    let a = Array::<u32>::new(zero, 0, true, &b).mark_as_inout("a");
+    let private = Array::<u32>::new(two, 2, false, &b);
+    let id = b.worker_id();
+    let even = (id % two).eq(zero);
+    private.at(zero).store(id * Constant::new(10_u32, &b));
+    private.at(one).store(Constant::new(7_u32, &b));
+    a.at(id).store(id);
+    b.global_barrier();
+    let neighbour = a.at((id + one) % b.num_workers()).load();
+    b.global_barrier();
+    let extra = Variable::<u32>::new(&b);
+    extra.store(zero);
+    b.if_then(|_| even, |_| extra.store(Constant::new(1000_u32, &b)));
+    a.at(id)
+        .store(neighbour + private.at(zero).load() + private.at(one).load() + extra.load());
+    let expected: Vec<u32> = (0..WORKERS)
+        .map(|i| (i + 1) % WORKERS + 10 * i + 7 + if i % 2 == 0 { 1000 } else { 0 })
+        .collect();
+    assert_eq!(
+        run_twice(executor, b.finalize().unwrap()).unwrap(),
+        expected
+    );
+
+    let conditional = |uniform: bool| {
+        let b = ProgramBuilder::new();
+        let one = Constant::new(1_u32, &b);
+        let four = Constant::new(4_u32, &b);
+        let a = Array::<u32>::new(Constant::new(0_u32, &b), 0, true, &b).mark_as_inout("a");
+        let id = b.worker_id();
+        let n = b.num_workers();
+        b.if_then(
+            |_| if uniform { four.lt(n) } else { id.lt(four) },
+            |_| {
+                let v = a.at((id + one) % n).load();
+                b.global_barrier();
+                a.at(id).store(v + Constant::new(100_u32, &b));
+            },
+        );
+        b.finalize().unwrap()
+    };
+    let expected: Vec<u32> = (0..WORKERS).map(|i| (i + 1) % WORKERS + 100).collect();
+    assert_eq!(run_twice(executor, conditional(true)).unwrap(), expected);
+    match run_twice(executor, conditional(false)) {
+        Err(Error::Execution(message)) => {
+            assert!(
+                message.starts_with(
+                    "GlobalBarrier reached on non-uniform control flow: worker 0 continues at phase "
+                ),
+                "{}",
+                message
+            );
+            assert!(message.ends_with(" but worker 4 at the end"), "{}", message);
+        }
+        x => panic!("Expected an execution error, got {:?}", x),
+    }
+
+    let b = ProgramBuilder::new();
+    let zero = Constant::new(0_u32, &b);
+    b.if_then(
+        |_| {
+            b.global_barrier();
+            zero.eq(zero)
+        },
+        |_| {},
+    );
+    match executor.compile(b.finalize().unwrap()) {
+        Err(Error::Validation(_)) => {}
+        x => panic!("Expected a validation error, got {:?}", x.err()),
+    }
+}
+
+pub fn faults<E>(executor: &E)
+where
+    E: Executor<Error = Error>,
+    E::Executable: Executable<Resource = E::Resource, Error = Error, Report = Report>,
+{
+    let b = ProgramBuilder::new();
+    let a = Array::<u32>::new(Constant::new(0_u32, &b), 0, true, &b).mark_as_inout("a");
+    let out = Variable::<u32>::new(&b).mark_as_output("out");
+    out.store(a.at(a.len()).load());
+    let message = fault(executor, b.finalize().unwrap(), Some(&[1, 2, 3]));
+    assert!(
+        message.starts_with("Index 3 out of bounds for TokenId("),
+        "{}",
+        message
+    );
+    assert!(message.contains(" of length 3 at op #"), "{}", message);
+    assert!(message.contains("(ArrayLoad("), "{}", message);
+
+    let b = ProgramBuilder::new();
+    let size = Variable::<u32>::new(&b).mark_as_input("size");
+    let out = Variable::<u32>::new(&b).mark_as_output("out");
+    let private = Array::<u32>::new(size.load(), 4, false, &b);
+    out.store(private.len());
+    let message = fault(executor, b.finalize().unwrap(), None);
+    assert!(
+        message.starts_with("Size 9 exceeds the max size 4 of TokenId("),
+        "{}",
+        message
+    );
+    assert!(message.contains("(ArrayNew("), "{}", message);
+}
+
+fn fault<E>(executor: &E, program: Program, a: Option<&[u32]>) -> String
+where
+    E: Executor<Error = Error>,
+    E::Executable: Executable<Resource = E::Resource, Error = Error, Report = Report>,
+{
+    let mut executable = executor.compile(program).unwrap();
+    executable.set_work_size(Some(WorkSize::new(1, 1)));
+    for parameter in executable.parameters() {
+        let resource = executor.new_resource().unwrap();
+        match (parameter.kind, a) {
+            (IO::InOut, Some(a)) => resource.write_slice(a).unwrap(),
+            (IO::Input, _) => resource.set_data(u32_scalar(9)).unwrap(),
+            _ => {}
+        }
+        executable
+            .bind(&parameter.name, parameter.kind, resource)
+            .unwrap();
+    }
+    match executable.run() {
+        Err(Error::OutOfBounds(message)) => message,
+        x => panic!("Expected an out of bounds error, got {:?}", x),
+    }
+}
+
+fn run_twice<E>(executor: &E, program: Program) -> Result<Vec<u32>, Error>
+where
+    E: Executor<Error = Error>,
+    E::Executable: Executable<Resource = E::Resource, Error = Error, Report = Report>,
+{
+    let workers = 32;
+    let mut executable = executor.compile(program)?;
+    executable.set_work_size(Some(WorkSize::new(4, workers / 4)));
+    let a = executor.new_resource()?;
+    a.write_slice(&(0..workers).collect::<Vec<_>>())?;
+    executable.bind("a", IO::InOut, a.clone())?;
+    executable.run()?;
+    let first = a.map(|x: &[u32]| x.to_vec())?;
+    a.write_slice(&(0..workers).collect::<Vec<_>>())?;
+    executable.run()?;
+    assert_eq!(a.map(|x: &[u32]| x.to_vec())?, first);
+    Ok(first)
+}
+
+fn bind_error<X>(executable: &mut X, name: &str, kind: IO, resource: Arc<X::Resource>) -> Error
+where
+    X: Executable<Error = Error>,
+{
+    match executable.bind(name, kind, resource) {
+        Err(e) => e,
+        Ok(_) => panic!("Expected binding {} {} to fail", kind, name),
+    }
+}
+
+fn signature() -> Program {
+    let b = ProgramBuilder::new();
+    let zero = Constant::new(0_u32, &b);
+    let k = Variable::<u32>::new(&b).mark_as_input("k");
+    let a = Array::<u32>::new(zero, 4, true, &b).mark_as_input("a");
+    let out = Array::<u32>::new(zero, 4, true, &b).mark_as_inout("out");
+    let id = b.worker_id();
+    out.at(id).store(a.at(id).load() + k.load());
+    b.finalize().unwrap()
+}
+
+fn u32_scalar(value: u32) -> TokenValue {
+    TokenValue::Scalar(ConstantScalar::U32(value))
+}
@@ -0,0 +1,204 @@
+// Copyright 2018 | Dario Ostuni <dario.ostuni@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use error::Error;
+use executor::{Executable, Executor, Report, Resource, WorkSize, IO};
+use program::{Parameter, Program, TokenType, TokenValue};
+use std::any::Any;
+use std::collections::BTreeMap;
+use std::fmt::{self, Debug, Formatter};
+use std::sync::Arc;
+use submission::Submission;
+
+type Factory = Box<dyn Fn() -> Result<Box<dyn DynExecutor>, Error>>;
+
+pub trait DynExecutor {
+    fn compile(&self, program: Program) -> Result<Box<dyn DynExecutable>, Error>;
+    fn new_resource(&self) -> Result<Arc<dyn DynResource>, Error>;
+}
+
+pub trait DynExecutable {
+    fn bind(
+        &mut self,
+        name: &str,
+        kind: IO,
+        resource: Arc<dyn DynResource>,
+    ) -> Result<Option<Arc<dyn DynResource>>, Error>;
+    fn unbind(&mut self, name: &str, kind: IO) -> Option<Arc<dyn DynResource>>;
+    fn binding(&self, name: &str, kind: IO) -> Option<Arc<dyn DynResource>>;
+    fn parameters(&self) -> Vec<Parameter>;
+    fn check_bindings(&self) -> Result<(), Error>;
+    fn work_size(&self) -> WorkSize;
+    fn set_work_size(&mut self, work_size: Option<WorkSize>);
+    fn run(&mut self) -> Result<Report, Error>;
+    fn submit(&mut self) -> Result<Submission<Report, Error>, Error>;
+}
+
+pub trait DynResource: Send + Sync {
+    fn clear(&self);
+    fn token_type(&self) -> TokenType;
+    fn set_data(&self, value: TokenValue) -> Result<(), Error>;
+    fn get_data(&self) -> TokenValue;
+    fn into_any(self: Arc<Self>) -> Arc<dyn Any + Send + Sync>;
+}
+
+pub struct Registry {
+    backends: BTreeMap<String, Factory>,
+}
+
+impl<T> DynExecutor for T
+where
+    T: Executor<Error = Error>,
+    T::Executable: Executable<Resource = T::Resource, Error = Error, Report = Report> + 'static,
+    T::Resource: Send + Sync + 'static,
+{
+    fn compile(&self, program: Program) -> Result<Box<dyn DynExecutable>, Error> {
+        Ok(Box::new(Executor::compile(self, program)?))
+    }
+
+    fn new_resource(&self) -> Result<Arc<dyn DynResource>, Error> {
+        Ok(Executor::new_resource(self)?)
+    }
+}
+
+impl<T> DynExecutable for T
+where
+    T: Executable<Error = Error, Report = Report>,
+    T::Resource: Send + Sync + 'static,
+{
+    fn bind(
+        &mut self,
+        name: &str,
+        kind: IO,
+        resource: Arc<dyn DynResource>,
+    ) -> Result<Option<Arc<dyn DynResource>>, Error> {
+        let resource = resource.into_any().downcast::<T::Resource>().map_err(|_| {
+            Error::Validation(format!(
+                "Resource bound to {} belongs to another backend",
+                name
+            ))
+        })?;
+        Ok(Executable::bind(self, name, kind, resource)?.map(|x| x as Arc<dyn DynResource>))
+    }
+
+    fn unbind(&mut self, name: &str, kind: IO) -> Option<Arc<dyn DynResource>> {
+        Executable::unbind(self, name, kind).map(|x| x as Arc<dyn DynResource>)
+    }
+
+    fn binding(&self, name: &str, kind: IO) -> Option<Arc<dyn DynResource>> {
+        Executable::binding(self, name, kind).map(|x| x as Arc<dyn DynResource>)
+    }
+
+    fn parameters(&self) -> Vec<Parameter> {
+        Executable::parameters(self)
+    }
+
+    fn check_bindings(&self) -> Result<(), Error> {
+        Executable::check_bindings(self)
+    }
+
+    fn work_size(&self) -> WorkSize {
+        Executable::work_size(self)
+    }
+
+    fn set_work_size(&mut self, work_size: Option<WorkSize>) {
+        Executable::set_work_size(self, work_size)
+    }
+
+    fn run(&mut self) -> Result<Report, Error> {
+        Executable::run(self)
+    }
+
+    fn submit(&mut self) -> Result<Submission<Report, Error>, Error> {
+        Executable::submit(self)
+    }
+}
+
+impl<T: Resource + Send + Sync + 'static> DynResource for T {
+    fn clear(&self) {
+        Resource::clear(self)
+    }
+
+    fn token_type(&self) -> TokenType {
+        Resource::token_type(self)
+    }
+
+    fn set_data(&self, value: TokenValue) -> Result<(), Error> {
+        Resource::set_data(self, value)
+    }
+
+    fn get_data(&self) -> TokenValue {
+        Resource::get_data(self)
+    }
+
+    fn into_any(self: Arc<Self>) -> Arc<dyn Any + Send + Sync> {
+        self
+    }
+}
+
+impl Registry {
+    pub fn new() -> Registry {
+        Registry {
+            backends: BTreeMap::new(),
+        }
+    }
+
+    pub fn register<S, F>(&mut self, name: S, factory: F)
+    where
+        S: ToString,
+        F: Fn() -> Result<Box<dyn DynExecutor>, Error> + 'static,
+    {
+        self.backends.insert(name.to_string(), Box::new(factory));
+    }
+
+    pub fn register_executor<E, S>(&mut self, name: S, config: E::Config)
+    where
+        E: Executor<Error = Error> + DynExecutor + 'static,
+        E::Config: 'static,
+        S: ToString,
+    {
+        self.register(name, move || {
+            Ok(Box::new(E::new(config.clone())?) as Box<dyn DynExecutor>)
+        });
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        self.backends.keys().map(|x| x.as_str()).collect()
+    }
+
+    pub fn create(&self, name: &str) -> Result<Box<dyn DynExecutor>, Error> {
+        let factory = self.backends.get(name).ok_or_else(|| {
+            Error::Validation(format!(
+                "Unknown backend {}, available backends are {:?}",
+                name,
+                self.names()
+            ))
+        })?;
+        factory()
+    }
+}
+
+impl Default for Registry {
+    fn default() -> Registry {
+        Registry::new()
+    }
+}
+
+impl Debug for Registry {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.debug_struct("Registry")
+            .field("backends", &self.names())
+            .finish()
+    }
+}
@@ -0,0 +1,44 @@
+// Copyright 2018 | Dario Ostuni <dario.ostuni@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use executor::IO;
+use std::error;
+use std::fmt::{self, Display, Formatter};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    Validation(String),
+    TypeMismatch(String),
+    MissingBinding(IO, String),
+    OutOfBounds(String),
+    Execution(String),
+    Device(String),
+    Compilation(String),
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            Error::TypeMismatch(ref name) => write!(f, "Type mismatch for {}", name),
+            Error::MissingBinding(kind, ref name) => write!(f, "Missing {} {}", kind, name),
+            Error::Validation(ref message)
+            | Error::OutOfBounds(ref message)
+            | Error::Execution(ref message)
+            | Error::Device(ref message)
+            | Error::Compilation(ref message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl error::Error for Error {}
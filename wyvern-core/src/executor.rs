@@ -12,9 +12,20 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use program::{Program, TokenType, TokenValue};
+use error::Error;
+use pipeline::Pipeline;
+use program::{
+    get_token_type, ConstantVector, DataType, Parameter, Program, TokenType, TokenValue,
+};
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt::{self, Display, Formatter};
 use std::hash::Hash;
+use std::str::FromStr;
 use std::sync::Arc;
+use std::time::Duration;
+use submission::Submission;
+use types::Type;
 
 pub trait Executor {
     type Config: Default + Clone;
@@ -28,10 +39,44 @@ pub trait Executor {
     fn new_resource(&self) -> Result<Arc<Self::Resource>, Self::Error>;
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub enum IO {
     Input,
     Output,
+    InOut,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub struct WorkSize {
+    pub groups: u32,
+    pub group_size: u32,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Report {
+    pub wall_time: Duration,
+    pub compile_time: Duration,
+    pub run_time: Duration,
+    pub work_size: WorkSize,
+    pub workers: u32,
+    pub uploaded: u64,
+    pub downloaded: u64,
+    pub op_counts: Option<BTreeMap<String, u64>>,
+}
+
+pub type Overrides = Vec<(String, IO, TokenValue)>;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BatchRun<R> {
+    pub outputs: BTreeMap<String, TokenValue>,
+    pub report: R,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Convergence<R> {
+    pub iterations: u32,
+    pub converged: bool,
+    pub report: R,
 }
 
 pub trait Executable {
@@ -43,14 +88,386 @@ pub trait Executable {
         name: S,
         kind: IO,
         res: Arc<Self::Resource>,
-    ) -> Option<Arc<Self::Resource>>;
+    ) -> Result<Option<Arc<Self::Resource>>, Self::Error>;
     fn unbind<S: ToString>(&mut self, name: S, kind: IO) -> Option<Arc<Self::Resource>>;
+    fn binding<S: ToString>(&self, name: S, kind: IO) -> Option<Arc<Self::Resource>>;
+    fn work_size(&self) -> WorkSize;
+    fn set_work_size(&mut self, work_size: Option<WorkSize>);
+    fn parameters(&self) -> Vec<Parameter>;
+    fn check_bindings(&self) -> Result<(), Self::Error>;
     fn run(&mut self) -> Result<Self::Report, Self::Error>;
+
+    /// Starts a run and returns without waiting for it to finish. The resources bound to the
+    /// executable stay locked until the run completes, so any access to them through the
+    /// `Resource` API, such as `set_data`, `resize` or `get_data`, blocks until then.
+    fn submit(&mut self) -> Result<Submission<Self::Report, Self::Error>, Self::Error> {
+        Ok(Submission::ready(self.run()))
+    }
+
+    fn run_pipeline(pipeline: &mut Pipeline<Self>) -> Result<Vec<Self::Report>, Self::Error>
+    where
+        Self: Sized,
+    {
+        let mut reports: Vec<_> = (0..pipeline.len()).map(|_| None).collect();
+        for index in pipeline.stages().concat() {
+            reports[index] = Some(pipeline.node_mut(index).run()?);
+        }
+        Ok(reports.into_iter().flatten().collect())
+    }
+
+    fn run_batch(&mut self, runs: &[Overrides]) -> Result<Vec<BatchRun<Self::Report>>, Self::Error>
+    where
+        Self: Sized,
+        Self::Error: From<Error>,
+    {
+        let parameters = self.parameters();
+        let saved: Vec<_> = parameters
+            .iter()
+            .filter_map(|x| self.binding(&x.name, x.kind))
+            .map(|x| {
+                let value = x.get_data();
+                (x, value)
+            })
+            .collect();
+        let mut results = Vec::new();
+        for overrides in runs {
+            let result = run_overridden(self, &parameters, overrides);
+            for (resource, value) in &saved {
+                resource.set_data(value.clone())?;
+            }
+            results.push(result?);
+        }
+        Ok(results)
+    }
+
+    fn run_until<T: Type, F: FnMut(T) -> bool>(
+        &mut self,
+        flag: &str,
+        max_iterations: Option<u32>,
+        mut predicate: F,
+    ) -> Result<Convergence<Self::Report>, Self::Error>
+    where
+        Self: Sized,
+        Self::Error: From<Error>,
+    {
+        let parameter = convergence_flag::<T>(&self.parameters(), flag)?;
+        let resource = self
+            .binding(flag, parameter.kind)
+            .ok_or_else(|| Error::MissingBinding(parameter.kind, flag.to_string()))?;
+        let mut iterations = 0;
+        loop {
+            let report = self.run()?;
+            iterations += 1;
+            let converged = predicate(resource.map(|x: &[T]| x[0])?);
+            if converged || max_iterations.is_some_and(|x| iterations >= x) {
+                return Ok(Convergence {
+                    iterations,
+                    converged,
+                    report,
+                });
+            }
+        }
+    }
 }
 
 pub trait Resource: Eq + Hash {
     fn clear(&self);
     fn token_type(&self) -> TokenType;
-    fn set_data(&self, value: TokenValue);
+    fn set_data(&self, value: TokenValue) -> Result<(), Error>;
     fn get_data(&self) -> TokenValue;
+    fn map<T: Type, R, F: FnOnce(&[T]) -> R>(&self, f: F) -> Result<R, Error>;
+    fn map_mut<T: Type, R, F: FnOnce(&mut [T]) -> R>(&self, f: F) -> Result<R, Error>;
+    fn resize<T: Type>(&self, len: usize) -> Result<(), Error>;
+    fn reserve<T: Type>(&self, capacity: usize) -> Result<(), Error>;
+
+    fn update<T: Type>(&self, offset: usize, data: &[T]) -> Result<(), Error> {
+        self.map_mut(|x: &mut [T]| {
+            let len = x.len();
+            match x.get_mut(offset..offset + data.len()) {
+                Some(x) => {
+                    x.copy_from_slice(data);
+                    Ok(())
+                }
+                None => Err(Error::OutOfBounds(format!(
+                    "Update of {} elements at offset {} exceeds the resource length {}",
+                    data.len(),
+                    offset,
+                    len
+                ))),
+            }
+        })?
+    }
+
+    fn write_slice<T: Type>(&self, data: &[T]) -> Result<(), Error> {
+        self.resize::<T>(data.len())?;
+        self.update(0, data)
+    }
+
+    fn read_into<T: Type>(&self, data: &mut [T]) -> Result<(), Error> {
+        self.map(|x: &[T]| {
+            if x.len() == data.len() {
+                data.copy_from_slice(x);
+                Ok(())
+            } else {
+                Err(Error::OutOfBounds(format!(
+                    "Resource holds {} elements but the destination has {}",
+                    x.len(),
+                    data.len()
+                )))
+            }
+        })?
+    }
+}
+
+impl IO {
+    pub fn reads(self) -> bool {
+        self != IO::Output
+    }
+
+    pub fn writes(self) -> bool {
+        self != IO::Input
+    }
+}
+
+impl WorkSize {
+    pub fn new(groups: u32, group_size: u32) -> WorkSize {
+        WorkSize { groups, group_size }
+    }
+
+    pub fn clamped(self, max_groups: u32, max_group_size: u32) -> WorkSize {
+        WorkSize {
+            groups: self.groups.min(max_groups),
+            group_size: self.group_size.min(max_group_size),
+        }
+    }
+
+    pub fn for_workers(workers: u32, group_size: u32) -> WorkSize {
+        let group_size = group_size.max(1);
+        WorkSize {
+            groups: workers.div_ceil(group_size).max(1),
+            group_size,
+        }
+    }
+
+    pub fn num_workers(&self) -> Option<u32> {
+        if self.groups == 0 || self.group_size == 0 {
+            None
+        } else {
+            self.groups.checked_mul(self.group_size)
+        }
+    }
+}
+
+/// Checks a resource at bind time. Resources without data are accepted here, as their data may
+/// be set after binding, and are rejected by `check_bindings` if they are read.
+pub fn check_binding<R: Resource>(
+    program: &Program,
+    name: &str,
+    kind: IO,
+    resource: &R,
+) -> Result<(), Error> {
+    let parameter = program.parameter(name, kind);
+    if parameter.is_some() && resource.token_type() == TokenType::Null {
+        return Ok(());
+    }
+    check_resource(parameter, name, kind, resource)
+}
+
+fn check_resource<R: Resource>(
+    parameter: Option<Parameter>,
+    name: &str,
+    kind: IO,
+    resource: &R,
+) -> Result<(), Error> {
+    check_parameter(
+        parameter,
+        name,
+        kind,
+        resource.token_type(),
+        |data_type| match data_type {
+            DataType::Bool => resource.map(|x: &[bool]| x.len()),
+            DataType::I32 => resource.map(|x: &[i32]| x.len()),
+            DataType::U32 => resource.map(|x: &[u32]| x.len()),
+            DataType::F32 => resource.map(|x: &[f32]| x.len()),
+        },
+    )
+}
+
+pub fn check_override(
+    parameters: &[Parameter],
+    name: &str,
+    kind: IO,
+    value: &TokenValue,
+) -> Result<(), Error> {
+    if !kind.reads() {
+        return Err(Error::Validation(format!(
+            "Cannot override {} {}",
+            kind, name
+        )));
+    }
+    check_parameter(
+        parameters
+            .iter()
+            .find(|x| x.name == name && x.kind == kind)
+            .cloned(),
+        name,
+        kind,
+        get_token_type(value),
+        |_| {
+            Ok(match *value {
+                TokenValue::Vector(ConstantVector::Bool(ref x)) => x.len(),
+                TokenValue::Vector(ConstantVector::I32(ref x)) => x.len(),
+                TokenValue::Vector(ConstantVector::U32(ref x)) => x.len(),
+                TokenValue::Vector(ConstantVector::F32(ref x)) => x.len(),
+                _ => 1,
+            })
+        },
+    )
+}
+
+pub fn check_parameter<F: FnOnce(DataType) -> Result<usize, Error>>(
+    parameter: Option<Parameter>,
+    name: &str,
+    kind: IO,
+    found: TokenType,
+    len: F,
+) -> Result<(), Error> {
+    let parameter = parameter
+        .ok_or_else(|| Error::Validation(format!("The program has no {} named {}", kind, name)))?;
+    if found == TokenType::Null && !kind.reads() {
+        return Ok(());
+    }
+    if found != parameter.token_type {
+        return Err(Error::TypeMismatch(format!(
+            "{}: expected {:?}, found {:?}",
+            name, parameter.token_type, found
+        )));
+    }
+    if let (true, Some(max_size)) = (parameter.array, parameter.max_size) {
+        let len = len(parameter.data_type)?;
+        if len > max_size as usize {
+            return Err(Error::OutOfBounds(format!(
+                "Array bound to {} has {} elements but at most {} are allowed",
+                name, len, max_size
+            )));
+        }
+    }
+    Ok(())
+}
+
+pub fn check_bindings<R: Resource, F: Fn(IO, &str) -> Option<Arc<R>>>(
+    program: &Program,
+    binding: F,
+) -> Result<(), Error> {
+    for parameter in program.parameters() {
+        let (kind, name) = (parameter.kind, parameter.name);
+        let resource =
+            binding(kind, &name).ok_or_else(|| Error::MissingBinding(kind, name.clone()))?;
+        check_resource(program.parameter(&name, kind), &name, kind, &*resource)?;
+    }
+    Ok(())
+}
+
+fn run_overridden<E: Executable>(
+    executable: &mut E,
+    parameters: &[Parameter],
+    overrides: &[(String, IO, TokenValue)],
+) -> Result<BatchRun<E::Report>, E::Error>
+where
+    E::Error: From<Error>,
+{
+    for &(ref name, kind, ref value) in overrides {
+        check_override(parameters, name, kind, value)?;
+        executable
+            .binding(name, kind)
+            .ok_or_else(|| Error::MissingBinding(kind, name.clone()))?
+            .set_data(value.clone())?;
+    }
+    let report = executable.run()?;
+    let mut outputs = BTreeMap::new();
+    for parameter in parameters.iter().filter(|x| x.kind.writes()) {
+        if let Some(resource) = executable.binding(&parameter.name, parameter.kind) {
+            outputs.insert(parameter.name.clone(), resource.get_data());
+        }
+    }
+    Ok(BatchRun { outputs, report })
+}
+
+pub fn convergence_flag<T: Type>(parameters: &[Parameter], flag: &str) -> Result<Parameter, Error> {
+    let parameter = parameters
+        .iter()
+        .find(|x| x.name == flag && x.kind.writes())
+        .ok_or_else(|| Error::Validation(format!("The program has no output named {}", flag)))?;
+    let expected = TokenType::Variable(T::data_type());
+    if parameter.token_type != expected {
+        return Err(Error::TypeMismatch(format!(
+            "{}: expected {:?}, found {:?}",
+            flag, expected, parameter.token_type
+        )));
+    }
+    Ok(parameter.clone())
+}
+
+pub fn access_error<T: Type>(token_type: TokenType) -> Error {
+    Error::TypeMismatch(format!(
+        "resource of type {:?} accessed as {:?}",
+        token_type,
+        T::data_type()
+    ))
+}
+
+pub fn env_override<T: FromStr>(name: &str) -> Result<Option<T>, Error> {
+    match env::var(name) {
+        Ok(value) => value
+            .parse()
+            .map(Some)
+            .map_err(|_| Error::Validation(format!("Invalid value {:?} for {}", value, name))),
+        Err(_) => Ok(None),
+    }
+}
+
+pub fn env_flag(name: &str) -> Result<Option<bool>, Error> {
+    match env::var(name) {
+        Ok(value) => match value.to_lowercase().as_str() {
+            "1" | "true" | "on" | "yes" => Ok(Some(true)),
+            "0" | "false" | "off" | "no" => Ok(Some(false)),
+            _ => Err(Error::Validation(format!(
+                "Invalid value {:?} for {}",
+                value, name
+            ))),
+        },
+        Err(_) => Ok(None),
+    }
+}
+
+impl Display for IO {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            IO::Input => write!(f, "input"),
+            IO::Output => write!(f, "output"),
+            IO::InOut => write!(f, "inout"),
+        }
+    }
+}
+
+impl Display for Report {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Completed in {:?} (compile {:?}, run {:?}) with {} workers in {} groups, {} bytes uploaded, {} bytes downloaded",
+            self.wall_time,
+            self.compile_time,
+            self.run_time,
+            self.workers,
+            self.work_size.groups,
+            self.uploaded,
+            self.downloaded
+        )?;
+        if let Some(ref op_counts) = self.op_counts {
+            for (op, count) in op_counts {
+                write!(f, "\n  {}: {}", op, count)?;
+            }
+        }
+        Ok(())
+    }
 }
@@ -18,7 +18,16 @@ extern crate num_traits;
 extern crate rand;
 extern crate serde;
 
+pub mod analysis;
 pub mod builder;
+#[cfg(feature = "conformance")]
+pub mod conformance;
+pub mod dynamic;
+pub mod error;
 pub mod executor;
+pub mod pipeline;
 pub mod program;
+pub mod semantics;
+pub mod split;
+pub mod submission;
 pub mod types;
@@ -0,0 +1,126 @@
+// Copyright 2018 | Dario Ostuni <dario.ostuni@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use executor::Executable;
+use std::collections::{BTreeSet, HashMap};
+use std::fmt::{self, Debug, Formatter};
+use std::sync::Arc;
+
+pub struct Pipeline<E: Executable> {
+    nodes: Vec<E>,
+}
+
+impl<E: Executable> Pipeline<E> {
+    pub fn new() -> Pipeline<E> {
+        Pipeline { nodes: Vec::new() }
+    }
+
+    pub fn add(&mut self, executable: E) -> usize {
+        self.nodes.push(executable);
+        self.nodes.len() - 1
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    pub fn node(&self, index: usize) -> &E {
+        &self.nodes[index]
+    }
+
+    pub fn node_mut(&mut self, index: usize) -> &mut E {
+        &mut self.nodes[index]
+    }
+
+    pub fn nodes(&self) -> &[E] {
+        &self.nodes
+    }
+
+    pub fn nodes_mut(&mut self) -> &mut [E] {
+        &mut self.nodes
+    }
+
+    pub fn into_nodes(self) -> Vec<E> {
+        self.nodes
+    }
+
+    pub fn dependencies(&self) -> Vec<BTreeSet<usize>> {
+        let mut writer: HashMap<Arc<E::Resource>, usize> = HashMap::new();
+        let mut readers: HashMap<Arc<E::Resource>, Vec<usize>> = HashMap::new();
+        let mut dependencies = Vec::new();
+        for (index, node) in self.nodes.iter().enumerate() {
+            let mut depends = BTreeSet::new();
+            let mut accesses = Vec::new();
+            for parameter in node.parameters() {
+                let resource = match node.binding(&parameter.name, parameter.kind) {
+                    Some(resource) => resource,
+                    None => continue,
+                };
+                depends.extend(writer.get(&resource));
+                if parameter.kind.writes() {
+                    depends.extend(readers.get(&resource).into_iter().flatten());
+                }
+                accesses.push((resource, parameter.kind));
+            }
+            for (resource, kind) in accesses {
+                if kind.writes() {
+                    writer.insert(resource.clone(), index);
+                    readers.remove(&resource);
+                } else {
+                    readers.entry(resource).or_default().push(index);
+                }
+            }
+            depends.remove(&index);
+            dependencies.push(depends);
+        }
+        dependencies
+    }
+
+    pub fn stages(&self) -> Vec<Vec<usize>> {
+        let mut level: Vec<usize> = Vec::new();
+        let mut stages: Vec<Vec<usize>> = Vec::new();
+        for (index, depends) in self.dependencies().into_iter().enumerate() {
+            let stage = depends.iter().map(|&x| level[x] + 1).max().unwrap_or(0);
+            if stage == stages.len() {
+                stages.push(Vec::new());
+            }
+            stages[stage].push(index);
+            level.push(stage);
+        }
+        stages
+    }
+
+    pub fn run(&mut self) -> Result<Vec<E::Report>, E::Error> {
+        E::run_pipeline(self)
+    }
+}
+
+impl<E: Executable> Default for Pipeline<E> {
+    fn default() -> Pipeline<E> {
+        Pipeline::new()
+    }
+}
+
+impl<E: Executable> Debug for Pipeline<E> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.debug_struct("Pipeline")
+            .field("nodes", &self.nodes.len())
+            .field("stages", &self.stages())
+            .finish()
+    }
+}
@@ -12,6 +12,8 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use executor::IO;
+use split::is_hidden;
 use std::collections::HashMap;
 
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
@@ -21,6 +23,18 @@ pub struct Program {
     pub storage: HashMap<TokenId, StorageType>,
     pub input: HashMap<String, TokenId>,
     pub output: HashMap<String, TokenId>,
+    #[serde(default)]
+    pub inout: HashMap<String, TokenId>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Parameter {
+    pub name: String,
+    pub kind: IO,
+    pub token_type: TokenType,
+    pub data_type: DataType,
+    pub array: bool,
+    pub max_size: Option<u32>,
 }
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
@@ -98,6 +112,7 @@ pub enum Op {
     While(LabelId, Vec<Op>, TokenId, LabelId, Vec<Op>, LabelId),
     MemoryBarrier,
     ControlBarrier,
+    GlobalBarrier,
     WorkerId(TokenId),
     NumWorkers(TokenId),
     Load(TokenId, TokenId),
@@ -133,6 +148,124 @@ pub enum Op {
     Ge(TokenId, TokenId, TokenId),
 }
 
+impl Program {
+    pub fn op(&self, id: usize) -> Option<&Op> {
+        fn find<'a>(block: &'a [Op], id: usize, next: &mut usize) -> Option<&'a Op> {
+            for op in block {
+                if *next == id {
+                    return Some(op);
+                }
+                *next += 1;
+                let found = match *op {
+                    Op::If(ref c, _, _, ref a, _) => {
+                        find(c, id, next).or_else(|| find(a, id, next))
+                    }
+                    Op::IfElse(ref c, _, _, ref a, _, ref b, _) => find(c, id, next)
+                        .or_else(|| find(a, id, next))
+                        .or_else(|| find(b, id, next)),
+                    Op::While(_, ref c, _, _, ref a, _) => {
+                        find(c, id, next).or_else(|| find(a, id, next))
+                    }
+                    _ => None,
+                };
+                if found.is_some() {
+                    return found;
+                }
+            }
+            None
+        }
+        find(&self.operation, id, &mut 0)
+    }
+
+    pub fn parameter(&self, name: &str, kind: IO) -> Option<Parameter> {
+        let id = match kind {
+            IO::Input => self.input.get(name),
+            IO::Output => self.output.get(name),
+            IO::InOut => self.inout.get(name),
+        }?;
+        let (token_type, data_type, max_size) = match *self.storage.get(id)? {
+            StorageType::Variable(ty) => (TokenType::Variable(ty), ty, 0),
+            StorageType::SharedArray(ty, max_size) => (TokenType::Array(ty), ty, max_size),
+            StorageType::PrivateArray(_, _) => return None,
+        };
+        Some(Parameter {
+            name: name.to_string(),
+            kind,
+            token_type,
+            data_type,
+            array: token_type == TokenType::Array(data_type),
+            max_size: if max_size > 0 { Some(max_size) } else { None },
+        })
+    }
+
+    pub fn io(&self) -> Vec<(IO, &String, TokenId)> {
+        let mut io: Vec<_> = self
+            .input
+            .iter()
+            .map(|(name, id)| (IO::Input, name, *id))
+            .chain(self.output.iter().map(|(name, id)| (IO::Output, name, *id)))
+            .chain(self.inout.iter().map(|(name, id)| (IO::InOut, name, *id)))
+            .collect();
+        io.sort_by_key(|&(kind, name, _)| (kind, name.clone()));
+        io
+    }
+
+    pub fn parameters(&self) -> Vec<Parameter> {
+        self.io()
+            .into_iter()
+            .filter(|&(_, name, _)| !is_hidden(name))
+            .filter_map(|(kind, name, _)| self.parameter(name, kind))
+            .collect()
+    }
+}
+
+impl Op {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Op::Phi(..) => "Phi",
+            Op::If(..) => "If",
+            Op::IfElse(..) => "IfElse",
+            Op::While(..) => "While",
+            Op::MemoryBarrier => "MemoryBarrier",
+            Op::ControlBarrier => "ControlBarrier",
+            Op::GlobalBarrier => "GlobalBarrier",
+            Op::WorkerId(..) => "WorkerId",
+            Op::NumWorkers(..) => "NumWorkers",
+            Op::Load(..) => "Load",
+            Op::Store(..) => "Store",
+            Op::ArrayNew(..) => "ArrayNew",
+            Op::ArrayLen(..) => "ArrayLen",
+            Op::ArrayLoad(..) => "ArrayLoad",
+            Op::ArrayStore(..) => "ArrayStore",
+            Op::Constant(..) => "Constant",
+            Op::U32fromF32(..) => "U32fromF32",
+            Op::I32fromF32(..) => "I32fromF32",
+            Op::F32fromU32(..) => "F32fromU32",
+            Op::F32fromI32(..) => "F32fromI32",
+            Op::I32fromU32(..) => "I32fromU32",
+            Op::U32fromI32(..) => "U32fromI32",
+            Op::Add(..) => "Add",
+            Op::Sub(..) => "Sub",
+            Op::Mul(..) => "Mul",
+            Op::Div(..) => "Div",
+            Op::Rem(..) => "Rem",
+            Op::Neg(..) => "Neg",
+            Op::Not(..) => "Not",
+            Op::Shl(..) => "Shl",
+            Op::Shr(..) => "Shr",
+            Op::BitAnd(..) => "BitAnd",
+            Op::BitOr(..) => "BitOr",
+            Op::BitXor(..) => "BitXor",
+            Op::Eq(..) => "Eq",
+            Op::Ne(..) => "Ne",
+            Op::Lt(..) => "Lt",
+            Op::Le(..) => "Le",
+            Op::Gt(..) => "Gt",
+            Op::Ge(..) => "Ge",
+        }
+    }
+}
+
 impl TokenId {
     pub(crate) fn next(&mut self) -> TokenId {
         let prev = *self;
@@ -166,3 +299,18 @@ pub fn get_token_type(value: &TokenValue) -> TokenType {
         }),
     }
 }
+
+pub fn byte_size(value: &TokenValue) -> u64 {
+    match *value {
+        TokenValue::Null => 0,
+        TokenValue::Scalar(_) => 4,
+        TokenValue::Vector(ref x) => {
+            4 * match *x {
+                ConstantVector::Bool(ref v) => v.len(),
+                ConstantVector::I32(ref v) => v.len(),
+                ConstantVector::U32(ref v) => v.len(),
+                ConstantVector::F32(ref v) => v.len(),
+            } as u64
+        }
+    }
+}
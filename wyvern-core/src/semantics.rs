@@ -0,0 +1,77 @@
+// Copyright 2018 | Dario Ostuni <dario.ostuni@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+pub const SHIFT_MASK: u32 = 31;
+
+pub fn div_u32(a: u32, b: u32) -> u32 {
+    a.checked_div(b).unwrap_or(u32::MAX)
+}
+
+pub fn div_i32(a: i32, b: i32) -> i32 {
+    if b == 0 {
+        -1
+    } else {
+        a.wrapping_div(b)
+    }
+}
+
+pub fn rem_u32(a: u32, b: u32) -> u32 {
+    a.checked_rem(b).unwrap_or(a)
+}
+
+pub fn rem_i32(a: i32, b: i32) -> i32 {
+    if b == 0 {
+        a
+    } else {
+        a.wrapping_rem(b)
+    }
+}
+
+pub fn rem_f32(a: f32, b: f32) -> f32 {
+    a % b
+}
+
+pub fn shl_u32(a: u32, b: u32) -> u32 {
+    a << (b & SHIFT_MASK)
+}
+
+pub fn shr_u32(a: u32, b: u32) -> u32 {
+    a >> (b & SHIFT_MASK)
+}
+
+pub fn shr_i32(a: i32, b: u32) -> i32 {
+    a >> (b & SHIFT_MASK)
+}
+
+pub fn u32_from_f32(a: f32) -> u32 {
+    if a.is_nan() || a <= 0.0 {
+        0
+    } else if a >= 4_294_967_296.0 {
+        u32::MAX
+    } else {
+        a as u32
+    }
+}
+
+pub fn i32_from_f32(a: f32) -> i32 {
+    if a.is_nan() {
+        0
+    } else if a <= -2_147_483_648.0 {
+        i32::MIN
+    } else if a >= 2_147_483_648.0 {
+        i32::MAX
+    } else {
+        a as i32
+    }
+}
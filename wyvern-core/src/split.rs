@@ -0,0 +1,811 @@
+// Copyright 2018 | Dario Ostuni <dario.ostuni@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use error::Error;
+use executor::{Overrides, IO};
+use program::{
+    ConstantScalar, ConstantVector, DataType, LabelId, Op, Program, StorageType, TokenId,
+    TokenType, TokenValue,
+};
+use std::collections::{HashMap, HashSet};
+use std::mem;
+use std::slice;
+
+pub const PHASE: &str = "__wyvern_phase";
+pub const NEXT: &str = "__wyvern_next";
+pub const DONE: u32 = u32::MAX;
+
+const HIDDEN: &str = "__wyvern_";
+const LANES: [DataType; 3] = [DataType::U32, DataType::I32, DataType::F32];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Buffer {
+    pub name: String,
+    pub data_type: DataType,
+    pub size: u32,
+    pub per_worker: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Phases {
+    pub count: u32,
+    pub buffers: Vec<Buffer>,
+}
+
+struct Splitter {
+    program: Program,
+    next_token: u32,
+    next_label: u32,
+    marker: TokenId,
+    phases: Vec<Vec<Op>>,
+    jumps: Vec<(u32, Vec<TokenId>)>,
+    values: HashSet<TokenId>,
+    variables: HashSet<TokenId>,
+    arrays: HashSet<TokenId>,
+    offsets: HashMap<TokenId, (usize, u32)>,
+    lengths: HashMap<TokenId, u32>,
+    strides: [u32; 3],
+    spills: [TokenId; 3],
+    next: TokenId,
+}
+
+impl Phases {
+    pub fn initial(&self, num_workers: u32) -> Overrides {
+        let mut values = vec![
+            (
+                PHASE.to_string(),
+                IO::Input,
+                TokenValue::Scalar(ConstantScalar::U32(0)),
+            ),
+            (
+                NEXT.to_string(),
+                IO::Output,
+                TokenValue::Vector(ConstantVector::U32(vec![DONE; num_workers as usize])),
+            ),
+        ];
+        for buffer in &self.buffers {
+            let size = if buffer.per_worker {
+                buffer.size as usize * num_workers as usize
+            } else {
+                buffer.size as usize
+            };
+            let vector = match buffer.data_type {
+                DataType::Bool => ConstantVector::Bool(vec![false; size]),
+                DataType::I32 => ConstantVector::I32(vec![0; size]),
+                DataType::U32 => ConstantVector::U32(vec![0; size]),
+                DataType::F32 => ConstantVector::F32(vec![0.0; size]),
+            };
+            values.push((buffer.name.clone(), IO::InOut, TokenValue::Vector(vector)));
+        }
+        values
+    }
+}
+
+/// Returns the phase every worker continues at, given the target each worker stored in `NEXT`.
+/// Workers that reached different global barriers, or the same one a different number of
+/// times, cannot be synchronized by splitting, so any disagreement is an execution error.
+pub fn next_phase(targets: &[u32]) -> Result<u32, Error> {
+    let first = targets.first().cloned().unwrap_or(DONE);
+    match targets.iter().position(|&x| x != first) {
+        Some(worker) => Err(Error::Execution(format!(
+            "GlobalBarrier reached on non-uniform control flow: worker 0 continues at {} but worker {} at {}",
+            describe(first),
+            worker,
+            describe(targets[worker])
+        ))),
+        None => Ok(first),
+    }
+}
+
+pub fn is_hidden(name: &str) -> bool {
+    name.starts_with(HIDDEN)
+}
+
+pub fn has_global_barrier(block: &[Op]) -> bool {
+    block.iter().any(|op| match *op {
+        Op::GlobalBarrier => true,
+        Op::If(ref c, _, _, ref a, _) | Op::While(_, ref c, _, _, ref a, _) => {
+            has_global_barrier(c) || has_global_barrier(a)
+        }
+        Op::IfElse(ref c, _, _, ref a, _, ref b, _) => {
+            has_global_barrier(c) || has_global_barrier(a) || has_global_barrier(b)
+        }
+        _ => false,
+    })
+}
+
+pub fn split(mut program: Program) -> Result<(Program, Option<Phases>), Error> {
+    if !has_global_barrier(&program.operation) {
+        return Ok((program, None));
+    }
+    let mut operation = mem::take(&mut program.operation);
+    let mut next_label = 1;
+    let mut phi = false;
+    walk(&mut operation, &mut |op| {
+        phi |= matches!(*op, Op::Phi(..));
+        for label in labels(op) {
+            next_label = next_label.max(label.0 + 1);
+        }
+    });
+    if phi {
+        return Err(Error::Validation(
+            "Phi cannot be combined with GlobalBarrier".into(),
+        ));
+    }
+    let next_token = program
+        .symbol
+        .keys()
+        .chain(program.storage.keys())
+        .map(|x| x.0 + 1)
+        .max()
+        .unwrap_or(0);
+    let mut splitter = Splitter {
+        program,
+        next_token: next_token + 1,
+        next_label,
+        marker: TokenId(next_token),
+        phases: Vec::new(),
+        jumps: Vec::new(),
+        values: HashSet::new(),
+        variables: HashSet::new(),
+        arrays: HashSet::new(),
+        offsets: HashMap::new(),
+        lengths: HashMap::new(),
+        strides: [0; 3],
+        spills: [TokenId::default(); 3],
+        next: TokenId::default(),
+    };
+    splitter.phase(&operation, DONE)?;
+    splitter
+        .finish()
+        .map(|(program, phases)| (program, Some(phases)))
+}
+
+impl Splitter {
+    fn phase(&mut self, ops: &[Op], next: u32) -> Result<u32, Error> {
+        if ops.is_empty() {
+            return Ok(next);
+        }
+        let phase = self.reserve();
+        let body = self.block(ops, next, &mut Vec::new())?;
+        self.phases[phase as usize] = body;
+        Ok(phase)
+    }
+
+    fn block(&mut self, ops: &[Op], next: u32, scope: &mut Vec<TokenId>) -> Result<Vec<Op>, Error> {
+        let mut result = Vec::new();
+        for (index, op) in ops.iter().enumerate() {
+            if !has_global_barrier(slice::from_ref(op)) {
+                scope.extend(defined(op));
+                result.push(op.clone());
+                continue;
+            }
+            let next = self.phase(&ops[index + 1..], next)?;
+            match *op {
+                Op::GlobalBarrier => {
+                    let jump = self.jump(next, scope);
+                    result.push(jump);
+                }
+                Op::If(ref c, cond, l0, ref a, lend) => {
+                    let scope = condition(c, scope)?;
+                    let then = self.block(a, next, &mut scope.clone())?;
+                    let other = vec![self.jump(next, &scope)];
+                    let l1 = self.label();
+                    result.push(Op::IfElse(c.clone(), cond, l0, then, l1, other, lend));
+                }
+                Op::IfElse(ref c, cond, l0, ref a, l1, ref b, lend) => {
+                    let scope = condition(c, scope)?;
+                    let then = self.block(a, next, &mut scope.clone())?;
+                    let other = self.block(b, next, &mut scope.clone())?;
+                    result.push(Op::IfElse(c.clone(), cond, l0, then, l1, other, lend));
+                }
+                Op::While(_, ref c, cond, l0, ref a, lend) => {
+                    let inner = condition(c, &[])?;
+                    let head = self.reserve();
+                    let body = self.block(a, head, &mut inner.clone())?;
+                    let exit = vec![self.jump(next, &inner)];
+                    let l1 = self.label();
+                    self.phases[head as usize] =
+                        vec![Op::IfElse(c.clone(), cond, l0, body, l1, exit, lend)];
+                    let jump = self.jump(head, scope);
+                    result.push(jump);
+                }
+                _ => unreachable!(),
+            }
+            return Ok(result);
+        }
+        let jump = self.jump(next, scope);
+        result.push(jump);
+        Ok(result)
+    }
+
+    fn finish(mut self) -> Result<(Program, Phases), Error> {
+        let mut phases = mem::take(&mut self.phases);
+        let io: HashSet<TokenId> = self.program.io().into_iter().map(|(_, _, id)| id).collect();
+        let mut used = Vec::new();
+        let mut targets = Vec::new();
+        let mut sizes = HashMap::new();
+        let mut constants = HashMap::new();
+        for body in &mut phases {
+            let mut defined = HashSet::new();
+            let mut operands = HashSet::new();
+            let mut target = Vec::new();
+            let (marker, jumps) = (self.marker, &self.jumps);
+            walk(body, &mut |op| {
+                match *op {
+                    Op::Constant(t, ConstantScalar::U32(jump)) if t == marker => {
+                        target.push(jumps[jump as usize].0);
+                        return;
+                    }
+                    Op::Constant(t, ConstantScalar::U32(value)) => {
+                        constants.insert(t, value);
+                    }
+                    Op::ArrayNew(t, size, _, _, _) => {
+                        sizes.insert(t, size);
+                    }
+                    _ => {}
+                }
+                defined.extend(self::defined(op));
+                operands.extend(self::operands(op).into_iter().map(|x| *x));
+            });
+            let mut free: Vec<TokenId> = operands
+                .into_iter()
+                .filter(|x| !defined.contains(x) && !io.contains(x))
+                .collect();
+            free.sort_by_key(|x| x.0);
+            used.push(free);
+            targets.push(target);
+        }
+        let mut buffers = Vec::new();
+        let mut shared = Vec::new();
+        let mut uses = HashMap::new();
+        for (phase, free) in used.iter().enumerate() {
+            let reentrant = reaches(&targets, phase);
+            for &id in free {
+                match self.program.storage.get(&id) {
+                    Some(&StorageType::Variable(_)) => {
+                        let count = uses.entry(id).or_insert(0);
+                        *count += if reentrant { 2 } else { 1 };
+                        if *count > 1 {
+                            self.variables.insert(id);
+                        }
+                    }
+                    Some(&StorageType::PrivateArray(..)) => {
+                        self.arrays.insert(id);
+                    }
+                    Some(&StorageType::SharedArray(..)) => {
+                        if !shared.contains(&id) {
+                            shared.push(id);
+                        }
+                    }
+                    None => {
+                        self.values.insert(id);
+                    }
+                }
+            }
+        }
+        shared.sort_by_key(|x| x.0);
+        for id in shared {
+            let (data_type, max_size) = match self.program.storage[&id] {
+                StorageType::SharedArray(ty, max_size) => (ty, max_size),
+                _ => unreachable!(),
+            };
+            let size = sizes.get(&id).and_then(|x| constants.get(x));
+            if max_size == 0 || size != Some(&max_size) {
+                return Err(Error::Validation(format!(
+                    "Shared array {:?} used across a GlobalBarrier must have a constant size equal to its maximum size",
+                    id
+                )));
+            }
+            let name = format!("{}shared{}", HIDDEN, id.0);
+            self.program.inout.insert(name.clone(), id);
+            buffers.push(Buffer {
+                name,
+                data_type,
+                size: max_size,
+                per_worker: false,
+            });
+        }
+        let mut spilled: Vec<TokenId> = self
+            .values
+            .iter()
+            .chain(&self.variables)
+            .chain(&self.arrays)
+            .cloned()
+            .collect();
+        spilled.sort_by_key(|x| x.0);
+        for id in spilled {
+            match self.program.storage.get(&id) {
+                Some(&StorageType::PrivateArray(ty, max_size)) => {
+                    if max_size == 0 {
+                        return Err(Error::Validation(format!(
+                            "Private array {:?} used across a GlobalBarrier must have a maximum size",
+                            id
+                        )));
+                    }
+                    let length = self.allocate(DataType::U32, 1);
+                    self.lengths.insert(id, length.1);
+                    let elements = self.allocate(ty, max_size);
+                    self.offsets.insert(id, elements);
+                }
+                Some(&StorageType::Variable(ty)) => {
+                    let slot = self.allocate(ty, 1);
+                    self.offsets.insert(id, slot);
+                }
+                _ => match self.program.symbol.get(&id) {
+                    Some(&TokenType::Constant(ty)) => {
+                        let slot = self.allocate(ty, 1);
+                        self.offsets.insert(id, slot);
+                    }
+                    _ => {
+                        return Err(Error::Validation(format!(
+                            "{:?} cannot be kept across a GlobalBarrier",
+                            id
+                        )))
+                    }
+                },
+            }
+        }
+        for (lane, &ty) in LANES.iter().enumerate() {
+            if self.strides[lane] == 0 {
+                continue;
+            }
+            let name = format!("{}spill_{:?}", HIDDEN, ty).to_lowercase();
+            let id = self.token(TokenType::Array(ty));
+            self.program
+                .storage
+                .insert(id, StorageType::SharedArray(ty, 0));
+            self.program.inout.insert(name.clone(), id);
+            self.spills[lane] = id;
+            buffers.push(Buffer {
+                name,
+                data_type: ty,
+                size: self.strides[lane],
+                per_worker: true,
+            });
+        }
+        let phase = self.variable(DataType::U32);
+        self.program.input.insert(PHASE.to_string(), phase);
+        self.next = self.token(TokenType::Array(DataType::U32));
+        self.program
+            .storage
+            .insert(self.next, StorageType::SharedArray(DataType::U32, 0));
+        self.program.output.insert(NEXT.to_string(), self.next);
+        let current = self.token(TokenType::Constant(DataType::U32));
+        let mut operation = vec![Op::Load(current, phase)];
+        let count = phases.len() as u32;
+        for (index, (mut body, free)) in phases.into_iter().zip(used).enumerate() {
+            let worker = self.token(TokenType::Constant(DataType::U32));
+            let mut code = vec![Op::WorkerId(worker)];
+            let mut renamed = HashMap::new();
+            let mut state = Vec::new();
+            for id in free {
+                if self.values.contains(&id) {
+                    let value = self.token(self.program.symbol[&id]);
+                    self.restore(&mut code, worker, id, value, None);
+                    renamed.insert(id, value);
+                } else if self.variables.contains(&id) {
+                    let value = self.token(TokenType::Constant(self.data_type(id)));
+                    self.restore(&mut code, worker, id, value, None);
+                    code.push(Op::Store(id, value));
+                    state.push(id);
+                } else if self.arrays.contains(&id) {
+                    self.restore_array(&mut code, worker, id);
+                    state.push(id);
+                }
+            }
+            walk(&mut body, &mut |op| {
+                for id in operands(op) {
+                    if let Some(value) = renamed.get(id) {
+                        *id = *value;
+                    }
+                }
+            });
+            let body = self.rewrite(body, worker, &state);
+            code.extend(body);
+            let mut cond = Vec::new();
+            let value = self.constant(&mut cond, index as u32);
+            let test = self.token(TokenType::Constant(DataType::Bool));
+            cond.push(Op::Eq(test, current, value));
+            let (l0, l1) = (self.label(), self.label());
+            operation.push(Op::If(cond, test, l0, code, l1));
+        }
+        self.program.operation = operation;
+        Ok((self.program, Phases { count, buffers }))
+    }
+
+    fn rewrite(&mut self, block: Vec<Op>, worker: TokenId, state: &[TokenId]) -> Vec<Op> {
+        let mut result = Vec::new();
+        for op in block {
+            match op {
+                Op::Constant(t, ConstantScalar::U32(jump)) if t == self.marker => {
+                    let (target, scope) = self.jumps[jump as usize].clone();
+                    self.transition(&mut result, worker, target, &scope, state);
+                }
+                Op::If(c, cond, l0, a, lend) => {
+                    let a = self.rewrite(a, worker, state);
+                    result.push(Op::If(c, cond, l0, a, lend));
+                }
+                Op::IfElse(c, cond, l0, a, l1, b, lend) => {
+                    let a = self.rewrite(a, worker, state);
+                    let b = self.rewrite(b, worker, state);
+                    result.push(Op::IfElse(c, cond, l0, a, l1, b, lend));
+                }
+                Op::While(lcond, c, cond, l0, a, lend) => {
+                    let a = self.rewrite(a, worker, state);
+                    result.push(Op::While(lcond, c, cond, l0, a, lend));
+                }
+                op => result.push(op),
+            }
+        }
+        result
+    }
+
+    fn transition(
+        &mut self,
+        ops: &mut Vec<Op>,
+        worker: TokenId,
+        target: u32,
+        scope: &[TokenId],
+        state: &[TokenId],
+    ) {
+        for &id in scope {
+            if self.values.contains(&id) {
+                self.save(ops, worker, id, id, None);
+            } else if self.arrays.contains(&id) {
+                self.save_array(ops, worker, id);
+            }
+        }
+        for &id in state {
+            if self.variables.contains(&id) {
+                let value = self.token(TokenType::Constant(self.data_type(id)));
+                ops.push(Op::Load(value, id));
+                self.save(ops, worker, id, value, None);
+            } else {
+                self.save_array(ops, worker, id);
+            }
+        }
+        let value = self.constant(ops, target);
+        ops.push(Op::ArrayStore(self.next, worker, value));
+    }
+
+    fn save(
+        &mut self,
+        ops: &mut Vec<Op>,
+        worker: TokenId,
+        id: TokenId,
+        value: TokenId,
+        element: Option<TokenId>,
+    ) {
+        let (lane, offset) = self.offsets[&id];
+        let index = self.index(ops, worker, lane, offset, element);
+        let spill = self.spills[lane];
+        if self.program.symbol[&value] == TokenType::Constant(DataType::Bool) {
+            let mut then = Vec::new();
+            let one = self.constant(&mut then, 1);
+            then.push(Op::ArrayStore(spill, index, one));
+            let mut other = Vec::new();
+            let zero = self.constant(&mut other, 0);
+            other.push(Op::ArrayStore(spill, index, zero));
+            let (l0, l1, lend) = (self.label(), self.label(), self.label());
+            ops.push(Op::IfElse(Vec::new(), value, l0, then, l1, other, lend));
+        } else {
+            ops.push(Op::ArrayStore(spill, index, value));
+        }
+    }
+
+    fn restore(
+        &mut self,
+        ops: &mut Vec<Op>,
+        worker: TokenId,
+        id: TokenId,
+        value: TokenId,
+        element: Option<TokenId>,
+    ) {
+        let (lane, offset) = self.offsets[&id];
+        let index = self.index(ops, worker, lane, offset, element);
+        let spill = self.spills[lane];
+        if self.program.symbol[&value] == TokenType::Constant(DataType::Bool) {
+            let raw = self.token(TokenType::Constant(DataType::U32));
+            ops.push(Op::ArrayLoad(raw, spill, index));
+            let zero = self.constant(ops, 0);
+            ops.push(Op::Ne(value, raw, zero));
+        } else {
+            ops.push(Op::ArrayLoad(value, spill, index));
+        }
+    }
+
+    fn save_array(&mut self, ops: &mut Vec<Op>, worker: TokenId, id: TokenId) {
+        let ty = self.data_type(id);
+        let length = self.token(TokenType::Constant(DataType::U32));
+        ops.push(Op::ArrayLen(length, id));
+        let index = self.index(ops, worker, 0, self.lengths[&id], None);
+        ops.push(Op::ArrayStore(self.spills[0], index, length));
+        self.each(ops, length, |s, body, element| {
+            let value = s.token(TokenType::Constant(ty));
+            body.push(Op::ArrayLoad(value, id, element));
+            s.save(body, worker, id, value, Some(element));
+        });
+    }
+
+    fn restore_array(&mut self, ops: &mut Vec<Op>, worker: TokenId, id: TokenId) {
+        let (ty, max_size) = match self.program.storage[&id] {
+            StorageType::PrivateArray(ty, max_size) => (ty, max_size),
+            _ => unreachable!(),
+        };
+        let index = self.index(ops, worker, 0, self.lengths[&id], None);
+        let length = self.token(TokenType::Constant(DataType::U32));
+        ops.push(Op::ArrayLoad(length, self.spills[0], index));
+        ops.push(Op::ArrayNew(id, length, ty, max_size, false));
+        self.each(ops, length, |s, body, element| {
+            let value = s.token(TokenType::Constant(ty));
+            s.restore(body, worker, id, value, Some(element));
+            body.push(Op::ArrayStore(id, element, value));
+        });
+    }
+
+    fn each<F: FnOnce(&mut Splitter, &mut Vec<Op>, TokenId)>(
+        &mut self,
+        ops: &mut Vec<Op>,
+        length: TokenId,
+        f: F,
+    ) {
+        let counter = self.variable(DataType::U32);
+        let zero = self.constant(ops, 0);
+        ops.push(Op::Store(counter, zero));
+        let current = self.token(TokenType::Constant(DataType::U32));
+        let test = self.token(TokenType::Constant(DataType::Bool));
+        let cond = vec![Op::Load(current, counter), Op::Lt(test, current, length)];
+        let element = self.token(TokenType::Constant(DataType::U32));
+        let mut body = vec![Op::Load(element, counter)];
+        f(self, &mut body, element);
+        let one = self.constant(&mut body, 1);
+        let next = self.token(TokenType::Constant(DataType::U32));
+        body.push(Op::Add(next, element, one));
+        body.push(Op::Store(counter, next));
+        let (lcond, l0, lend) = (self.label(), self.label(), self.label());
+        ops.push(Op::While(lcond, cond, test, l0, body, lend));
+    }
+
+    fn index(
+        &mut self,
+        ops: &mut Vec<Op>,
+        worker: TokenId,
+        lane: usize,
+        offset: u32,
+        element: Option<TokenId>,
+    ) -> TokenId {
+        let stride = self.strides[lane];
+        let stride = self.constant(ops, stride);
+        let base = self.token(TokenType::Constant(DataType::U32));
+        ops.push(Op::Mul(base, worker, stride));
+        let offset = self.constant(ops, offset);
+        let mut index = self.token(TokenType::Constant(DataType::U32));
+        ops.push(Op::Add(index, base, offset));
+        if let Some(element) = element {
+            let result = self.token(TokenType::Constant(DataType::U32));
+            ops.push(Op::Add(result, index, element));
+            index = result;
+        }
+        index
+    }
+
+    fn allocate(&mut self, ty: DataType, size: u32) -> (usize, u32) {
+        let lane = match ty {
+            DataType::Bool | DataType::U32 => 0,
+            DataType::I32 => 1,
+            DataType::F32 => 2,
+        };
+        let offset = self.strides[lane];
+        self.strides[lane] += size;
+        (lane, offset)
+    }
+
+    fn data_type(&self, id: TokenId) -> DataType {
+        match self.program.storage[&id] {
+            StorageType::Variable(ty)
+            | StorageType::SharedArray(ty, _)
+            | StorageType::PrivateArray(ty, _) => ty,
+        }
+    }
+
+    fn constant(&mut self, ops: &mut Vec<Op>, value: u32) -> TokenId {
+        let id = self.token(TokenType::Constant(DataType::U32));
+        ops.push(Op::Constant(id, ConstantScalar::U32(value)));
+        id
+    }
+
+    fn variable(&mut self, ty: DataType) -> TokenId {
+        let id = self.token(TokenType::Variable(ty));
+        self.program.storage.insert(id, StorageType::Variable(ty));
+        id
+    }
+
+    fn token(&mut self, token_type: TokenType) -> TokenId {
+        let id = TokenId(self.next_token);
+        self.next_token += 1;
+        self.program.symbol.insert(id, token_type);
+        id
+    }
+
+    fn label(&mut self) -> LabelId {
+        let id = LabelId(self.next_label);
+        self.next_label += 1;
+        id
+    }
+
+    fn jump(&mut self, target: u32, scope: &[TokenId]) -> Op {
+        self.jumps.push((target, scope.to_vec()));
+        Op::Constant(
+            self.marker,
+            ConstantScalar::U32(self.jumps.len() as u32 - 1),
+        )
+    }
+
+    fn reserve(&mut self) -> u32 {
+        self.phases.push(Vec::new());
+        self.phases.len() as u32 - 1
+    }
+}
+
+fn condition(block: &[Op], scope: &[TokenId]) -> Result<Vec<TokenId>, Error> {
+    if has_global_barrier(block) {
+        return Err(Error::Validation(
+            "GlobalBarrier cannot be used inside a condition".into(),
+        ));
+    }
+    Ok(scope
+        .iter()
+        .cloned()
+        .chain(block.iter().filter_map(defined))
+        .collect())
+}
+
+fn describe(phase: u32) -> String {
+    if phase == DONE {
+        "the end".to_string()
+    } else {
+        format!("phase {}", phase)
+    }
+}
+
+fn reaches(targets: &[Vec<u32>], phase: usize) -> bool {
+    let mut visited = vec![false; targets.len()];
+    let mut stack = targets[phase].clone();
+    while let Some(target) = stack.pop() {
+        if target == DONE {
+            continue;
+        }
+        let target = target as usize;
+        if target == phase {
+            return true;
+        }
+        if !visited[target] {
+            visited[target] = true;
+            stack.extend(&targets[target]);
+        }
+    }
+    false
+}
+
+fn walk(block: &mut [Op], f: &mut dyn FnMut(&mut Op)) {
+    for op in block {
+        f(op);
+        match *op {
+            Op::If(ref mut c, _, _, ref mut a, _) | Op::While(_, ref mut c, _, _, ref mut a, _) => {
+                walk(c, f);
+                walk(a, f);
+            }
+            Op::IfElse(ref mut c, _, _, ref mut a, _, ref mut b, _) => {
+                walk(c, f);
+                walk(a, f);
+                walk(b, f);
+            }
+            _ => {}
+        }
+    }
+}
+
+fn labels(op: &Op) -> Vec<LabelId> {
+    match *op {
+        Op::Phi(_, _, l0, _, l1) | Op::If(_, _, l0, _, l1) => vec![l0, l1],
+        Op::IfElse(_, _, l0, _, l1, _, l2) | Op::While(l0, _, _, l1, _, l2) => vec![l0, l1, l2],
+        _ => Vec::new(),
+    }
+}
+
+fn defined(op: &Op) -> Option<TokenId> {
+    match *op {
+        Op::Phi(r, ..)
+        | Op::WorkerId(r)
+        | Op::NumWorkers(r)
+        | Op::Load(r, _)
+        | Op::ArrayNew(r, ..)
+        | Op::ArrayLen(r, _)
+        | Op::ArrayLoad(r, ..)
+        | Op::Constant(r, _)
+        | Op::U32fromF32(r, _)
+        | Op::I32fromF32(r, _)
+        | Op::F32fromU32(r, _)
+        | Op::F32fromI32(r, _)
+        | Op::I32fromU32(r, _)
+        | Op::U32fromI32(r, _)
+        | Op::Add(r, ..)
+        | Op::Sub(r, ..)
+        | Op::Mul(r, ..)
+        | Op::Div(r, ..)
+        | Op::Rem(r, ..)
+        | Op::Neg(r, _)
+        | Op::Not(r, _)
+        | Op::Shl(r, ..)
+        | Op::Shr(r, ..)
+        | Op::BitAnd(r, ..)
+        | Op::BitOr(r, ..)
+        | Op::BitXor(r, ..)
+        | Op::Eq(r, ..)
+        | Op::Ne(r, ..)
+        | Op::Lt(r, ..)
+        | Op::Le(r, ..)
+        | Op::Gt(r, ..)
+        | Op::Ge(r, ..) => Some(r),
+        _ => None,
+    }
+}
+
+fn operands(op: &mut Op) -> Vec<&mut TokenId> {
+    match *op {
+        Op::Phi(_, ref mut a, _, ref mut b, _) => vec![a, b],
+        Op::If(_, ref mut c, ..)
+        | Op::IfElse(_, ref mut c, ..)
+        | Op::While(_, _, ref mut c, ..) => {
+            vec![c]
+        }
+        Op::Load(_, ref mut a)
+        | Op::ArrayNew(_, ref mut a, ..)
+        | Op::ArrayLen(_, ref mut a)
+        | Op::U32fromF32(_, ref mut a)
+        | Op::I32fromF32(_, ref mut a)
+        | Op::F32fromU32(_, ref mut a)
+        | Op::F32fromI32(_, ref mut a)
+        | Op::I32fromU32(_, ref mut a)
+        | Op::U32fromI32(_, ref mut a)
+        | Op::Neg(_, ref mut a)
+        | Op::Not(_, ref mut a) => vec![a],
+        Op::Store(ref mut a, ref mut b)
+        | Op::ArrayLoad(_, ref mut a, ref mut b)
+        | Op::Add(_, ref mut a, ref mut b)
+        | Op::Sub(_, ref mut a, ref mut b)
+        | Op::Mul(_, ref mut a, ref mut b)
+        | Op::Div(_, ref mut a, ref mut b)
+        | Op::Rem(_, ref mut a, ref mut b)
+        | Op::Shl(_, ref mut a, ref mut b)
+        | Op::Shr(_, ref mut a, ref mut b)
+        | Op::BitAnd(_, ref mut a, ref mut b)
+        | Op::BitOr(_, ref mut a, ref mut b)
+        | Op::BitXor(_, ref mut a, ref mut b)
+        | Op::Eq(_, ref mut a, ref mut b)
+        | Op::Ne(_, ref mut a, ref mut b)
+        | Op::Lt(_, ref mut a, ref mut b)
+        | Op::Le(_, ref mut a, ref mut b)
+        | Op::Gt(_, ref mut a, ref mut b)
+        | Op::Ge(_, ref mut a, ref mut b) => vec![a, b],
+        Op::ArrayStore(ref mut a, ref mut b, ref mut c) => vec![a, b, c],
+        Op::MemoryBarrier
+        | Op::ControlBarrier
+        | Op::GlobalBarrier
+        | Op::WorkerId(_)
+        | Op::NumWorkers(_)
+        | Op::Constant(..) => Vec::new(),
+    }
+}
@@ -0,0 +1,143 @@
+// Copyright 2018 | Dario Ostuni <dario.ostuni@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::fmt::{self, Debug, Formatter};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Condvar, Mutex};
+use std::task::{Context, Poll, Waker};
+use std::thread;
+
+struct State<R, E> {
+    result: Option<Result<R, E>>,
+    finished: bool,
+    waker: Option<Waker>,
+}
+
+struct Shared<R, E> {
+    state: Mutex<State<R, E>>,
+    done: Condvar,
+}
+
+pub struct Submission<R, E> {
+    shared: Arc<Shared<R, E>>,
+}
+
+pub struct Completion<R, E> {
+    shared: Arc<Shared<R, E>>,
+}
+
+impl<R, E> Submission<R, E> {
+    pub fn new() -> (Submission<R, E>, Completion<R, E>) {
+        let shared = Arc::new(Shared {
+            state: Mutex::new(State {
+                result: None,
+                finished: false,
+                waker: None,
+            }),
+            done: Condvar::new(),
+        });
+        let completion = Completion {
+            shared: shared.clone(),
+        };
+        (Submission { shared }, completion)
+    }
+
+    pub fn ready(result: Result<R, E>) -> Submission<R, E> {
+        let (submission, completion) = Submission::new();
+        completion.complete(result);
+        submission
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.shared.state.lock().unwrap().finished
+    }
+
+    pub fn try_wait(&mut self) -> Option<Result<R, E>> {
+        let mut state = self.shared.state.lock().unwrap();
+        if state.finished {
+            Some(take(&mut state))
+        } else {
+            None
+        }
+    }
+
+    pub fn wait(self) -> Result<R, E> {
+        let mut state = self.shared.state.lock().unwrap();
+        while !state.finished {
+            state = self.shared.done.wait(state).unwrap();
+        }
+        take(&mut state)
+    }
+}
+
+impl<R: Send + 'static, E: Send + 'static> Submission<R, E> {
+    pub fn spawn<F: FnOnce() -> Result<R, E> + Send + 'static>(f: F) -> Submission<R, E> {
+        let (submission, completion) = Submission::new();
+        thread::spawn(move || completion.complete(f()));
+        submission
+    }
+}
+
+impl<R, E> Completion<R, E> {
+    pub fn complete(self, result: Result<R, E>) {
+        self.shared.state.lock().unwrap().result = Some(result);
+    }
+}
+
+impl<R, E> Drop for Completion<R, E> {
+    fn drop(&mut self) {
+        let mut state = self.shared.state.lock().unwrap_or_else(|x| x.into_inner());
+        state.finished = true;
+        if let Some(waker) = state.waker.take() {
+            waker.wake();
+        }
+        self.shared.done.notify_all();
+    }
+}
+
+impl<R, E> Future for Submission<R, E> {
+    type Output = Result<R, E>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<R, E>> {
+        let mut state = self.shared.state.lock().unwrap();
+        if state.finished {
+            Poll::Ready(take(&mut state))
+        } else {
+            state.waker = Some(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+}
+
+impl<R, E> Debug for Submission<R, E> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.debug_struct("Submission")
+            .field("done", &self.is_done())
+            .finish()
+    }
+}
+
+impl<R, E> Debug for Completion<R, E> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.debug_struct("Completion").finish()
+    }
+}
+
+fn take<R, E>(state: &mut State<R, E>) -> Result<R, E> {
+    state
+        .result
+        .take()
+        .expect("Submission was abandoned or its result was already taken")
+}
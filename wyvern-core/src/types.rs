@@ -15,16 +15,22 @@
 use builder::{ProgramBuilder, ProgramObjectInfo, WorkerMessage};
 use num_traits::{PrimInt, Unsigned};
 use program::TokenId;
-use program::{ConstantScalar, DataType, Op, TokenType};
+use program::{ConstantScalar, ConstantVector, DataType, Op, TokenType, TokenValue};
+use std::any::TypeId;
 use std::cmp::{PartialEq, PartialOrd};
 use std::marker::PhantomData;
 use std::ops::{Add, BitAnd, BitOr, BitXor, Div, Mul, Neg, Not, Rem, Shl, Shr, Sub};
+use std::slice;
 use std::string::ToString;
 
-pub trait Type: Copy + PartialEq + Default {
+pub trait Type: Copy + PartialEq + Default + 'static {
     type TrueType;
     fn data_type() -> DataType;
     fn symbol_constant(&self) -> ConstantScalar;
+    fn vector(data: Vec<Self>) -> ConstantVector;
+    fn slice(value: &TokenValue) -> Option<&[Self]>;
+    fn slice_mut(value: &mut TokenValue) -> Option<&mut [Self]>;
+    fn vec_mut(value: &mut TokenValue) -> Option<&mut Vec<Self>>;
 }
 
 #[derive(Clone, Copy)]
@@ -63,6 +69,31 @@ macro_rules! impl_type {
             fn symbol_constant(&self) -> ConstantScalar {
                 ConstantScalar::$upper(*self)
             }
+            fn vector(data: Vec<$primitive>) -> ConstantVector {
+                ConstantVector::$upper(data)
+            }
+            fn slice(value: &TokenValue) -> Option<&[$primitive]> {
+                match *value {
+                    TokenValue::Scalar(ConstantScalar::$upper(ref x)) => Some(slice::from_ref(x)),
+                    TokenValue::Vector(ConstantVector::$upper(ref x)) => Some(x),
+                    _ => None,
+                }
+            }
+            fn slice_mut(value: &mut TokenValue) -> Option<&mut [$primitive]> {
+                match *value {
+                    TokenValue::Scalar(ConstantScalar::$upper(ref mut x)) => {
+                        Some(slice::from_mut(x))
+                    }
+                    TokenValue::Vector(ConstantVector::$upper(ref mut x)) => Some(x),
+                    _ => None,
+                }
+            }
+            fn vec_mut(value: &mut TokenValue) -> Option<&mut Vec<$primitive>> {
+                match *value {
+                    TokenValue::Vector(ConstantVector::$upper(ref mut x)) => Some(x),
+                    _ => None,
+                }
+            }
         }
     };
 }
@@ -212,6 +243,140 @@ impl<'a, T: Type> Constant<'a, T> {
     }
 }
 
+impl<'a, T: Type + PrimInt> Constant<'a, T> {
+    pub fn wrapping_add(self, rhs: Self) -> Self {
+        self + rhs
+    }
+
+    pub fn wrapping_sub(self, rhs: Self) -> Self {
+        self - rhs
+    }
+
+    pub fn wrapping_mul(self, rhs: Self) -> Self {
+        self * rhs
+    }
+
+    pub fn wrapping_div(self, rhs: Self) -> Self {
+        self / rhs
+    }
+
+    pub fn wrapping_rem(self, rhs: Self) -> Self {
+        self % rhs
+    }
+
+    fn select(condition: Constant<'a, bool>, a: Self, b: Self) -> Self {
+        let builder = condition.info.builder;
+        let result = Variable::new(builder);
+        result.store(b);
+        builder.if_then(|_| condition, |_| result.store(a));
+        result.load()
+    }
+}
+
+impl<'a> Constant<'a, u32> {
+    pub fn checked_add(self, rhs: Self) -> (Self, Constant<'a, bool>) {
+        let result = self + rhs;
+        (result, result.ge(self))
+    }
+
+    pub fn checked_sub(self, rhs: Self) -> (Self, Constant<'a, bool>) {
+        (self - rhs, self.ge(rhs))
+    }
+
+    pub fn checked_mul(self, rhs: Self) -> (Self, Constant<'a, bool>) {
+        let zero = Constant::new(0, self.info.builder);
+        let result = self * rhs;
+        (result, self.eq(zero) | (result / self).eq(rhs))
+    }
+
+    pub fn checked_div(self, rhs: Self) -> (Self, Constant<'a, bool>) {
+        let zero = Constant::new(0, self.info.builder);
+        (self / rhs, rhs.ne(zero))
+    }
+
+    pub fn checked_rem(self, rhs: Self) -> (Self, Constant<'a, bool>) {
+        let zero = Constant::new(0, self.info.builder);
+        (self % rhs, rhs.ne(zero))
+    }
+
+    pub fn saturating_add(self, rhs: Self) -> Self {
+        let (result, ok) = self.checked_add(rhs);
+        Self::select(ok, result, Constant::new(u32::MAX, self.info.builder))
+    }
+
+    pub fn saturating_sub(self, rhs: Self) -> Self {
+        let (result, ok) = self.checked_sub(rhs);
+        Self::select(ok, result, Constant::new(0, self.info.builder))
+    }
+
+    pub fn saturating_mul(self, rhs: Self) -> Self {
+        let (result, ok) = self.checked_mul(rhs);
+        Self::select(ok, result, Constant::new(u32::MAX, self.info.builder))
+    }
+}
+
+impl<'a> Constant<'a, i32> {
+    pub fn checked_add(self, rhs: Self) -> (Self, Constant<'a, bool>) {
+        let zero = Constant::new(0, self.info.builder);
+        let result = self + rhs;
+        (result, ((self ^ result) & (rhs ^ result)).ge(zero))
+    }
+
+    pub fn checked_sub(self, rhs: Self) -> (Self, Constant<'a, bool>) {
+        let zero = Constant::new(0, self.info.builder);
+        let result = self - rhs;
+        (result, ((self ^ rhs) & (self ^ result)).ge(zero))
+    }
+
+    pub fn checked_mul(self, rhs: Self) -> (Self, Constant<'a, bool>) {
+        let zero = Constant::new(0, self.info.builder);
+        let minus_one = Constant::new(-1, self.info.builder);
+        let min = Constant::new(i32::MIN, self.info.builder);
+        let result = self * rhs;
+        let ok = self.eq(zero) | ((result / self).eq(rhs) & (self.ne(minus_one) | rhs.ne(min)));
+        (result, ok)
+    }
+
+    pub fn checked_div(self, rhs: Self) -> (Self, Constant<'a, bool>) {
+        (self / rhs, self.divisor_ok(rhs))
+    }
+
+    pub fn checked_rem(self, rhs: Self) -> (Self, Constant<'a, bool>) {
+        (self % rhs, self.divisor_ok(rhs))
+    }
+
+    pub fn saturating_add(self, rhs: Self) -> Self {
+        let (result, ok) = self.checked_add(rhs);
+        Self::select(ok, result, self.saturation(self))
+    }
+
+    pub fn saturating_sub(self, rhs: Self) -> Self {
+        let (result, ok) = self.checked_sub(rhs);
+        Self::select(ok, result, self.saturation(self))
+    }
+
+    pub fn saturating_mul(self, rhs: Self) -> Self {
+        let (result, ok) = self.checked_mul(rhs);
+        Self::select(ok, result, self.saturation(self ^ rhs))
+    }
+
+    fn divisor_ok(self, rhs: Self) -> Constant<'a, bool> {
+        let zero = Constant::new(0, self.info.builder);
+        let minus_one = Constant::new(-1, self.info.builder);
+        let min = Constant::new(i32::MIN, self.info.builder);
+        rhs.ne(zero) & (self.ne(min) | rhs.ne(minus_one))
+    }
+
+    fn saturation(self, sign: Self) -> Self {
+        let zero = Constant::new(0, self.info.builder);
+        Self::select(
+            sign.lt(zero),
+            Constant::new(i32::MIN, self.info.builder),
+            Constant::new(i32::MAX, self.info.builder),
+        )
+    }
+}
+
 impl<'a, T: Type> Variable<'a, T> {
     pub fn new(builder: &'a ProgramBuilder) -> Variable<'a, T> {
         Variable {
@@ -280,9 +445,21 @@ impl<'a, T: Type> Variable<'a, T> {
         ));
         *self
     }
+
+    pub fn mark_as_inout<S: ToString>(&self, name: S) -> Self {
+        if self.ty != VariableType::Variable {
+            panic!("Can't mark array index as inout");
+        }
+        let name = name;
+        self.info.builder.send_message(WorkerMessage::MarkInOut(
+            self.info.token.id,
+            name.to_string(),
+        ));
+        *self
+    }
 }
 
-#[cfg_attr(feature = "cargo-clippy", allow(len_without_is_empty))]
+#[allow(clippy::len_without_is_empty)]
 impl<'a, T: Type> Array<'a, T> {
     pub fn new<U: Type + Unsigned>(
         size: Constant<'a, U>,
@@ -326,6 +503,16 @@ impl<'a, T: Type> Array<'a, T> {
         *self
     }
 
+    pub fn mark_as_inout<S: ToString>(&self, name: S) -> Self {
+        assert!(self.shared);
+        let name = name;
+        self.info.builder.send_message(WorkerMessage::MarkInOut(
+            self.info.token.id,
+            name.to_string(),
+        ));
+        *self
+    }
+
     pub fn len(&'a self) -> Constant<'a, u32> {
         let result = Constant::generate(self.info.builder);
         result
@@ -348,6 +535,22 @@ impl<'a, T: Type> Array<'a, T> {
     }
 }
 
+pub fn cast_slice<T: Type, U: Type>(data: &[U]) -> Option<&[T]> {
+    if TypeId::of::<T>() == TypeId::of::<U>() {
+        Some(unsafe { slice::from_raw_parts(data.as_ptr() as *const T, data.len()) })
+    } else {
+        None
+    }
+}
+
+pub fn cast_slice_mut<T: Type, U: Type>(data: &mut [U]) -> Option<&mut [T]> {
+    if TypeId::of::<T>() == TypeId::of::<U>() {
+        Some(unsafe { slice::from_raw_parts_mut(data.as_mut_ptr() as *mut T, data.len()) })
+    } else {
+        None
+    }
+}
+
 impl_type!(i32, I32);
 impl_type!(u32, U32);
 impl_type!(f32, F32);
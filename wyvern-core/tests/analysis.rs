@@ -0,0 +1,73 @@
+// Copyright 2018 | Dario Ostuni <dario.ostuni@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+extern crate wyvern_core;
+
+use wyvern_core::analysis::{uninitialized_loads, UninitializedLoad};
+use wyvern_core::builder::ProgramBuilder;
+use wyvern_core::program::{Op, Program};
+use wyvern_core::types::{Array, Constant, Variable};
+
+fn loads(program: &Program) -> Vec<(&'static str, UninitializedLoad)> {
+    let mut names = Vec::new();
+    let mut next = 0;
+    while let Some(op) = program.op(next) {
+        names.push(op.name());
+        next += 1;
+    }
+    uninitialized_loads(program)
+        .into_iter()
+        .map(|x| (names[x.op as usize], x))
+        .collect()
+}
+
+#[test]
+fn loads_before_any_store_are_flagged() {
+    let b = ProgramBuilder::new();
+    let zero = Constant::new(0_u32, &b);
+    let out = Variable::<u32>::new(&b).mark_as_output("out");
+    let v = Variable::<u32>::new(&b);
+    let a = Array::<u32>::new(Constant::new(4_u32, &b), 4, false, &b);
+    out.store(v.load() + a.at(zero).load());
+    v.store(zero);
+    out.store(v.load());
+    let program = b.finalize().unwrap();
+    let found = loads(&program);
+    assert_eq!(found.len(), 2);
+    assert_eq!(found[0].0, "Load");
+    assert_eq!(found[1].0, "ArrayLoad");
+    match program.op(found[0].1.op as usize) {
+        Some(&Op::Load(_, token)) => assert_eq!(token, found[0].1.token),
+        x => panic!("Expected a load, got {:?}", x),
+    }
+}
+
+#[test]
+fn parameters_and_conditional_stores_are_not_flagged() {
+    let b = ProgramBuilder::new();
+    let zero = Constant::new(0_u32, &b);
+    let input = Variable::<u32>::new(&b).mark_as_input("in");
+    let out = Variable::<u32>::new(&b).mark_as_output("out");
+    let v = Variable::<u32>::new(&b);
+    let w = Variable::<u32>::new(&b);
+    b.if_then(|_| input.load().eq(zero), |_| v.store(input.load()));
+    b.while_loop(
+        |_| out.load().lt(Constant::new(3_u32, &b)),
+        |_| {
+            out.store(w.load());
+            w.store(out.load() + Constant::new(1_u32, &b));
+        },
+    );
+    out.store(v.load() + w.load());
+    assert_eq!(loads(&b.finalize().unwrap()), vec![]);
+}
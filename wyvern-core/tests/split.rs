@@ -0,0 +1,172 @@
+// Copyright 2018 | Dario Ostuni <dario.ostuni@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+extern crate wyvern_core;
+
+use wyvern_core::builder::ProgramBuilder;
+use wyvern_core::error::Error;
+use wyvern_core::program::{DataType, LabelId, Op, Program, TokenId};
+use wyvern_core::split::{has_global_barrier, is_hidden, split, NEXT, PHASE};
+use wyvern_core::types::{Array, Constant, Variable};
+
+fn rejected(program: Program) -> String {
+    match split(program) {
+        Err(Error::Validation(message)) => message,
+        x => panic!("Expected a validation error, got {:?}", x),
+    }
+}
+
+fn while_program() -> Program {
+    let b = ProgramBuilder::new();
+    let zero = Constant::new(0_u32, &b);
+    let one = Constant::new(1_u32, &b);
+    let a = Array::<u32>::new(zero, 0, true, &b).mark_as_inout("a");
+    let id = b.worker_id();
+    let n = b.num_workers();
+    let round = Variable::<u32>::new(&b);
+    round.store(zero);
+    b.while_loop(
+        |_| round.load().lt(Constant::new(3_u32, &b)),
+        |_| {
+            let v = a.at((id + one) % n).load();
+            b.global_barrier();
+            a.at(id).store(v + one);
+            b.global_barrier();
+            round.store(round.load() + one);
+        },
+    );
+    b.finalize().unwrap()
+}
+
+#[test]
+fn programs_without_global_barriers_are_unchanged() {
+    let b = ProgramBuilder::new();
+    let out = Variable::<u32>::new(&b).mark_as_output("out");
+    out.store(b.worker_id());
+    b.barrier();
+    let program = b.finalize().unwrap();
+    let (split, phases) = split(program.clone()).unwrap();
+    assert_eq!(split.operation, program.operation);
+    assert_eq!(split.input, program.input);
+    assert_eq!(phases, None);
+}
+
+#[test]
+fn barrier_inside_while_is_split_into_phases() {
+    let (program, phases) = split(while_program()).unwrap();
+    let phases = phases.unwrap();
+    assert!(phases.count >= 3);
+    assert!(program.input.contains_key(PHASE));
+    assert!(program.output.contains_key(NEXT));
+    assert!(program.parameters().iter().all(|x| !is_hidden(&x.name)));
+    assert_eq!(
+        program
+            .parameters()
+            .iter()
+            .map(|x| x.name.as_str())
+            .collect::<Vec<_>>(),
+        vec!["a"]
+    );
+    assert!(!has_global_barrier(&program.operation));
+}
+
+#[test]
+fn private_array_and_bool_are_spilled_per_worker() {
+    let b = ProgramBuilder::new();
+    let zero = Constant::new(0_u32, &b);
+    let one = Constant::new(1_u32, &b);
+    let out = Array::<u32>::new(zero, 0, true, &b).mark_as_output("out");
+    let private = Array::<u32>::new(Constant::new(4_u32, &b), 4, false, &b);
+    let id = b.worker_id();
+    let even = (id % Constant::new(2_u32, &b)).eq(zero);
+    private.at(zero).store(id);
+    private.at(one).store(id + one);
+    b.global_barrier();
+    let extra = Variable::<u32>::new(&b);
+    extra.store(zero);
+    b.if_then(|_| even, |_| extra.store(one));
+    out.at(id)
+        .store(private.at(zero).load() + private.at(one).load() + extra.load());
+    let (_, phases) = split(b.finalize().unwrap()).unwrap();
+    let phases = phases.unwrap();
+    assert_eq!(phases.count, 2);
+    let spill = phases
+        .buffers
+        .iter()
+        .find(|x| x.data_type == DataType::U32 && x.per_worker)
+        .unwrap();
+    assert!(is_hidden(&spill.name));
+    assert!(spill.size >= 6);
+    let initial = phases.initial(8);
+    assert!(initial.iter().all(|x| is_hidden(&x.0)));
+    assert_eq!(initial.len(), 2 + phases.buffers.len());
+}
+
+#[test]
+fn barrier_inside_condition_is_rejected() {
+    let b = ProgramBuilder::new();
+    let zero = Constant::new(0_u32, &b);
+    let out = Variable::<u32>::new(&b).mark_as_output("out");
+    b.if_then(
+        |_| {
+            b.global_barrier();
+            zero.eq(zero)
+        },
+        |_| out.store(zero),
+    );
+    let message = rejected(b.finalize().unwrap());
+    assert!(message.contains("condition"), "{}", message);
+}
+
+#[test]
+fn barrier_inside_while_condition_is_rejected() {
+    let b = ProgramBuilder::new();
+    let zero = Constant::new(0_u32, &b);
+    let round = Variable::<u32>::new(&b);
+    round.store(zero);
+    b.while_loop(
+        |_| {
+            b.global_barrier();
+            round.load().eq(zero)
+        },
+        |_| round.store(Constant::new(1_u32, &b)),
+    );
+    let message = rejected(b.finalize().unwrap());
+    assert!(message.contains("condition"), "{}", message);
+}
+
+#[test]
+fn phi_is_rejected() {
+    let mut program = while_program();
+    let token = TokenId(program.symbol.keys().map(|x| x.0).max().unwrap());
+    program
+        .operation
+        .push(Op::Phi(token, token, LabelId(0), token, LabelId(1)));
+    let message = rejected(program);
+    assert!(message.contains("Phi"), "{}", message);
+}
+
+#[test]
+fn non_constant_shared_array_is_rejected() {
+    let b = ProgramBuilder::new();
+    let zero = Constant::new(0_u32, &b);
+    let out = Array::<u32>::new(zero, 0, true, &b).mark_as_output("out");
+    let shared = Array::<u32>::new(b.num_workers(), 64, true, &b);
+    let id = b.worker_id();
+    shared.at(id).store(id);
+    b.global_barrier();
+    out.at(id).store(shared.at(zero).load());
+    let message = rejected(b.finalize().unwrap());
+    assert!(message.contains("constant size"), "{}", message);
+}
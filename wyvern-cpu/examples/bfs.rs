@@ -4,7 +4,7 @@ extern crate wyvern_cpu as wcpu;
 use std::io::stdin;
 use wcore::builder::ProgramBuilder;
 use wcore::executor::{Executable, Executor, Resource, IO};
-use wcore::program::{ConstantScalar, ConstantVector, TokenValue};
+use wcore::program::{ConstantScalar, TokenValue};
 use wcore::types::{Array, Constant, Variable};
 use wcpu::executor::CpuExecutor;
 
@@ -50,26 +50,20 @@ fn main() {
     let edges_resource = executor.new_resource().unwrap();
     let dist_resource = executor.new_resource().unwrap();
     let starting_node_resource = executor.new_resource().unwrap();
-    nodes_resource.set_data(TokenValue::Vector(ConstantVector::U32(nodes)));
-    edges_resource.set_data(TokenValue::Vector(ConstantVector::U32(edges)));
-    dist_resource.set_data(TokenValue::Vector(ConstantVector::U32(dist)));
-    executable.bind("nodes", IO::Input, nodes_resource.clone());
-    executable.bind("edges", IO::Input, edges_resource.clone());
-    executable.bind("starting_node", IO::Input, starting_node_resource.clone());
-    executable.bind("distance", IO::Output, dist_resource.clone());
+    nodes_resource.write_slice(&nodes).unwrap();
+    edges_resource.write_slice(&edges).unwrap();
+    dist_resource.write_slice(&dist).unwrap();
+    executable.bind("nodes", IO::Input, nodes_resource.clone()).unwrap();
+    executable.bind("edges", IO::Input, edges_resource.clone()).unwrap();
+    executable.bind("starting_node", IO::Input, starting_node_resource.clone()).unwrap();
+    executable.bind("distance", IO::Output, dist_resource.clone()).unwrap();
     let mut max_distance = 0;
     for i in 0..n {
-        starting_node_resource.set_data(TokenValue::Scalar(ConstantScalar::U32(i as u32)));
+        starting_node_resource.set_data(TokenValue::Scalar(ConstantScalar::U32(i as u32))).unwrap();
         executable.run().unwrap();
-        let value = match dist_resource.get_data() {
-            TokenValue::Vector(ConstantVector::U32(x)) => x,
-            _ => unreachable!(),
-        };
-        for v in value {
-            if v > max_distance {
-                max_distance = v;
-            }
-        }
+        max_distance = dist_resource
+            .map(|x: &[u32]| x.iter().cloned().fold(max_distance, u32::max))
+            .unwrap();
         eprintln!("Processed {}/{}", i + 1, n);
     }
     println!("Diameter: {}", max_distance);
@@ -96,13 +90,14 @@ fn bfs(builder: &ProgramBuilder, v: u32, e: u32) {
             node.store(nid + size);
         },
     );
-    builder.memory_barrier();
+    builder.global_barrier();
     let iteration = Variable::new(builder);
     iteration.store(zero);
     stop.at(zero).store(zero);
     builder.while_loop(
         |_| stop.at(zero).load().ne(one),
         |_| {
+            builder.global_barrier();
             stop.at(zero).store(one);
             node.store(id);
             builder.while_loop(
@@ -135,7 +130,7 @@ fn bfs(builder: &ProgramBuilder, v: u32, e: u32) {
                     node.store(nid + size);
                 },
             );
-            builder.memory_barrier();
+            builder.global_barrier();
             node.store(id);
             builder.while_loop(
                 |_| node.load().lt(num_nodes),
@@ -150,7 +145,7 @@ fn bfs(builder: &ProgramBuilder, v: u32, e: u32) {
                     node.store(nid + size);
                 },
             );
-            builder.memory_barrier();
+            builder.global_barrier();
             iteration.store(iteration.load() + 1);
         },
     );
@@ -14,7 +14,7 @@ fn prog() -> Program {
     r = r | a;
     let r = Constant::from(r);
     let r = r / 2.0;
-    let _b = Variable::new(&builder).mark_as_output("out").store(r);
+    Variable::new(&builder).mark_as_output("out").store(r);
     builder.finalize().unwrap()
 }
 
@@ -24,9 +24,9 @@ fn main() {
     let mut executable = executor.compile(program).unwrap();
     let input = executor.new_resource().unwrap();
     let output = executor.new_resource().unwrap();
-    input.set_data(TokenValue::Scalar(ConstantScalar::U32(1)));
-    executable.bind("in", IO::Input, input.clone());
-    executable.bind("out", IO::Output, output.clone());
+    input.set_data(TokenValue::Scalar(ConstantScalar::U32(1))).unwrap();
+    executable.bind("in", IO::Input, input.clone()).unwrap();
+    executable.bind("out", IO::Output, output.clone()).unwrap();
     executable.run().unwrap();
     let value = output.get_data();
     let value = match value {
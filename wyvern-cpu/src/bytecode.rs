@@ -0,0 +1,561 @@
+// Copyright 2018 | Dario Ostuni <dario.ostuni@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use memory::to_bits;
+use std::collections::HashMap;
+use wcore::executor::IO;
+use wcore::program::{DataType, LabelId, Op, Program, StorageType, TokenId, TokenType};
+use wcore::semantics;
+
+pub(crate) type Reg = u32;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Instr {
+    Const(Reg, u32),
+    Copy(Reg, Reg),
+    LoadShared(Reg, u32),
+    StoreShared(u32, Reg),
+    WorkerId(Reg),
+    NumWorkers(Reg),
+    Unary(UnaryOp, Reg, Reg),
+    Binary(BinaryOp, Reg, Reg, Reg),
+    SharedArrayNew(u32, Reg),
+    SharedArrayLen(Reg, u32),
+    SharedArrayLoad(Reg, u32, Reg),
+    SharedArrayStore(u32, Reg, Reg),
+    PrivateArrayNew(u32, Reg),
+    PrivateArrayLen(Reg, u32),
+    PrivateArrayLoad(Reg, u32, Reg),
+    PrivateArrayStore(u32, Reg, Reg),
+    Jump(u32),
+    JumpUnless(Reg, u32),
+    Label(LabelId),
+    Phi(Reg, Reg, LabelId, Reg, LabelId),
+    MemoryBarrier,
+    ControlBarrier,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum UnaryOp {
+    Neg,
+    FNeg,
+    Not,
+    LogicalNot,
+    U32fromF32,
+    I32fromF32,
+    F32fromU32,
+    F32fromI32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum BinaryOp {
+    IAdd,
+    ISub,
+    IMul,
+    SDiv,
+    UDiv,
+    SRem,
+    URem,
+    FAdd,
+    FSub,
+    FMul,
+    FDiv,
+    FRem,
+    Shl,
+    SShr,
+    UShr,
+    And,
+    Or,
+    Xor,
+    IEq,
+    INe,
+    FEq,
+    FNe,
+    SLt,
+    SLe,
+    SGt,
+    SGe,
+    ULt,
+    ULe,
+    UGt,
+    UGe,
+    FLt,
+    FLe,
+    FGt,
+    FGe,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum SharedSlot {
+    Scalar(u32),
+    Array(u32),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ArraySlot {
+    Shared(u32),
+    Private(u32),
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub(crate) struct Bytecode {
+    pub(crate) code: Vec<Instr>,
+    pub(crate) origin: Vec<u32>,
+    pub(crate) labels: HashMap<LabelId, usize>,
+    pub(crate) registers: Vec<TokenId>,
+    pub(crate) scalars: HashMap<TokenId, u32>,
+    pub(crate) arrays: HashMap<TokenId, ArraySlot>,
+    pub(crate) private_arrays: Vec<u32>,
+    pub(crate) shared_scalars: Vec<DataType>,
+    pub(crate) shared_arrays: Vec<(DataType, u32)>,
+    pub(crate) io: Vec<(IO, String, SharedSlot)>,
+    pub(crate) has_barrier: bool,
+}
+
+struct Lowering<'a> {
+    program: &'a Program,
+    bytecode: Bytecode,
+    registers: HashMap<TokenId, Reg>,
+    scalars: HashMap<TokenId, u32>,
+    arrays: HashMap<TokenId, ArraySlot>,
+    io_arrays: Vec<TokenId>,
+    labels: bool,
+    op: u32,
+    next_op: u32,
+}
+
+impl Bytecode {
+    pub(crate) fn new(program: &Program) -> Result<Bytecode, String> {
+        let mut lowering = Lowering {
+            program,
+            bytecode: Bytecode::default(),
+            registers: HashMap::new(),
+            scalars: HashMap::new(),
+            arrays: HashMap::new(),
+            io_arrays: Vec::new(),
+            labels: has_phi(&program.operation),
+            op: 0,
+            next_op: 0,
+        };
+        lowering.allocate_storage()?;
+        lowering.lower_block(&program.operation)?;
+        let mut registers: Vec<_> = lowering.registers.into_iter().collect();
+        registers.sort_by_key(|&(_, reg)| reg);
+        lowering.bytecode.registers = registers.into_iter().map(|(id, _)| id).collect();
+        lowering.bytecode.scalars = lowering.scalars;
+        lowering.bytecode.arrays = lowering.arrays;
+        Ok(lowering.bytecode)
+    }
+}
+
+impl<'a> Lowering<'a> {
+    fn allocate_storage(&mut self) -> Result<(), String> {
+        for (kind, name, id) in self.program.io() {
+            let slot = match self.program.storage.get(&id) {
+                Some(&StorageType::Variable(ty)) => {
+                    let shared_scalars = &mut self.bytecode.shared_scalars;
+                    let slot = *self.scalars.entry(id).or_insert_with(|| {
+                        shared_scalars.push(ty);
+                        shared_scalars.len() as u32 - 1
+                    });
+                    SharedSlot::Scalar(slot)
+                }
+                Some(&StorageType::SharedArray(ty, max_size)) => {
+                    let shared_arrays = &mut self.bytecode.shared_arrays;
+                    let slot = self.arrays.entry(id).or_insert_with(|| {
+                        shared_arrays.push((ty, max_size));
+                        ArraySlot::Shared(shared_arrays.len() as u32 - 1)
+                    });
+                    self.io_arrays.push(id);
+                    match *slot {
+                        ArraySlot::Shared(slot) => SharedSlot::Array(slot),
+                        ArraySlot::Private(_) => unreachable!(),
+                    }
+                }
+                storage => {
+                    return Err(format!(
+                        "Invalid storage {:?} for IO {} ({:?})",
+                        storage, name, id
+                    ))
+                }
+            };
+            self.bytecode.io.push((kind, name.clone(), slot));
+        }
+        let mut storage: Vec<_> = self.program.storage.iter().collect();
+        storage.sort_by_key(|&(id, _)| id.0);
+        for (id, storage) in storage {
+            if self.arrays.contains_key(id) {
+                continue;
+            }
+            match *storage {
+                StorageType::SharedArray(ty, max_size) => {
+                    self.bytecode.shared_arrays.push((ty, max_size));
+                    let slot = self.bytecode.shared_arrays.len() as u32 - 1;
+                    self.arrays.insert(*id, ArraySlot::Shared(slot));
+                }
+                StorageType::PrivateArray(_, max_size) => {
+                    self.bytecode.private_arrays.push(max_size);
+                    let slot = self.bytecode.private_arrays.len() as u32 - 1;
+                    self.arrays.insert(*id, ArraySlot::Private(slot));
+                }
+                StorageType::Variable(_) => continue,
+            }
+        }
+        Ok(())
+    }
+
+    fn lower_block(&mut self, block: &[Op]) -> Result<(), String> {
+        for op in block {
+            self.lower(op)?;
+        }
+        Ok(())
+    }
+
+    fn lower(&mut self, op: &Op) -> Result<(), String> {
+        let parent = self.op;
+        self.op = self.next_op;
+        self.next_op += 1;
+        self.lower_op(op)?;
+        self.op = parent;
+        Ok(())
+    }
+
+    fn lower_op(&mut self, op: &Op) -> Result<(), String> {
+        let instr = match *op {
+            Op::Phi(r, a0, l0, a1, l1) => {
+                Instr::Phi(self.reg(r)?, self.reg(a0)?, l0, self.reg(a1)?, l1)
+            }
+            Op::If(ref cond_op, cond, l0, ref a0, lend) => {
+                self.lower_block(cond_op)?;
+                let cond = self.reg(cond)?;
+                let jump = self.emit(Instr::JumpUnless(cond, 0));
+                self.label(l0);
+                self.lower_block(a0)?;
+                self.patch(jump);
+                self.label(lend);
+                return Ok(());
+            }
+            Op::IfElse(ref cond_op, cond, l0, ref a0, l1, ref a1, lend) => {
+                self.lower_block(cond_op)?;
+                let cond = self.reg(cond)?;
+                let jump_else = self.emit(Instr::JumpUnless(cond, 0));
+                self.label(l0);
+                self.lower_block(a0)?;
+                let jump_end = self.emit(Instr::Jump(0));
+                self.patch(jump_else);
+                self.label(l1);
+                self.lower_block(a1)?;
+                self.patch(jump_end);
+                self.label(lend);
+                return Ok(());
+            }
+            Op::While(lcond, ref cond_op, cond, l0, ref a0, lend) => {
+                let start = self.bytecode.code.len() as u32;
+                self.label(lcond);
+                self.lower_block(cond_op)?;
+                let cond = self.reg(cond)?;
+                let jump_end = self.emit(Instr::JumpUnless(cond, 0));
+                self.label(l0);
+                self.lower_block(a0)?;
+                self.emit(Instr::Jump(start));
+                self.patch(jump_end);
+                self.label(lend);
+                return Ok(());
+            }
+            Op::MemoryBarrier => Instr::MemoryBarrier,
+            Op::ControlBarrier => {
+                self.bytecode.has_barrier = true;
+                Instr::ControlBarrier
+            }
+            Op::GlobalBarrier => return Err("GlobalBarrier must be split before lowering".into()),
+            Op::WorkerId(r) => Instr::WorkerId(self.reg(r)?),
+            Op::NumWorkers(r) => Instr::NumWorkers(self.reg(r)?),
+            Op::Load(r, a) => match self.scalars.get(&a) {
+                Some(&slot) => Instr::LoadShared(self.reg(r)?, slot),
+                None => Instr::Copy(self.reg(r)?, self.reg(a)?),
+            },
+            Op::Store(r, a) => match self.scalars.get(&r) {
+                Some(&slot) => Instr::StoreShared(slot, self.reg(a)?),
+                None => Instr::Copy(self.reg(r)?, self.reg(a)?),
+            },
+            Op::ArrayNew(r, s, _, _, _) => match self.array(r)? {
+                ArraySlot::Shared(_) if self.io_arrays.contains(&r) => return Ok(()),
+                ArraySlot::Shared(slot) => Instr::SharedArrayNew(slot, self.reg(s)?),
+                ArraySlot::Private(slot) => Instr::PrivateArrayNew(slot, self.reg(s)?),
+            },
+            Op::ArrayLen(r, v) => match self.array(v)? {
+                ArraySlot::Shared(slot) => Instr::SharedArrayLen(self.reg(r)?, slot),
+                ArraySlot::Private(slot) => Instr::PrivateArrayLen(self.reg(r)?, slot),
+            },
+            Op::ArrayLoad(r, v, i) => match self.array(v)? {
+                ArraySlot::Shared(slot) => Instr::SharedArrayLoad(self.reg(r)?, slot, self.reg(i)?),
+                ArraySlot::Private(slot) => {
+                    Instr::PrivateArrayLoad(self.reg(r)?, slot, self.reg(i)?)
+                }
+            },
+            Op::ArrayStore(v, i, a) => match self.array(v)? {
+                ArraySlot::Shared(slot) => {
+                    Instr::SharedArrayStore(slot, self.reg(i)?, self.reg(a)?)
+                }
+                ArraySlot::Private(slot) => {
+                    Instr::PrivateArrayStore(slot, self.reg(i)?, self.reg(a)?)
+                }
+            },
+            Op::Constant(r, a) => Instr::Const(self.reg(r)?, to_bits(a)),
+            Op::U32fromF32(r, a) => Instr::Unary(UnaryOp::U32fromF32, self.reg(r)?, self.reg(a)?),
+            Op::I32fromF32(r, a) => Instr::Unary(UnaryOp::I32fromF32, self.reg(r)?, self.reg(a)?),
+            Op::F32fromU32(r, a) => Instr::Unary(UnaryOp::F32fromU32, self.reg(r)?, self.reg(a)?),
+            Op::F32fromI32(r, a) => Instr::Unary(UnaryOp::F32fromI32, self.reg(r)?, self.reg(a)?),
+            Op::I32fromU32(r, a) | Op::U32fromI32(r, a) => Instr::Copy(self.reg(r)?, self.reg(a)?),
+            Op::Neg(r, a) | Op::Not(r, a) => {
+                let unary = unary_op(op, self.data_type(a)?)
+                    .ok_or_else(|| format!("Invalid operand type for {:?}", op))?;
+                Instr::Unary(unary, self.reg(r)?, self.reg(a)?)
+            }
+            Op::Add(r, a, b)
+            | Op::Sub(r, a, b)
+            | Op::Mul(r, a, b)
+            | Op::Div(r, a, b)
+            | Op::Rem(r, a, b)
+            | Op::Shl(r, a, b)
+            | Op::Shr(r, a, b)
+            | Op::BitAnd(r, a, b)
+            | Op::BitOr(r, a, b)
+            | Op::BitXor(r, a, b)
+            | Op::Eq(r, a, b)
+            | Op::Ne(r, a, b)
+            | Op::Lt(r, a, b)
+            | Op::Le(r, a, b)
+            | Op::Gt(r, a, b)
+            | Op::Ge(r, a, b) => {
+                let binary = binary_op(op, self.data_type(a)?)
+                    .ok_or_else(|| format!("Invalid operand type for {:?}", op))?;
+                Instr::Binary(binary, self.reg(r)?, self.reg(a)?, self.reg(b)?)
+            }
+        };
+        self.emit(instr);
+        Ok(())
+    }
+
+    fn emit(&mut self, instr: Instr) -> usize {
+        self.bytecode.code.push(instr);
+        self.bytecode.origin.push(self.op);
+        self.bytecode.code.len() - 1
+    }
+
+    fn patch(&mut self, index: usize) {
+        let target = self.bytecode.code.len() as u32;
+        match self.bytecode.code[index] {
+            Instr::Jump(ref mut x) | Instr::JumpUnless(_, ref mut x) => *x = target,
+            _ => unreachable!(),
+        }
+    }
+
+    fn label(&mut self, label: LabelId) {
+        let position = self.bytecode.code.len();
+        self.bytecode.labels.insert(label, position);
+        if self.labels {
+            self.emit(Instr::Label(label));
+        }
+    }
+
+    fn reg(&mut self, id: TokenId) -> Result<Reg, String> {
+        if self.scalars.contains_key(&id) || self.arrays.contains_key(&id) {
+            return Err(format!("{:?} is not a private value", id));
+        }
+        let next = self.registers.len() as Reg;
+        Ok(*self.registers.entry(id).or_insert(next))
+    }
+
+    fn array(&self, id: TokenId) -> Result<ArraySlot, String> {
+        self.arrays
+            .get(&id)
+            .cloned()
+            .ok_or_else(|| format!("{:?} is not an array", id))
+    }
+
+    fn data_type(&self, id: TokenId) -> Result<DataType, String> {
+        match self.program.symbol.get(&id) {
+            Some(&TokenType::Constant(ty))
+            | Some(&TokenType::Variable(ty))
+            | Some(&TokenType::ArrayPointer(ty))
+            | Some(&TokenType::Array(ty)) => Ok(ty),
+            _ => Err(format!("{:?} has no data type", id)),
+        }
+    }
+}
+
+fn has_phi(block: &[Op]) -> bool {
+    block.iter().any(|op| match *op {
+        Op::Phi(..) => true,
+        Op::If(ref c, _, _, ref a, _) => has_phi(c) || has_phi(a),
+        Op::IfElse(ref c, _, _, ref a, _, ref b, _) => has_phi(c) || has_phi(a) || has_phi(b),
+        Op::While(_, ref c, _, _, ref a, _) => has_phi(c) || has_phi(a),
+        _ => false,
+    })
+}
+
+fn unary_op(op: &Op, ty: DataType) -> Option<UnaryOp> {
+    Some(match (op, ty) {
+        (&Op::Neg(..), DataType::I32) => UnaryOp::Neg,
+        (&Op::Neg(..), DataType::F32) => UnaryOp::FNeg,
+        (&Op::Not(..), DataType::I32) | (&Op::Not(..), DataType::U32) => UnaryOp::Not,
+        (&Op::Not(..), DataType::Bool) => UnaryOp::LogicalNot,
+        _ => return None,
+    })
+}
+
+fn binary_op(op: &Op, ty: DataType) -> Option<BinaryOp> {
+    use self::BinaryOp::*;
+    use wcore::program::DataType::*;
+    Some(match (op, ty) {
+        (&Op::Add(..), I32) | (&Op::Add(..), U32) => IAdd,
+        (&Op::Add(..), F32) => FAdd,
+        (&Op::Sub(..), I32) | (&Op::Sub(..), U32) => ISub,
+        (&Op::Sub(..), F32) => FSub,
+        (&Op::Mul(..), I32) | (&Op::Mul(..), U32) => IMul,
+        (&Op::Mul(..), F32) => FMul,
+        (&Op::Div(..), I32) => SDiv,
+        (&Op::Div(..), U32) => UDiv,
+        (&Op::Div(..), F32) => FDiv,
+        (&Op::Rem(..), I32) => SRem,
+        (&Op::Rem(..), U32) => URem,
+        (&Op::Rem(..), F32) => FRem,
+        (&Op::Shl(..), I32) | (&Op::Shl(..), U32) => Shl,
+        (&Op::Shr(..), I32) => SShr,
+        (&Op::Shr(..), U32) => UShr,
+        (&Op::BitAnd(..), F32) | (&Op::BitOr(..), F32) | (&Op::BitXor(..), F32) => return None,
+        (&Op::BitAnd(..), _) => And,
+        (&Op::BitOr(..), _) => Or,
+        (&Op::BitXor(..), _) => Xor,
+        (&Op::Eq(..), F32) => FEq,
+        (&Op::Eq(..), _) => IEq,
+        (&Op::Ne(..), F32) => FNe,
+        (&Op::Ne(..), _) => INe,
+        (&Op::Lt(..), I32) => SLt,
+        (&Op::Le(..), I32) => SLe,
+        (&Op::Gt(..), I32) => SGt,
+        (&Op::Ge(..), I32) => SGe,
+        (&Op::Lt(..), F32) => FLt,
+        (&Op::Le(..), F32) => FLe,
+        (&Op::Gt(..), F32) => FGt,
+        (&Op::Ge(..), F32) => FGe,
+        (&Op::Lt(..), _) => ULt,
+        (&Op::Le(..), _) => ULe,
+        (&Op::Gt(..), _) => UGt,
+        (&Op::Ge(..), _) => UGe,
+        _ => return None,
+    })
+}
+
+impl Instr {
+    pub(crate) fn is_shared(self) -> bool {
+        matches!(
+            self,
+            Instr::LoadShared(_, _)
+                | Instr::StoreShared(_, _)
+                | Instr::SharedArrayNew(_, _)
+                | Instr::SharedArrayLen(_, _)
+                | Instr::SharedArrayLoad(_, _, _)
+                | Instr::SharedArrayStore(_, _, _)
+                | Instr::MemoryBarrier
+                | Instr::ControlBarrier
+        )
+    }
+
+    pub(crate) fn registers(self) -> (Option<Reg>, Vec<Reg>) {
+        match self {
+            Instr::Const(r, _)
+            | Instr::LoadShared(r, _)
+            | Instr::WorkerId(r)
+            | Instr::NumWorkers(r)
+            | Instr::SharedArrayLen(r, _)
+            | Instr::PrivateArrayLen(r, _) => (Some(r), vec![]),
+            Instr::Copy(r, a) | Instr::Unary(_, r, a) => (Some(r), vec![a]),
+            Instr::Binary(_, r, a, b) => (Some(r), vec![a, b]),
+            Instr::SharedArrayLoad(r, _, i) | Instr::PrivateArrayLoad(r, _, i) => {
+                (Some(r), vec![i])
+            }
+            Instr::Phi(r, a0, _, a1, _) => (Some(r), vec![a0, a1]),
+            Instr::StoreShared(_, a)
+            | Instr::SharedArrayNew(_, a)
+            | Instr::PrivateArrayNew(_, a)
+            | Instr::JumpUnless(a, _) => (None, vec![a]),
+            Instr::SharedArrayStore(_, i, a) | Instr::PrivateArrayStore(_, i, a) => {
+                (None, vec![i, a])
+            }
+            Instr::Jump(_) | Instr::Label(_) | Instr::MemoryBarrier | Instr::ControlBarrier => {
+                (None, vec![])
+            }
+        }
+    }
+}
+
+impl UnaryOp {
+    pub(crate) fn eval(self, a: u32) -> u32 {
+        match self {
+            UnaryOp::Neg => a.wrapping_neg(),
+            UnaryOp::FNeg => (-f32::from_bits(a)).to_bits(),
+            UnaryOp::Not => !a,
+            UnaryOp::LogicalNot => (a == 0) as u32,
+            UnaryOp::U32fromF32 => semantics::u32_from_f32(f32::from_bits(a)),
+            UnaryOp::I32fromF32 => semantics::i32_from_f32(f32::from_bits(a)) as u32,
+            UnaryOp::F32fromU32 => (a as f32).to_bits(),
+            UnaryOp::F32fromI32 => (a as i32 as f32).to_bits(),
+        }
+    }
+}
+
+impl BinaryOp {
+    pub(crate) fn eval(self, a: u32, b: u32) -> u32 {
+        let (fa, fb) = (f32::from_bits(a), f32::from_bits(b));
+        let (sa, sb) = (a as i32, b as i32);
+        match self {
+            BinaryOp::IAdd => a.wrapping_add(b),
+            BinaryOp::ISub => a.wrapping_sub(b),
+            BinaryOp::IMul => a.wrapping_mul(b),
+            BinaryOp::SDiv => semantics::div_i32(sa, sb) as u32,
+            BinaryOp::UDiv => semantics::div_u32(a, b),
+            BinaryOp::SRem => semantics::rem_i32(sa, sb) as u32,
+            BinaryOp::URem => semantics::rem_u32(a, b),
+            BinaryOp::FAdd => (fa + fb).to_bits(),
+            BinaryOp::FSub => (fa - fb).to_bits(),
+            BinaryOp::FMul => (fa * fb).to_bits(),
+            BinaryOp::FDiv => (fa / fb).to_bits(),
+            BinaryOp::FRem => semantics::rem_f32(fa, fb).to_bits(),
+            BinaryOp::Shl => semantics::shl_u32(a, b),
+            BinaryOp::SShr => semantics::shr_i32(sa, b) as u32,
+            BinaryOp::UShr => semantics::shr_u32(a, b),
+            BinaryOp::And => a & b,
+            BinaryOp::Or => a | b,
+            BinaryOp::Xor => a ^ b,
+            BinaryOp::IEq => (a == b) as u32,
+            BinaryOp::INe => (a != b) as u32,
+            BinaryOp::FEq => (fa == fb) as u32,
+            BinaryOp::FNe => (fa != fb) as u32,
+            BinaryOp::SLt => (sa < sb) as u32,
+            BinaryOp::SLe => (sa <= sb) as u32,
+            BinaryOp::SGt => (sa > sb) as u32,
+            BinaryOp::SGe => (sa >= sb) as u32,
+            BinaryOp::ULt => (a < b) as u32,
+            BinaryOp::ULe => (a <= b) as u32,
+            BinaryOp::UGt => (a > b) as u32,
+            BinaryOp::UGe => (a >= b) as u32,
+            BinaryOp::FLt => (fa < fb) as u32,
+            BinaryOp::FLe => (fa <= fb) as u32,
+            BinaryOp::FGt => (fa > fb) as u32,
+            BinaryOp::FGe => (fa >= fb) as u32,
+        }
+    }
+}
@@ -0,0 +1,261 @@
+// Copyright 2018 | Dario Ostuni <dario.ostuni@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use bytecode::ArraySlot;
+use executable::CpuExecutable;
+use memory::{from_bits, vector_from_bits, SharedMemory};
+use std::sync::atomic::{AtomicBool, Ordering};
+use wcore::error::Error;
+use wcore::executor::{Executable, WorkSize};
+use wcore::program::{ConstantScalar, ConstantVector, LabelId, Op, TokenId, TokenType};
+use wcore::split::{next_phase, DONE};
+use worker::{Event, Worker};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Breakpoint {
+    Op(u32),
+    Label(LabelId),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DebugEvent {
+    Stepped,
+    Breakpoint(Breakpoint),
+    Finished,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Running,
+    Barrier,
+    Done,
+}
+
+#[derive(Debug)]
+pub struct Debugger<'a> {
+    executable: &'a CpuExecutable,
+    shared: SharedMemory,
+    abort: AtomicBool,
+    work_size: WorkSize,
+    group: u32,
+    workers: Vec<Worker>,
+    states: Vec<State>,
+    last: Vec<Option<u32>>,
+    active: usize,
+    breakpoints: Vec<Breakpoint>,
+    finished: bool,
+}
+
+impl CpuExecutable {
+    pub fn debug(&self) -> Result<Debugger<'_>, Error> {
+        self.check_bindings()?;
+        let work_size = self.work_size();
+        let num_workers = work_size
+            .num_workers()
+            .ok_or_else(|| Error::Validation(format!("Invalid work size {:?}", work_size)))?;
+        let group_size = work_size.group_size as usize;
+        let mut debugger = Debugger {
+            executable: self,
+            shared: self.load_shared(&[])?.0,
+            abort: AtomicBool::new(false),
+            work_size,
+            group: 0,
+            workers: (0..group_size)
+                .map(|_| Worker::new(&self.bytecode, num_workers, &self.config))
+                .collect(),
+            states: vec![State::Running; group_size],
+            last: vec![None; group_size],
+            active: 0,
+            breakpoints: Vec::new(),
+            finished: false,
+        };
+        debugger.start_group();
+        debugger.schedule()?;
+        Ok(debugger)
+    }
+}
+
+impl<'a> Debugger<'a> {
+    pub fn add_breakpoint(&mut self, breakpoint: Breakpoint) {
+        if !self.breakpoints.contains(&breakpoint) {
+            self.breakpoints.push(breakpoint);
+        }
+    }
+
+    pub fn remove_breakpoint(&mut self, breakpoint: Breakpoint) -> bool {
+        let len = self.breakpoints.len();
+        self.breakpoints.retain(|&x| x != breakpoint);
+        self.breakpoints.len() != len
+    }
+
+    pub fn breakpoints(&self) -> &[Breakpoint] {
+        &self.breakpoints
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    pub fn step(&mut self) -> Result<DebugEvent, Error> {
+        if self.finished {
+            return Ok(DebugEvent::Finished);
+        }
+        let executable = self.executable;
+        let bytecode = &executable.bytecode;
+        let worker = &mut self.workers[self.active];
+        let op = bytecode.origin[worker.pc()];
+        let event = worker
+            .step(bytecode, &self.shared, &self.abort)
+            .map_err(|fault| executable.describe(fault, worker.current()))?;
+        self.last[self.active] = Some(op);
+        match event {
+            Some(Event::Barrier) => self.states[self.active] = State::Barrier,
+            Some(Event::Done) => self.states[self.active] = State::Done,
+            None if worker.pc() >= bytecode.code.len() => self.states[self.active] = State::Done,
+            _ => (),
+        }
+        self.schedule()?;
+        Ok(if self.finished {
+            DebugEvent::Finished
+        } else {
+            DebugEvent::Stepped
+        })
+    }
+
+    pub fn resume(&mut self) -> Result<DebugEvent, Error> {
+        loop {
+            let event = self.step()?;
+            if event == DebugEvent::Finished {
+                return Ok(event);
+            }
+            if let Some(breakpoint) = self.hit() {
+                return Ok(DebugEvent::Breakpoint(breakpoint));
+            }
+        }
+    }
+
+    pub fn worker_id(&self) -> Option<u32> {
+        self.current().map(|x| x.worker_id())
+    }
+
+    pub fn op_id(&self) -> Option<u32> {
+        self.current()
+            .map(|x| self.executable.bytecode.origin[x.pc()])
+    }
+
+    pub fn op(&self) -> Option<&Op> {
+        self.op_id()
+            .and_then(|x| self.executable.program.op(x as usize))
+    }
+
+    pub fn value(&self, token: TokenId) -> Option<ConstantScalar> {
+        let bytecode = &self.executable.bytecode;
+        if let Some(&slot) = bytecode.scalars.get(&token) {
+            let bits = self.shared.scalars[slot as usize].load(Ordering::Relaxed);
+            return Some(from_bits(bytecode.shared_scalars[slot as usize], bits));
+        }
+        let worker = self.current()?;
+        let reg = bytecode.registers.iter().position(|&x| x == token)?;
+        let ty = match self.executable.program.symbol.get(&token) {
+            Some(&TokenType::Constant(ty)) | Some(&TokenType::Variable(ty)) => ty,
+            _ => return None,
+        };
+        Some(from_bits(ty, worker.register(reg as u32)))
+    }
+
+    pub fn array(&self, token: TokenId) -> Option<ConstantVector> {
+        match *self.executable.bytecode.arrays.get(&token)? {
+            ArraySlot::Shared(slot) => Some(self.shared.arrays[slot as usize].to_vector()),
+            ArraySlot::Private(slot) => {
+                let data = self.current()?.private_array(slot)?;
+                match self.executable.program.symbol.get(&token) {
+                    Some(&TokenType::Array(ty)) | Some(&TokenType::ArrayPointer(ty)) => {
+                        Some(vector_from_bits(ty, data))
+                    }
+                    _ => None,
+                }
+            }
+        }
+    }
+
+    fn current(&self) -> Option<&Worker> {
+        if self.finished {
+            None
+        } else {
+            Some(&self.workers[self.active])
+        }
+    }
+
+    fn hit(&self) -> Option<Breakpoint> {
+        let worker = self.current()?;
+        let bytecode = &self.executable.bytecode;
+        let op = bytecode.origin[worker.pc()];
+        self.breakpoints.iter().cloned().find(|&x| match x {
+            Breakpoint::Op(id) => id == op && self.last[self.active] != Some(id),
+            Breakpoint::Label(label) => bytecode.labels.get(&label) == Some(&worker.pc()),
+        })
+    }
+
+    fn start_group(&mut self) {
+        let group_size = self.work_size.group_size;
+        let code = &self.executable.bytecode.code;
+        for (local_id, worker) in self.workers.iter_mut().enumerate() {
+            worker.reset(self.group * group_size + local_id as u32);
+            self.states[local_id] = if code.is_empty() {
+                State::Done
+            } else {
+                State::Running
+            };
+            self.last[local_id] = None;
+        }
+        self.active = 0;
+    }
+
+    fn schedule(&mut self) -> Result<(), Error> {
+        loop {
+            let len = self.states.len();
+            if let Some(next) = (0..len)
+                .map(|x| (self.active + x) % len)
+                .find(|&x| self.states[x] == State::Running)
+            {
+                self.active = next;
+                return Ok(());
+            }
+            if self.states.contains(&State::Barrier) {
+                for state in &mut self.states {
+                    if *state == State::Barrier {
+                        *state = State::Running;
+                    }
+                }
+                continue;
+            }
+            self.group += 1;
+            if self.group >= self.work_size.groups {
+                if let Some((current, next)) = self.executable.phase_slots() {
+                    let phase = next_phase(&self.shared.arrays[next].bits())?;
+                    if phase != DONE {
+                        self.shared.scalars[current].store(phase, Ordering::Relaxed);
+                        self.group = 0;
+                        self.start_group();
+                        continue;
+                    }
+                }
+                self.executable.store_shared(&self.shared)?;
+                self.finished = true;
+                return Ok(());
+            }
+            self.start_group();
+        }
+    }
+}
@@ -12,23 +12,60 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use bytecode::ArraySlot;
+use bytecode::{Bytecode, SharedSlot};
+use executor::{CpuConfig, CpuMode};
+use memory::{from_bits, to_bits, SharedArray, SharedMemory};
+use race::{Access, Race, RaceDetector};
 use resource::CpuResource;
-use std::collections::HashMap;
-use std::ops::{Add, BitAnd, BitOr, BitXor, Div, Mul, Neg, Not, Rem, Shl, Shr, Sub};
-use std::sync::Arc;
-use wcore::executor::{Executable, Resource, IO};
-use wcore::program::DataType;
-use wcore::program::{ConstantScalar, ConstantVector, LabelId, Op, Program, TokenId, TokenValue};
+use schedule::{Schedule, Scheduler, MAX_EXHAUSTIVE_WORKERS, MAX_SCHEDULES};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use trace::{self, Trace, TraceEntry, TraceFilter};
+use uninit::{self, SharedShadow};
+use wcore::error::Error;
+use wcore::executor::{
+    check_binding, check_bindings, check_override, convergence_flag, BatchRun, Convergence,
+    Executable, Overrides, Report, Resource, WorkSize, IO,
+};
+use wcore::program::{
+    byte_size, get_token_type, Op, Parameter, Program, TokenId, TokenType, TokenValue,
+};
+use wcore::split::{is_hidden, next_phase, Phases, DONE, NEXT, PHASE};
+use wcore::submission::Submission;
+use wcore::types::Type;
+use worker::{Event, Fault, Worker};
 
 #[derive(Debug)]
 pub struct CpuExecutable {
     pub(crate) program: Program,
+    pub(crate) bytecode: Bytecode,
+    pub(crate) phases: Option<Phases>,
     pub(crate) binding: HashMap<(IO, String), Arc<CpuResource>>,
+    pub(crate) work_size: Option<WorkSize>,
+    pub(crate) config: CpuConfig,
+    pub(crate) trace_filter: Option<TraceFilter>,
+    pub(crate) trace: Option<Trace>,
+    pub(crate) compile_time: Duration,
+    pub(crate) races: Vec<Race>,
+}
+
+struct Context<'a> {
+    shared: &'a SharedMemory,
+    abort: &'a AtomicBool,
+    error: &'a Mutex<Option<Error>>,
+    trace: &'a Mutex<Vec<TraceEntry>>,
+    counts: &'a Mutex<Vec<u64>>,
+    initialized: Option<&'a SharedShadow>,
 }
 
 impl Executable for CpuExecutable {
-    type Error = String;
-    type Report = String;
+    type Error = Error;
+    type Report = Report;
     type Resource = CpuResource;
 
     fn bind<S: ToString>(
@@ -36,510 +73,871 @@ impl Executable for CpuExecutable {
         name: S,
         kind: IO,
         resource: Arc<CpuResource>,
-    ) -> Option<Arc<Self::Resource>> {
-        self.binding.insert((kind, name.to_string()), resource)
+    ) -> Result<Option<Arc<Self::Resource>>, Error> {
+        let name = name.to_string();
+        check_binding(&self.program, &name, kind, &*resource)?;
+        Ok(self.binding.insert((kind, name), resource))
     }
 
     fn unbind<S: ToString>(&mut self, name: S, kind: IO) -> Option<Arc<Self::Resource>> {
         self.binding.remove(&(kind, name.to_string()))
     }
 
-    fn run(&mut self) -> Result<String, String> {
-        self.simulate()
+    fn binding<S: ToString>(&self, name: S, kind: IO) -> Option<Arc<Self::Resource>> {
+        self.binding.get(&(kind, name.to_string())).cloned()
+    }
+
+    fn parameters(&self) -> Vec<Parameter> {
+        self.program.parameters()
+    }
+
+    fn check_bindings(&self) -> Result<(), Error> {
+        check_bindings(&self.program, |kind, name| self.binding(name, kind))
+    }
+
+    fn work_size(&self) -> WorkSize {
+        self.work_size
+            .unwrap_or_else(|| WorkSize::for_workers(self.config.threads as u32, 1))
+    }
+
+    fn set_work_size(&mut self, work_size: Option<WorkSize>) {
+        self.work_size = work_size;
+    }
+
+    fn run(&mut self) -> Result<Report, Error> {
+        self.check_bindings()?;
+        self.simulate(|_| true)
+    }
+
+    fn run_batch(&mut self, runs: &[Overrides]) -> Result<Vec<BatchRun<Report>>, Error> {
+        self.check_bindings()?;
+        let parameters = self.program.parameters();
+        for &(ref name, kind, ref value) in runs.iter().flatten() {
+            check_override(&parameters, name, kind, value)?;
+        }
+        let executable = &*self;
+        let chunk_size = runs.len().div_ceil(self.config.threads.max(1)).max(1);
+        let results: Vec<_> = thread::scope(|s| {
+            let handles: Vec<_> = runs
+                .chunks(chunk_size)
+                .map(|chunk| {
+                    s.spawn(move || {
+                        chunk
+                            .iter()
+                            .map(|x| executable.batch_run(x))
+                            .collect::<Vec<_>>()
+                    })
+                })
+                .collect();
+            handles
+                .into_iter()
+                .flat_map(|x| x.join().unwrap())
+                .collect()
+        });
+        results.into_iter().collect()
+    }
+
+    fn submit(&mut self) -> Result<Submission<Report, Error>, Error> {
+        self.check_bindings()?;
+        let executable = CpuExecutable {
+            program: self.program.clone(),
+            bytecode: self.bytecode.clone(),
+            phases: self.phases.clone(),
+            binding: self.binding.clone(),
+            work_size: self.work_size,
+            config: self.config,
+            trace_filter: None,
+            trace: None,
+            compile_time: self.compile_time,
+            races: Vec::new(),
+        };
+        let (locked, acquired) = mpsc::channel();
+        let submission = Submission::spawn(move || executable.run_locked(locked));
+        let _ = acquired.recv();
+        Ok(submission)
+    }
+
+    fn run_until<T: Type, F: FnMut(T) -> bool>(
+        &mut self,
+        flag: &str,
+        max_iterations: Option<u32>,
+        mut predicate: F,
+    ) -> Result<Convergence<Report>, Error> {
+        self.check_bindings()?;
+        let parameter = convergence_flag::<T>(&self.program.parameters(), flag)?;
+        let (slot, ty) = match self
+            .bytecode
+            .io
+            .iter()
+            .find(|x| x.0 == parameter.kind && x.1 == flag)
+        {
+            Some(&(_, _, SharedSlot::Scalar(x))) => {
+                (x as usize, self.bytecode.shared_scalars[x as usize])
+            }
+            _ => return Err(Error::MissingBinding(parameter.kind, flag.to_string())),
+        };
+        let (mut iterations, mut converged) = (0, false);
+        let report = self.simulate(|shared| {
+            iterations += 1;
+            let value =
+                TokenValue::Scalar(from_bits(ty, shared.scalars[slot].load(Ordering::Relaxed)));
+            converged = T::slice(&value).is_some_and(|x| predicate(x[0]));
+            converged || max_iterations.is_some_and(|x| iterations >= x)
+        })?;
+        Ok(Convergence {
+            iterations,
+            converged,
+            report,
+        })
     }
 }
 
 impl CpuExecutable {
-    fn simulate(&mut self) -> Result<String, String> {
-        let mut memory = HashMap::new();
-        for (name, id) in &self.program.input {
-            let value = self.binding
-                .get(&(IO::Input, name.clone()))
-                .ok_or_else(|| format!("Missing input {}", name))?;
-            memory.insert(*id, value.get_data());
+    pub fn set_trace(&mut self, filter: Option<TraceFilter>) {
+        self.trace_filter = filter;
+        self.trace = None;
+    }
+
+    pub fn trace(&self) -> Option<&Trace> {
+        self.trace.as_ref()
+    }
+
+    pub fn races(&self) -> &[Race] {
+        &self.races
+    }
+
+    fn simulate<F: FnMut(&SharedMemory) -> bool>(&mut self, stop: F) -> Result<Report, Error> {
+        let start = Instant::now();
+        let (shared, uploaded) = self.load_shared(&[])?;
+        let (mut races, mut trace) = (Vec::new(), None);
+        let result = self.execute(&shared, &mut races, &mut trace, stop);
+        self.races = races;
+        if trace.is_some() {
+            self.trace = trace;
         }
-        for (name, id) in &self.program.output {
-            let value = self.binding
-                .get(&(IO::Output, name.clone()))
-                .ok_or_else(|| format!("Missing output {}", name))?;
-            memory.insert(*id, value.get_data());
+        let mut report = result?;
+        report.wall_time = start.elapsed();
+        report.uploaded = uploaded;
+        report.downloaded = self.store_shared(&shared)?;
+        Ok(report)
+    }
+
+    fn batch_run(&self, overrides: &[(String, IO, TokenValue)]) -> Result<BatchRun<Report>, Error> {
+        let start = Instant::now();
+        let (shared, uploaded) = self.load_shared(overrides)?;
+        let mut report = self.execute(&shared, &mut Vec::new(), &mut None, |_| true)?;
+        let mut outputs = BTreeMap::new();
+        for (_, name, value) in self.read_outputs(&shared) {
+            report.downloaded += byte_size(&value);
+            outputs.insert(name.clone(), value);
         }
-        let operations = self.program.operation.clone();
-        let mut last_labels = (LabelId::default(), LabelId::default());
-        self.run_block(&operations, &mut memory, &mut last_labels)?;
-        for (name, id) in &self.program.output {
-            let value = memory
-                .remove(id)
-                .ok_or_else(|| format!("Missing output {}", name))?;
-            self.binding
-                .get(&(IO::Output, name.clone()))
-                .ok_or_else(|| format!("Missing output {}", name))?
-                .set_data(value);
+        report.wall_time = start.elapsed();
+        report.uploaded = uploaded;
+        Ok(BatchRun { outputs, report })
+    }
+
+    fn run_locked(&self, locked: Sender<()>) -> Result<Report, Error> {
+        let start = Instant::now();
+        let mut resources: Vec<_> = self.binding.values().collect();
+        resources.sort_by_key(|x| x.id);
+        resources.dedup_by_key(|x| x.id);
+        let mut data: Vec<_> = resources.iter().map(|x| x.data.lock().unwrap()).collect();
+        drop(locked);
+        let position = |resource: &CpuResource| {
+            resources
+                .binary_search_by_key(&resource.id, |x| x.id)
+                .unwrap()
+        };
+        let values: Vec<_> = self
+            .binding
+            .iter()
+            .map(|(&(kind, ref name), x)| (name.clone(), kind, data[position(x)].clone()))
+            .collect();
+        let (shared, uploaded) = self.load_shared(&values)?;
+        let mut report = self.execute(&shared, &mut Vec::new(), &mut None, |_| true)?;
+        for (kind, name, value) in self.read_outputs(&shared) {
+            report.downloaded += byte_size(&value);
+            *data[position(&self.binding[&(kind, name.clone())])] = value;
         }
-        Ok("Completed!".into())
+        report.wall_time = start.elapsed();
+        report.uploaded = uploaded;
+        Ok(report)
     }
 
-    fn run_block(
-        &mut self,
-        block: &[Op],
-        memory: &mut HashMap<TokenId, TokenValue>,
-        labels: &mut (LabelId, LabelId),
-    ) -> Result<(), String> {
-        for op in block {
-            match *op {
-                Op::Phi(r, a0, l0, a1, l1) => {
-                    let v;
-                    if labels.0 == l0 {
-                        v = Self::get_scalar(memory, a0)?;
-                    } else if labels.0 == l1 {
-                        v = Self::get_scalar(memory, a1)?;
-                    } else {
-                        unreachable!();
-                    }
-                    Self::insert_scalar(memory, r, v);
-                }
-                Op::If(ref cond_op, cond, l0, ref a0, lend) => {
-                    self.run_block(cond_op, memory, labels)?;
-                    let cond = Self::get_bool(memory, cond)?;
-                    if cond {
-                        Self::update_labels(labels, l0);
-                        self.run_block(a0, memory, labels)?;
-                    }
-                    Self::update_labels(labels, lend);
-                }
-                Op::IfElse(ref cond_op, cond, l0, ref a0, l1, ref a1, lend) => {
-                    self.run_block(cond_op, memory, labels)?;
-                    let cond = Self::get_bool(memory, cond)?;
-                    if cond {
-                        Self::update_labels(labels, l0);
-                        self.run_block(a0, memory, labels)?;
-                    } else {
-                        Self::update_labels(labels, l1);
-                        self.run_block(a1, memory, labels)?;
-                    }
-                    Self::update_labels(labels, lend);
-                }
-                Op::While(lcond, ref cond_op, cond, l0, ref a0, lend) => {
-                    Self::update_labels(labels, lcond);
-                    loop {
-                        self.run_block(cond_op, memory, labels)?;
-                        let condition = Self::get_bool(memory, cond)?;
-                        if !condition {
-                            break;
+    fn execute<F: FnMut(&SharedMemory) -> bool>(
+        &self,
+        shared: &SharedMemory,
+        races: &mut Vec<Race>,
+        trace: &mut Option<Trace>,
+        mut stop: F,
+    ) -> Result<Report, Error> {
+        let start = Instant::now();
+        let work_size = self.work_size();
+        let num_workers = work_size
+            .num_workers()
+            .ok_or_else(|| Error::Validation(format!("Invalid work size {:?}", work_size)))?;
+        if self.config.mode == CpuMode::Scheduled(Schedule::Exhaustive)
+            && num_workers > MAX_EXHAUSTIVE_WORKERS
+        {
+            return Err(Error::Validation(format!(
+                "Exhaustive scheduling supports at most {} workers, got {}",
+                MAX_EXHAUSTIVE_WORKERS, num_workers
+            )));
+        }
+        let barrier = self.bytecode.has_barrier && work_size.group_size > 1;
+        let counts = Mutex::new(vec![0; self.bytecode.code.len()]);
+        let initialized = if self.config.detect_uninit {
+            Some(SharedShadow::new(&self.program, &self.bytecode, shared))
+        } else {
+            None
+        };
+        let phases = self.phase_slots();
+        let mut iteration = 0;
+        let mut run_time = Duration::default();
+        loop {
+            let abort = AtomicBool::new(false);
+            let error = Mutex::new(None);
+            let entries = Mutex::new(Vec::new());
+            let context = Context {
+                shared,
+                abort: &abort,
+                error: &error,
+                trace: &entries,
+                counts: &counts,
+                initialized: initialized.as_ref(),
+            };
+            let run_start = Instant::now();
+            let timed_out = AtomicBool::new(false);
+            *races = thread::scope(|s| {
+                let (done, finished) = mpsc::channel::<()>();
+                if let Some(timeout) = self.config.timeout {
+                    let (abort, timed_out) = (&abort, &timed_out);
+                    s.spawn(move || {
+                        if finished.recv_timeout(timeout) == Err(RecvTimeoutError::Timeout) {
+                            timed_out.store(true, Ordering::SeqCst);
+                            abort.store(true, Ordering::SeqCst);
                         }
-                        Self::update_labels(labels, l0);
-                        self.run_block(a0, memory, labels)?;
-                    }
-                    Self::update_labels(labels, lend);
-                }
-                Op::MemoryBarrier => continue,
-                Op::ControlBarrier => continue,
-                Op::WorkerId(r) => Self::insert_scalar(memory, r, ConstantScalar::U32(0)),
-                Op::NumWorkers(r) => Self::insert_scalar(memory, r, ConstantScalar::U32(1)),
-                Op::Load(r, a) => {
-                    let v = Self::get_scalar(memory, a)?;
-                    Self::insert_scalar(memory, r, v);
+                    });
                 }
-                Op::Store(r, a) => {
-                    let v = Self::get_scalar(memory, a)?;
-                    Self::insert_scalar(memory, r, v);
+                let races = self.dispatch(work_size, num_workers, barrier, &context);
+                drop(done);
+                races
+            });
+            run_time += run_start.elapsed();
+            if self.trace_filter.is_some() {
+                let mut entries = entries.into_inner().unwrap();
+                entries.sort_by_key(|x| x.worker);
+                for entry in &mut entries {
+                    entry.iteration = iteration;
                 }
-                Op::Constant(r, a) => Self::insert_scalar(memory, r, a),
-                Op::U32fromF32(r, a) => {
-                    let v = Self::get_f32(memory, a)?;
-                    Self::insert_scalar(memory, r, ConstantScalar::U32(v as u32));
-                }
-                Op::I32fromF32(r, a) => {
-                    let v = Self::get_f32(memory, a)?;
-                    Self::insert_scalar(memory, r, ConstantScalar::I32(v as i32));
-                }
-                Op::F32fromU32(r, a) => {
-                    let v = Self::get_u32(memory, a)?;
-                    Self::insert_scalar(memory, r, ConstantScalar::F32(v as f32));
-                }
-                Op::F32fromI32(r, a) => {
-                    let v = Self::get_i32(memory, a)?;
-                    Self::insert_scalar(memory, r, ConstantScalar::F32(v as f32));
+                trace
+                    .get_or_insert_with(Trace::default)
+                    .entries
+                    .extend(entries);
+            }
+            let mut error = error.into_inner().unwrap();
+            if timed_out.load(Ordering::SeqCst) {
+                let timeout = self.config.timeout.unwrap_or_default();
+                error = Some(Error::Execution(match error {
+                    Some(e) => format!("Timeout of {:?} exceeded: {}", timeout, e),
+                    None => format!("Timeout of {:?} exceeded", timeout),
+                }));
+            }
+            if let Some(error) = error {
+                return Err(error);
+            }
+            if let Some(race) = races.first() {
+                let mut message = self.describe_race(race);
+                if races.len() > 1 {
+                    message += &format!(" and {} more races", races.len() - 1);
                 }
-                Op::I32fromU32(r, a) => {
-                    let v = Self::get_u32(memory, a)?;
-                    Self::insert_scalar(memory, r, ConstantScalar::I32(v as i32));
+                return Err(Error::Execution(message));
+            }
+            if let Some((current, next)) = phases {
+                let phase = next_phase(&shared.arrays[next].bits())?;
+                if phase != DONE {
+                    shared.scalars[current].store(phase, Ordering::Relaxed);
+                    continue;
                 }
-                Op::U32fromI32(r, a) => {
-                    let v = Self::get_i32(memory, a)?;
-                    Self::insert_scalar(memory, r, ConstantScalar::U32(v as u32));
+                shared.scalars[current].store(0, Ordering::Relaxed);
+            }
+            if stop(shared) {
+                break;
+            }
+            iteration += 1;
+        }
+        Ok(Report {
+            wall_time: start.elapsed(),
+            compile_time: self.compile_time,
+            run_time,
+            work_size,
+            workers: num_workers,
+            uploaded: 0,
+            downloaded: 0,
+            op_counts: if self.config.profile {
+                Some(self.op_counts(&counts.into_inner().unwrap()))
+            } else {
+                None
+            },
+        })
+    }
+
+    pub(crate) fn phase_slots(&self) -> Option<(usize, usize)> {
+        self.phases.as_ref()?;
+        let slot = |kind: IO, name: &str| match self
+            .bytecode
+            .io
+            .iter()
+            .find(|x| x.0 == kind && x.1 == name)
+        {
+            Some(&(_, _, slot)) => Some(slot),
+            _ => None,
+        };
+        match (slot(IO::Input, PHASE)?, slot(IO::Output, NEXT)?) {
+            (SharedSlot::Scalar(phase), SharedSlot::Array(next)) => {
+                Some((phase as usize, next as usize))
+            }
+            _ => None,
+        }
+    }
+
+    fn op_counts(&self, counts: &[u64]) -> BTreeMap<String, u64> {
+        let mut op_counts = BTreeMap::new();
+        for (pc, &count) in counts.iter().enumerate() {
+            if let Some(op) = self.program.op(self.bytecode.origin[pc] as usize) {
+                *op_counts.entry(op.name().to_string()).or_insert(0) += count;
+            }
+        }
+        op_counts
+    }
+
+    pub(crate) fn load_shared(
+        &self,
+        overrides: &[(String, IO, TokenValue)],
+    ) -> Result<(SharedMemory, u64), Error> {
+        let mut uploaded = 0;
+        let hidden = match self.phases {
+            Some(ref phases) => phases.initial(self.work_size().num_workers().unwrap_or(0)),
+            None => Vec::new(),
+        };
+        let mut shared = SharedMemory {
+            scalars: self
+                .bytecode
+                .shared_scalars
+                .iter()
+                .map(|_| AtomicU32::new(0))
+                .collect(),
+            arrays: self
+                .bytecode
+                .shared_arrays
+                .iter()
+                .map(|&(ty, max_size)| SharedArray::new(ty, max_size))
+                .collect(),
+        };
+        for &(kind, ref name, slot) in &self.bytecode.io {
+            let value = match overrides
+                .iter()
+                .chain(&hidden)
+                .find(|x| x.1 == kind && x.0 == *name)
+            {
+                Some(x) => x.2.clone(),
+                None => self
+                    .binding
+                    .get(&(kind, name.clone()))
+                    .ok_or_else(|| Error::MissingBinding(kind, name.clone()))?
+                    .get_data(),
+            };
+            if !is_hidden(name) {
+                uploaded += byte_size(&value);
+            }
+            match (slot, get_token_type(&value), value) {
+                (SharedSlot::Scalar(x), TokenType::Variable(ty), TokenValue::Scalar(v))
+                    if ty == self.bytecode.shared_scalars[x as usize] =>
+                {
+                    shared.scalars[x as usize] = AtomicU32::new(to_bits(v));
                 }
-                Op::Add(r, a, b) => Self::op_add(memory, r, a, b)?,
-                Op::Sub(r, a, b) => Self::op_sub(memory, r, a, b)?,
-                Op::Mul(r, a, b) => Self::op_mul(memory, r, a, b)?,
-                Op::Div(r, a, b) => Self::op_div(memory, r, a, b)?,
-                Op::Rem(r, a, b) => Self::op_rem(memory, r, a, b)?,
-                Op::Neg(r, a) => Self::op_neg(memory, r, a)?,
-                Op::Not(r, a) => Self::op_not(memory, r, a)?,
-                Op::Shl(r, a, b) => Self::op_shl(memory, r, a, b)?,
-                Op::Shr(r, a, b) => Self::op_shr(memory, r, a, b)?,
-                Op::BitAnd(r, a, b) => Self::op_bitand(memory, r, a, b)?,
-                Op::BitOr(r, a, b) => Self::op_bitor(memory, r, a, b)?,
-                Op::BitXor(r, a, b) => Self::op_bitxor(memory, r, a, b)?,
-                Op::ArrayNew(r, s, t, _, _) => {
-                    if memory.contains_key(&r) {
-                        continue;
-                    }
-                    let s = Self::get_u32(memory, s)?;
-                    Self::new_vector(memory, r, s, t);
+                (SharedSlot::Scalar(x), TokenType::Null, _) => {
+                    shared.scalars[x as usize] = AtomicU32::new(0);
                 }
-                Op::ArrayLen(r, v) => {
-                    let v = Self::get_vector_length(memory, v)?;
-                    Self::insert_scalar(memory, r, ConstantScalar::U32(v));
+                (SharedSlot::Array(x), TokenType::Array(ty), TokenValue::Vector(v))
+                    if ty == self.bytecode.shared_arrays[x as usize].0 =>
+                {
+                    shared.arrays[x as usize] = SharedArray::from_vector(&v);
                 }
-                Op::ArrayStore(v, i, a) => Self::insert_vector(memory, v, i, a)?,
-                Op::ArrayLoad(r, v, i) => {
-                    let v = Self::index_vector(memory, v, i)?;
-                    Self::insert_scalar(memory, r, v);
+                (SharedSlot::Array(x), TokenType::Null, _) => {
+                    let ty = self.bytecode.shared_arrays[x as usize].0;
+                    shared.arrays[x as usize] = SharedArray::new(ty, 0);
                 }
-                Op::Eq(r, a, b) => Self::op_eq(memory, r, a, b)?,
-                Op::Ne(r, a, b) => Self::op_ne(memory, r, a, b)?,
-                Op::Lt(r, a, b) => Self::op_lt(memory, r, a, b)?,
-                Op::Le(r, a, b) => Self::op_le(memory, r, a, b)?,
-                Op::Gt(r, a, b) => Self::op_gt(memory, r, a, b)?,
-                Op::Ge(r, a, b) => Self::op_ge(memory, r, a, b)?,
+                _ => return Err(Error::TypeMismatch(name.clone())),
             }
         }
-        Ok(())
+        Ok((shared, uploaded))
     }
 
-    fn update_labels(labels: &mut (LabelId, LabelId), new_label: LabelId) {
-        (labels.0).0 = (labels.1).0;
-        (labels.1).0 = new_label.0;
+    pub(crate) fn store_shared(&self, shared: &SharedMemory) -> Result<u64, Error> {
+        let mut downloaded = 0;
+        for (kind, name, value) in self.read_outputs(shared) {
+            downloaded += byte_size(&value);
+            self.binding[&(kind, name.clone())].set_data(value)?;
+        }
+        Ok(downloaded)
     }
 
-    fn get_scalar(
-        memory: &HashMap<TokenId, TokenValue>,
-        id: TokenId,
-    ) -> Result<ConstantScalar, String> {
-        let value = memory
-            .get(&id)
-            .ok_or_else(|| format!("{:?} doesn't exist!", id))?;
-        match *value {
-            TokenValue::Scalar(x) => Ok(x),
-            _ => unreachable!(),
-        }
+    fn read_outputs(&self, shared: &SharedMemory) -> Vec<(IO, &String, TokenValue)> {
+        self.bytecode
+            .io
+            .iter()
+            .filter(|x| x.0.writes() && !is_hidden(&x.1))
+            .map(|&(kind, ref name, slot)| {
+                let value = match slot {
+                    SharedSlot::Scalar(x) => {
+                        let bits = shared.scalars[x as usize].load(Ordering::Relaxed);
+                        TokenValue::Scalar(from_bits(
+                            self.bytecode.shared_scalars[x as usize],
+                            bits,
+                        ))
+                    }
+                    SharedSlot::Array(x) => {
+                        TokenValue::Vector(shared.arrays[x as usize].to_vector())
+                    }
+                };
+                (kind, name, value)
+            })
+            .collect()
     }
 
-    fn get_vector_mut(
-        memory: &mut HashMap<TokenId, TokenValue>,
-        v: TokenId,
-    ) -> Result<&mut ConstantVector, String> {
-        let value = memory
-            .get_mut(&v)
-            .ok_or_else(|| format!("{:?} doesn't exist!", v))?;
-        match *value {
-            TokenValue::Vector(ref mut x) => Ok(x),
-            _ => unreachable!(),
+    fn dispatch(
+        &self,
+        work_size: WorkSize,
+        num_workers: u32,
+        barrier: bool,
+        context: &Context,
+    ) -> Vec<Race> {
+        if self.config.detect_races {
+            return self.detect_races(work_size, num_workers, context);
         }
+        if let CpuMode::Scheduled(schedule) = self.config.mode {
+            self.run_scheduled(schedule, work_size, num_workers, context);
+        } else if self.config.mode == CpuMode::Sequential && barrier {
+            self.run_lockstep(work_size, num_workers, context, None);
+        } else if self.config.mode == CpuMode::Sequential {
+            let mut worker = Worker::new(&self.bytecode, num_workers, &self.config);
+            for worker_id in 0..num_workers {
+                if context.abort.load(Ordering::SeqCst) {
+                    break;
+                }
+                worker.reset(worker_id);
+                self.run_worker(&mut worker, context);
+            }
+            merge_counts(&worker, context);
+        } else {
+            self.run_parallel(work_size, barrier, context);
+        }
+        Vec::new()
     }
 
-    fn get_vector(
-        memory: &HashMap<TokenId, TokenValue>,
-        v: TokenId,
-    ) -> Result<&ConstantVector, String> {
-        let value = memory
-            .get(&v)
-            .ok_or_else(|| format!("{:?} doesn't exist!", v))?;
-        match *value {
-            TokenValue::Vector(ref x) => Ok(x),
-            _ => unreachable!(),
+    fn run_parallel(&self, work_size: WorkSize, barrier: bool, context: &Context) {
+        let num_workers = work_size.groups * work_size.group_size;
+        let next = AtomicU32::new(0);
+        if barrier {
+            thread::scope(|s| {
+                for _ in 0..self.config.threads.min(work_size.groups as usize) {
+                    s.spawn(|| {
+                        let mut workers = self.group_workers(work_size, num_workers, false);
+                        let mut entries = Vec::new();
+                        loop {
+                            let group = next.fetch_add(1, Ordering::Relaxed);
+                            if group >= work_size.groups
+                                || context.abort.load(Ordering::SeqCst)
+                                || !self.run_group(&mut workers, group, context, None, &mut entries)
+                            {
+                                break;
+                            }
+                        }
+                        for worker in &workers {
+                            merge_counts(worker, context);
+                        }
+                        context.trace.lock().unwrap().append(&mut entries);
+                    });
+                }
+            });
+        } else {
+            thread::scope(|s| {
+                for _ in 0..self.config.threads.min(num_workers as usize) {
+                    s.spawn(|| {
+                        let mut worker = Worker::new(&self.bytecode, num_workers, &self.config);
+                        loop {
+                            let worker_id = next.fetch_add(1, Ordering::Relaxed);
+                            if worker_id >= num_workers || context.abort.load(Ordering::SeqCst) {
+                                break;
+                            }
+                            worker.reset(worker_id);
+                            self.run_worker(&mut worker, context);
+                        }
+                        merge_counts(&worker, context);
+                    });
+                }
+            });
         }
     }
 
-    fn get_vector_length(memory: &HashMap<TokenId, TokenValue>, v: TokenId) -> Result<u32, String> {
-        Ok(match *Self::get_vector(memory, v)? {
-            ConstantVector::I32(ref x) => x.len(),
-            ConstantVector::U32(ref x) => x.len(),
-            ConstantVector::F32(ref x) => x.len(),
-            ConstantVector::Bool(ref x) => x.len(),
-        } as u32)
-    }
-
-    fn insert_vector(
-        memory: &mut HashMap<TokenId, TokenValue>,
-        v: TokenId,
-        i: TokenId,
-        a: TokenId,
-    ) -> Result<(), String> {
-        let a = Self::get_scalar(memory, a)?;
-        let i = Self::get_u32(memory, i)? as usize;
-        let v = Self::get_vector_mut(memory, v)?;
-        match (v, a) {
-            (&mut ConstantVector::I32(ref mut v), ConstantScalar::I32(a)) => v[i] = a,
-            (&mut ConstantVector::U32(ref mut v), ConstantScalar::U32(a)) => v[i] = a,
-            (&mut ConstantVector::F32(ref mut v), ConstantScalar::F32(a)) => v[i] = a,
-            (&mut ConstantVector::Bool(ref mut v), ConstantScalar::Bool(a)) => v[i] = a,
-            _ => unreachable!(),
+    fn run_worker(&self, worker: &mut Worker, context: &Context) {
+        let mut entries = Vec::new();
+        let result = loop {
+            match self.advance(worker, context, &mut entries) {
+                Ok(Event::Barrier) | Ok(Event::Flush) => {}
+                Ok(Event::Done) => break Ok(()),
+                Err(fault) => break Err(self.describe(fault, worker.current())),
+            }
         };
-        Ok(())
-    }
-
-    fn index_vector(
-        memory: &mut HashMap<TokenId, TokenValue>,
-        v: TokenId,
-        i: TokenId,
-    ) -> Result<ConstantScalar, String> {
-        let v = Self::get_vector(memory, v)?;
-        let i = Self::get_u32(memory, i)? as usize;
-        Ok(match *v {
-            ConstantVector::I32(ref v) => ConstantScalar::I32(v[i]),
-            ConstantVector::U32(ref v) => ConstantScalar::U32(v[i]),
-            ConstantVector::F32(ref v) => ConstantScalar::F32(v[i]),
-            ConstantVector::Bool(ref v) => ConstantScalar::Bool(v[i]),
-        })
+        if !entries.is_empty() {
+            context.trace.lock().unwrap().append(&mut entries);
+        }
+        if let Err(e) = result {
+            fail(context, e);
+        }
     }
 
-    fn insert_scalar(
-        memory: &mut HashMap<TokenId, TokenValue>,
-        id: TokenId,
-        value: ConstantScalar,
-    ) {
-        memory.insert(id, TokenValue::Scalar(value));
+    fn advance(
+        &self,
+        worker: &mut Worker,
+        context: &Context,
+        entries: &mut Vec<TraceEntry>,
+    ) -> Result<Event, Fault> {
+        if let Some(initialized) = context.initialized {
+            return uninit::run(
+                &self.bytecode,
+                worker,
+                context.shared,
+                context.abort,
+                initialized,
+            );
+        }
+        let filter = self
+            .trace_filter
+            .as_ref()
+            .filter(|x| x.accepts_worker(worker.worker_id()));
+        match filter {
+            Some(filter) => trace::run(
+                &self.program,
+                &self.bytecode,
+                worker,
+                context.shared,
+                context.abort,
+                filter,
+                entries,
+            ),
+            None => worker.run(&self.bytecode, context.shared, context.abort),
+        }
     }
 
-    fn new_vector(memory: &mut HashMap<TokenId, TokenValue>, id: TokenId, s: u32, t: DataType) {
-        let s = s as usize;
-        match t {
-            DataType::I32 => memory.insert(id, TokenValue::Vector(ConstantVector::I32(vec![0; s]))),
-            DataType::U32 => memory.insert(id, TokenValue::Vector(ConstantVector::U32(vec![0; s]))),
-            DataType::F32 => {
-                memory.insert(id, TokenValue::Vector(ConstantVector::F32(vec![0.0; s])))
+    fn detect_races(&self, work_size: WorkSize, num_workers: u32, context: &Context) -> Vec<Race> {
+        let mut arrays = vec![TokenId::default(); self.bytecode.shared_arrays.len()];
+        for (&id, &slot) in &self.bytecode.arrays {
+            if let ArraySlot::Shared(x) = slot {
+                arrays[x as usize] = id;
             }
-            DataType::Bool => {
-                memory.insert(id, TokenValue::Vector(ConstantVector::Bool(vec![false; s])))
-            }
-        };
+        }
+        let mut detector = RaceDetector::new(context.shared, arrays);
+        self.run_lockstep(work_size, num_workers, context, Some(&mut detector));
+        detector.races
     }
 
-    fn op_neg(
-        memory: &mut HashMap<TokenId, TokenValue>,
-        r: TokenId,
-        a: TokenId,
-    ) -> Result<(), String> {
-        let a = Self::get_scalar(memory, a)?;
-        let v = match a {
-            ConstantScalar::I32(x) => ConstantScalar::I32(Neg::neg(x)),
-            ConstantScalar::F32(x) => ConstantScalar::F32(Neg::neg(x)),
-            _ => unreachable!(),
-        };
-        Self::insert_scalar(memory, r, v);
-        Ok(())
-    }
-
-    fn op_not(
-        memory: &mut HashMap<TokenId, TokenValue>,
-        r: TokenId,
-        a: TokenId,
-    ) -> Result<(), String> {
-        let a = Self::get_scalar(memory, a)?;
-        let v = match a {
-            ConstantScalar::U32(x) => ConstantScalar::U32(Not::not(x)),
-            ConstantScalar::I32(x) => ConstantScalar::I32(Not::not(x)),
-            ConstantScalar::Bool(x) => ConstantScalar::Bool(Not::not(x)),
-            _ => unreachable!(),
-        };
-        Self::insert_scalar(memory, r, v);
-        Ok(())
+    fn run_lockstep(
+        &self,
+        work_size: WorkSize,
+        num_workers: u32,
+        context: &Context,
+        mut detector: Option<&mut RaceDetector>,
+    ) {
+        let mut workers = self.group_workers(work_size, num_workers, detector.is_some());
+        let mut entries = Vec::new();
+        for group in 0..work_size.groups {
+            let detector = detector.as_deref_mut();
+            if !self.run_group(&mut workers, group, context, detector, &mut entries) {
+                break;
+            }
+        }
+        for worker in &workers {
+            merge_counts(worker, context);
+        }
+        context.trace.lock().unwrap().append(&mut entries);
     }
-}
 
-macro_rules! impl_get_type {
-    ($lower:ty, $upper:ident, $fn_name:ident) => {
-        impl CpuExecutable {
-            fn $fn_name(
-                memory: &HashMap<TokenId, TokenValue>,
-                id: TokenId,
-            ) -> Result<$lower, String> {
-                let value = Self::get_scalar(memory, id)?;
-                match value {
-                    ConstantScalar::$upper(x) => Ok(x),
-                    _ => unreachable!(),
+    fn group_workers(&self, work_size: WorkSize, num_workers: u32, log: bool) -> Vec<Worker> {
+        (0..work_size.group_size)
+            .map(|_| {
+                let mut worker = Worker::new(&self.bytecode, num_workers, &self.config);
+                if log {
+                    worker.log_accesses();
                 }
-            }
-        }
-    };
-}
+                worker
+            })
+            .collect()
+    }
 
-macro_rules! impl_binary_op {
-    ($fn_name:ident, $lower:ident, $upper:ident) => {
-        impl CpuExecutable {
-            fn $fn_name(
-                memory: &mut HashMap<TokenId, TokenValue>,
-                r: TokenId,
-                a: TokenId,
-                b: TokenId,
-            ) -> Result<(), String> {
-                let a = Self::get_scalar(memory, a)?;
-                let b = Self::get_scalar(memory, b)?;
-                let v = match (a, b) {
-                    (ConstantScalar::U32(x), ConstantScalar::U32(y)) => {
-                        ConstantScalar::U32($upper::$lower(x, y))
-                    }
-                    (ConstantScalar::I32(x), ConstantScalar::I32(y)) => {
-                        ConstantScalar::I32($upper::$lower(x, y))
+    fn run_group(
+        &self,
+        workers: &mut [Worker],
+        group: u32,
+        context: &Context,
+        mut detector: Option<&mut RaceDetector>,
+        entries: &mut Vec<TraceEntry>,
+    ) -> bool {
+        let group_size = workers.len() as u32;
+        let mut running = vec![true; workers.len()];
+        for (local_id, worker) in workers.iter_mut().enumerate() {
+            worker.reset(group * group_size + local_id as u32);
+        }
+        let mut epoch = 0;
+        while running.contains(&true) {
+            for (local_id, worker) in workers.iter_mut().enumerate() {
+                if !running[local_id] {
+                    continue;
+                }
+                loop {
+                    let event = self.advance(worker, context, entries);
+                    if let Some(ref mut detector) = detector {
+                        for (array, index, write, pc) in worker.take_accesses() {
+                            let access = Access {
+                                worker: worker.worker_id(),
+                                op: self.bytecode.origin[pc],
+                                write,
+                            };
+                            detector.record(array, index, access, group, epoch);
+                        }
                     }
-                    (ConstantScalar::F32(x), ConstantScalar::F32(y)) => {
-                        ConstantScalar::F32($upper::$lower(x, y))
+                    match event {
+                        Ok(Event::Flush) => {}
+                        Ok(Event::Barrier) => break,
+                        Ok(Event::Done) => {
+                            running[local_id] = false;
+                            break;
+                        }
+                        Err(fault) => {
+                            fail(context, self.describe(fault, worker.current()));
+                            return false;
+                        }
                     }
-                    _ => unreachable!(),
-                };
-                Self::insert_scalar(memory, r, v);
-                Ok(())
+                }
             }
+            epoch += 1;
         }
-    };
-}
+        true
+    }
 
-macro_rules! impl_binary_shift_op {
-    ($fn_name:ident, $lower:ident, $upper:ident) => {
-        impl CpuExecutable {
-            fn $fn_name(
-                memory: &mut HashMap<TokenId, TokenValue>,
-                r: TokenId,
-                a: TokenId,
-                b: TokenId,
-            ) -> Result<(), String> {
-                let a = Self::get_scalar(memory, a)?;
-                let b = Self::get_scalar(memory, b)?;
-                let v = match (a, b) {
-                    (ConstantScalar::U32(x), ConstantScalar::U32(y)) => {
-                        ConstantScalar::U32($upper::$lower(x, y))
-                    }
-                    (ConstantScalar::I32(x), ConstantScalar::U32(y)) => {
-                        ConstantScalar::I32($upper::$lower(x, y))
-                    }
-                    (ConstantScalar::U32(x), ConstantScalar::I32(y)) => {
-                        ConstantScalar::U32($upper::$lower(x, y))
-                    }
-                    (ConstantScalar::I32(x), ConstantScalar::I32(y)) => {
-                        ConstantScalar::I32($upper::$lower(x, y))
+    fn run_scheduled(
+        &self,
+        schedule: Schedule,
+        work_size: WorkSize,
+        num_workers: u32,
+        context: &Context,
+    ) {
+        let mut scheduler = Scheduler::new(schedule, self.config.schedules);
+        let initial = context.shared.clone();
+        let result = self.run_schedule(
+            &mut scheduler,
+            work_size,
+            num_workers,
+            context.shared,
+            context,
+        );
+        match result {
+            Ok(workers) => {
+                for worker in &workers {
+                    merge_counts(worker, context);
+                }
+            }
+            Err(e) => return fail(context, annotate(e, &scheduler.describe())),
+        }
+        let expected = self.output_bits(context.shared);
+        let mut schedules = 1;
+        while schedules < MAX_SCHEDULES && scheduler.next() {
+            let shared = initial.clone();
+            let result = self
+                .run_schedule(&mut scheduler, work_size, num_workers, &shared, context)
+                .and_then(|_| {
+                    let outputs = self.output_bits(&shared);
+                    match outputs.iter().zip(&expected).find(|(x, y)| x != y) {
+                        Some((x, _)) => Err(Error::Execution(format!(
+                            "Output {} depends on the schedule",
+                            x.0
+                        ))),
+                        None => Ok(()),
                     }
-                    _ => unreachable!(),
-                };
-                Self::insert_scalar(memory, r, v);
-                Ok(())
+                });
+            if let Err(e) = result {
+                return fail(context, annotate(e, &scheduler.describe()));
             }
+            schedules += 1;
         }
-    };
-}
+        if schedule == Schedule::Exhaustive && schedules == MAX_SCHEDULES && scheduler.next() {
+            fail(
+                context,
+                Error::Execution(format!(
+                    "Exhaustive scheduling stopped after {} schedules without covering every interleaving",
+                    MAX_SCHEDULES
+                )),
+            );
+        }
+    }
 
-macro_rules! impl_binary_binary_op {
-    ($fn_name:ident, $lower:ident, $upper:ident) => {
-        impl CpuExecutable {
-            fn $fn_name(
-                memory: &mut HashMap<TokenId, TokenValue>,
-                r: TokenId,
-                a: TokenId,
-                b: TokenId,
-            ) -> Result<(), String> {
-                let a = Self::get_scalar(memory, a)?;
-                let b = Self::get_scalar(memory, b)?;
-                let v = match (a, b) {
-                    (ConstantScalar::U32(x), ConstantScalar::U32(y)) => {
-                        ConstantScalar::U32($upper::$lower(x, y))
-                    }
-                    (ConstantScalar::I32(x), ConstantScalar::I32(y)) => {
-                        ConstantScalar::I32($upper::$lower(x, y))
-                    }
-                    (ConstantScalar::Bool(x), ConstantScalar::Bool(y)) => {
-                        ConstantScalar::Bool($upper::$lower(x, y))
+    fn run_schedule(
+        &self,
+        scheduler: &mut Scheduler,
+        work_size: WorkSize,
+        num_workers: u32,
+        shared: &SharedMemory,
+        context: &Context,
+    ) -> Result<Vec<Worker>, Error> {
+        let group_size = work_size.group_size as usize;
+        let mut workers: Vec<Worker> = (0..num_workers)
+            .map(|worker_id| {
+                let mut worker = Worker::new(&self.bytecode, num_workers, &self.config);
+                worker.reset(worker_id);
+                worker
+            })
+            .collect();
+        let mut states = vec![None; num_workers as usize];
+        loop {
+            let runnable: Vec<u32> = (0..num_workers)
+                .filter(|&x| states[x as usize].is_none())
+                .collect();
+            if runnable.is_empty() {
+                break;
+            }
+            let worker_id = scheduler.choose(&runnable) as usize;
+            let worker = &mut workers[worker_id];
+            let event = self
+                .slice(worker, shared, context)
+                .map_err(|fault| self.describe(fault, worker.current()))?;
+            states[worker_id] = event;
+            let group = worker_id / group_size * group_size;
+            let group = &mut states[group..group + group_size];
+            if !group.contains(&None) {
+                for state in group {
+                    if *state == Some(Event::Barrier) {
+                        *state = None;
                     }
-                    _ => unreachable!(),
-                };
-                Self::insert_scalar(memory, r, v);
-                Ok(())
+                }
             }
         }
-    };
-}
+        Ok(workers)
+    }
 
-macro_rules! impl_binary_ord_op {
-    ($fn_name:ident, $lower:ident, $upper:ident) => {
-        impl CpuExecutable {
-            fn $fn_name(
-                memory: &mut HashMap<TokenId, TokenValue>,
-                r: TokenId,
-                a: TokenId,
-                b: TokenId,
-            ) -> Result<(), String> {
-                let a = Self::get_scalar(memory, a)?;
-                let b = Self::get_scalar(memory, b)?;
-                let v = match (a, b) {
-                    (ConstantScalar::U32(x), ConstantScalar::U32(y)) => {
-                        ConstantScalar::Bool(PartialOrd::$lower(&x, &y))
-                    }
-                    (ConstantScalar::I32(x), ConstantScalar::I32(y)) => {
-                        ConstantScalar::Bool(PartialOrd::$lower(&x, &y))
-                    }
-                    (ConstantScalar::F32(x), ConstantScalar::F32(y)) => {
-                        ConstantScalar::Bool(PartialOrd::$lower(&x, &y))
-                    }
-                    _ => unreachable!(),
-                };
-                Self::insert_scalar(memory, r, v);
-                Ok(())
+    fn slice(
+        &self,
+        worker: &mut Worker,
+        shared: &SharedMemory,
+        context: &Context,
+    ) -> Result<Option<Event>, Fault> {
+        loop {
+            let visible = self
+                .bytecode
+                .code
+                .get(worker.pc())
+                .is_some_and(|x| x.is_shared());
+            match worker.step(&self.bytecode, shared, context.abort)? {
+                Some(Event::Flush) | None if !visible => {}
+                Some(Event::Flush) | None => return Ok(None),
+                event => return Ok(event),
             }
         }
-    };
-}
+    }
 
-macro_rules! impl_binary_eq_op {
-    ($fn_name:ident, $lower:ident, $upper:ident) => {
-        impl CpuExecutable {
-            fn $fn_name(
-                memory: &mut HashMap<TokenId, TokenValue>,
-                r: TokenId,
-                a: TokenId,
-                b: TokenId,
-            ) -> Result<(), String> {
-                let a = Self::get_scalar(memory, a)?;
-                let b = Self::get_scalar(memory, b)?;
-                let v = match (a, b) {
-                    (ConstantScalar::U32(x), ConstantScalar::U32(y)) => {
-                        ConstantScalar::Bool(PartialEq::$lower(&x, &y))
-                    }
-                    (ConstantScalar::I32(x), ConstantScalar::I32(y)) => {
-                        ConstantScalar::Bool(PartialEq::$lower(&x, &y))
+    fn output_bits(&self, shared: &SharedMemory) -> Vec<(String, Vec<u32>)> {
+        self.bytecode
+            .io
+            .iter()
+            .filter(|x| x.0.writes())
+            .map(|&(_, ref name, slot)| {
+                let bits = match slot {
+                    SharedSlot::Scalar(x) => {
+                        vec![shared.scalars[x as usize].load(Ordering::Relaxed)]
                     }
-                    (ConstantScalar::F32(x), ConstantScalar::F32(y)) => {
-                        ConstantScalar::Bool(PartialEq::$lower(&x, &y))
-                    }
-                    (ConstantScalar::Bool(x), ConstantScalar::Bool(y)) => {
-                        ConstantScalar::Bool(PartialEq::$lower(&x, &y))
-                    }
-                    _ => unreachable!(),
+                    SharedSlot::Array(x) => shared.arrays[x as usize].bits(),
                 };
-                Self::insert_scalar(memory, r, v);
-                Ok(())
+                (name.clone(), bits)
+            })
+            .collect()
+    }
+
+    fn describe_race(&self, race: &Race) -> String {
+        let access = |x: &Access| {
+            let kind = if x.write { "write" } else { "read" };
+            match self.program.op(x.op as usize) {
+                Some(op) => format!("{} by worker {} at op #{} ({:?})", kind, x.worker, x.op, op),
+                None => format!("{} by worker {} at op #{}", kind, x.worker, x.op),
+            }
+        };
+        format!(
+            "Data race on {:?}[{}]: {} conflicts with {}",
+            race.array,
+            race.index,
+            access(&race.first),
+            access(&race.second)
+        )
+    }
+
+    pub(crate) fn describe(&self, fault: Fault, pc: usize) -> Error {
+        let id = self.bytecode.origin[pc];
+        let bounds = matches!(fault, Fault::OutOfBounds(_, _) | Fault::TooLarge(_, _));
+        let message = match (fault, self.program.op(id as usize)) {
+            (Fault::OutOfBounds(index, length), Some(op @ &Op::ArrayLoad(_, v, _)))
+            | (Fault::OutOfBounds(index, length), Some(op @ &Op::ArrayStore(v, _, _))) => format!(
+                "Index {} out of bounds for {:?} of length {} at op #{} ({:?})",
+                index, v, length, id, op
+            ),
+            (Fault::TooLarge(size, max_size), Some(op @ &Op::ArrayNew(v, _, _, _, _))) => format!(
+                "Size {} exceeds the max size {} of {:?} at op #{} ({:?})",
+                size, max_size, v, id, op
+            ),
+            (Fault::Budget(budget), Some(&Op::While(_, _, cond, _, _, _))) => format!(
+                "Loop budget of {} iterations exceeded at op #{} (While on {:?})",
+                budget, id, cond
+            ),
+            (Fault::Aborted, Some(&Op::While(_, _, cond, _, _, _))) => {
+                format!("Aborted at op #{} (While on {:?})", id, cond)
             }
+            (Fault::Uninitialized(None), Some(op @ &Op::Load(_, v))) => {
+                format!("Read of uninitialized {:?} at op #{} ({:?})", v, id, op)
+            }
+            (Fault::Uninitialized(Some(index)), Some(op @ &Op::ArrayLoad(_, v, _))) => format!(
+                "Read of uninitialized element {} of {:?} at op #{} ({:?})",
+                index, v, id, op
+            ),
+            (Fault::Error(e), _) => e,
+            (fault, _) => format!("{:?} at op #{}", fault, id),
+        };
+        if bounds {
+            Error::OutOfBounds(message)
+        } else {
+            Error::Execution(message)
         }
-    };
+    }
+}
+
+fn merge_counts(worker: &Worker, context: &Context) {
+    if worker.counts().is_empty() {
+        return;
+    }
+    let mut counts = context.counts.lock().unwrap();
+    for (total, count) in counts.iter_mut().zip(worker.counts()) {
+        *total += count;
+    }
 }
 
-impl_get_type!(u32, U32, get_u32);
-impl_get_type!(i32, I32, get_i32);
-impl_get_type!(f32, F32, get_f32);
-impl_get_type!(bool, Bool, get_bool);
-
-impl_binary_op!(op_add, add, Add);
-impl_binary_op!(op_sub, sub, Sub);
-impl_binary_op!(op_mul, mul, Mul);
-impl_binary_op!(op_div, div, Div);
-impl_binary_op!(op_rem, rem, Rem);
-impl_binary_binary_op!(op_bitand, bitand, BitAnd);
-impl_binary_binary_op!(op_bitor, bitor, BitOr);
-impl_binary_binary_op!(op_bitxor, bitxor, BitXor);
-impl_binary_shift_op!(op_shl, shl, Shl);
-impl_binary_shift_op!(op_shr, shr, Shr);
-impl_binary_eq_op!(op_eq, eq, Eq);
-impl_binary_eq_op!(op_ne, ne, Ne);
-impl_binary_ord_op!(op_lt, lt, Lt);
-impl_binary_ord_op!(op_le, le, Le);
-impl_binary_ord_op!(op_gt, gt, Gt);
-impl_binary_ord_op!(op_ge, ge, Ge);
+fn annotate(error: Error, schedule: &str) -> Error {
+    match error {
+        Error::OutOfBounds(e) => Error::OutOfBounds(format!("{} under the {}", e, schedule)),
+        e => Error::Execution(format!("{} under the {}", e, schedule)),
+    }
+}
+
+fn fail(context: &Context, e: Error) {
+    let mut error = context.error.lock().unwrap();
+    if error.is_none() {
+        *error = Some(e);
+    }
+    context.abort.store(true, Ordering::SeqCst);
+}
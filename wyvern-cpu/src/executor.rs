@@ -12,35 +12,203 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use bytecode::Bytecode;
 use executable::CpuExecutable;
 use rand::{thread_rng, Rng};
 use resource::CpuResource;
+use schedule::{Schedule, MAX_SCHEDULES};
 use std::collections::HashMap;
+use std::str::FromStr;
 use std::sync::{Arc, Mutex};
-use wcore::executor::Executor;
+use std::thread;
+use std::time::{Duration, Instant};
+use wcore::analysis::uninitialized_loads;
+use wcore::error::Error;
+use wcore::executor::{env_flag, env_override, Executor};
 use wcore::program::{Program, TokenValue};
+use wcore::split::split;
 
 #[derive(Debug)]
-pub struct CpuExecutor {}
+pub struct CpuExecutor {
+    config: CpuConfig,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CpuMode {
+    Sequential,
+    Parallel,
+    Scheduled(Schedule),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CpuConfig {
+    pub mode: CpuMode,
+    pub threads: usize,
+    pub schedules: u32,
+    pub profile: bool,
+    pub detect_races: bool,
+    pub detect_uninit: bool,
+    pub max_iterations: Option<u64>,
+    pub timeout: Option<Duration>,
+}
+
+impl Default for CpuConfig {
+    fn default() -> CpuConfig {
+        CpuConfig {
+            mode: CpuMode::Sequential,
+            threads: thread::available_parallelism()
+                .map(|x| x.get())
+                .unwrap_or(1),
+            schedules: 1,
+            profile: false,
+            detect_races: false,
+            detect_uninit: false,
+            max_iterations: None,
+            timeout: None,
+        }
+    }
+}
+
+impl FromStr for CpuMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<CpuMode, String> {
+        if let Some(schedule) = s.strip_prefix("scheduled:") {
+            return schedule.parse().map(CpuMode::Scheduled);
+        }
+        match s {
+            "sequential" => Ok(CpuMode::Sequential),
+            "parallel" => Ok(CpuMode::Parallel),
+            _ => Err(format!("Unknown CPU mode {}", s)),
+        }
+    }
+}
+
+impl CpuConfig {
+    pub fn mode(mut self, mode: CpuMode) -> CpuConfig {
+        self.mode = mode;
+        self
+    }
+
+    pub fn threads(mut self, threads: usize) -> CpuConfig {
+        self.threads = threads;
+        self
+    }
+
+    pub fn schedules(mut self, schedules: u32) -> CpuConfig {
+        self.schedules = schedules;
+        self
+    }
+
+    pub fn profile(mut self, profile: bool) -> CpuConfig {
+        self.profile = profile;
+        self
+    }
+
+    pub fn detect_races(mut self, detect_races: bool) -> CpuConfig {
+        self.detect_races = detect_races;
+        self
+    }
+
+    pub fn detect_uninit(mut self, detect_uninit: bool) -> CpuConfig {
+        self.detect_uninit = detect_uninit;
+        self
+    }
+
+    pub fn max_iterations(mut self, max_iterations: Option<u64>) -> CpuConfig {
+        self.max_iterations = max_iterations;
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Option<Duration>) -> CpuConfig {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn from_env() -> Result<CpuConfig, Error> {
+        CpuConfig::default().env_overrides()
+    }
+
+    pub fn env_overrides(mut self) -> Result<CpuConfig, Error> {
+        if let Some(mode) = env_override("WYVERN_CPU_MODE")? {
+            self.mode = mode;
+        }
+        if let Some(threads) = env_override("WYVERN_CPU_THREADS")? {
+            self.threads = threads;
+        }
+        if let Some(schedules) = env_override("WYVERN_CPU_SCHEDULES")? {
+            self.schedules = schedules;
+        }
+        if let Some(profile) = env_flag("WYVERN_CPU_PROFILE")? {
+            self.profile = profile;
+        }
+        if let Some(detect_races) = env_flag("WYVERN_CPU_DETECT_RACES")? {
+            self.detect_races = detect_races;
+        }
+        if let Some(detect_uninit) = env_flag("WYVERN_CPU_DETECT_UNINIT")? {
+            self.detect_uninit = detect_uninit;
+        }
+        if let Some(max_iterations) = env_override("WYVERN_CPU_MAX_ITERATIONS")? {
+            self.max_iterations = Some(max_iterations);
+        }
+        if let Some(timeout) = env_override("WYVERN_CPU_TIMEOUT_MS")? {
+            self.timeout = Some(Duration::from_millis(timeout));
+        }
+        Ok(self)
+    }
+}
 
 impl Executor for CpuExecutor {
-    type Config = ();
-    type Error = String;
+    type Config = CpuConfig;
+    type Error = Error;
     type Resource = CpuResource;
     type Executable = CpuExecutable;
 
-    fn new(_config: ()) -> Result<CpuExecutor, String> {
-        Ok(CpuExecutor {})
+    fn new(config: CpuConfig) -> Result<CpuExecutor, Error> {
+        if config.threads == 0 {
+            return Err(Error::Validation("At least one thread is required".into()));
+        }
+        if config.schedules == 0 || config.schedules as usize > MAX_SCHEDULES {
+            return Err(Error::Validation(format!(
+                "The schedule count must be between 1 and {}, got {}",
+                MAX_SCHEDULES, config.schedules
+            )));
+        }
+        Ok(CpuExecutor { config })
     }
 
-    fn compile(&self, program: Program) -> Result<CpuExecutable, String> {
+    fn compile(&self, program: Program) -> Result<CpuExecutable, Error> {
+        let start = Instant::now();
+        if self.config.detect_uninit {
+            let loads = uninitialized_loads(&program);
+            if let Some(load) = loads.first() {
+                let mut message =
+                    format!("Load of uninitialized {:?} at op #{}", load.token, load.op);
+                if let Some(op) = program.op(load.op as usize) {
+                    message += &format!(" ({:?})", op);
+                }
+                if loads.len() > 1 {
+                    message += &format!(" and {} more uninitialized loads", loads.len() - 1);
+                }
+                return Err(Error::Validation(message));
+            }
+        }
+        let (program, phases) = split(program)?;
         Ok(CpuExecutable {
+            bytecode: Bytecode::new(&program).map_err(Error::Compilation)?,
             program,
+            phases,
             binding: HashMap::new(),
+            work_size: None,
+            config: self.config,
+            trace_filter: None,
+            trace: None,
+            compile_time: start.elapsed(),
+            races: Vec::new(),
         })
     }
 
-    fn new_resource(&self) -> Result<Arc<CpuResource>, String> {
+    fn new_resource(&self) -> Result<Arc<CpuResource>, Error> {
         Ok(Arc::new(CpuResource {
             id: thread_rng().gen(),
             data: Mutex::new(TokenValue::Null),
@@ -13,8 +13,19 @@
 // limitations under the License.
 
 extern crate rand;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
 extern crate wyvern_core as wcore;
 
+mod bytecode;
+pub mod debugger;
 pub mod executable;
 pub mod executor;
+mod memory;
+pub mod race;
 pub mod resource;
+pub mod schedule;
+pub mod trace;
+mod uninit;
+mod worker;
@@ -0,0 +1,195 @@
+// Copyright 2018 | Dario Ostuni <dario.ostuni@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::RwLock;
+use wcore::program::{ConstantScalar, ConstantVector, DataType};
+
+#[derive(Debug)]
+pub(crate) struct SharedArray {
+    ty: DataType,
+    len: AtomicU32,
+    data: Elements,
+}
+
+#[derive(Debug)]
+enum Elements {
+    Fixed(Vec<AtomicU32>),
+    Growable(RwLock<Vec<AtomicU32>>),
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct SharedMemory {
+    pub(crate) scalars: Vec<AtomicU32>,
+    pub(crate) arrays: Vec<SharedArray>,
+}
+
+pub(crate) fn to_bits(value: ConstantScalar) -> u32 {
+    match value {
+        ConstantScalar::Bool(x) => x as u32,
+        ConstantScalar::I32(x) => x as u32,
+        ConstantScalar::U32(x) => x,
+        ConstantScalar::F32(x) => x.to_bits(),
+    }
+}
+
+pub(crate) fn from_bits(ty: DataType, bits: u32) -> ConstantScalar {
+    match ty {
+        DataType::Bool => ConstantScalar::Bool(bits != 0),
+        DataType::I32 => ConstantScalar::I32(bits as i32),
+        DataType::U32 => ConstantScalar::U32(bits),
+        DataType::F32 => ConstantScalar::F32(f32::from_bits(bits)),
+    }
+}
+
+pub(crate) fn vector_from_bits(ty: DataType, bits: &[u32]) -> ConstantVector {
+    let data = bits.iter().cloned();
+    match ty {
+        DataType::Bool => ConstantVector::Bool(data.map(|x| x != 0).collect()),
+        DataType::I32 => ConstantVector::I32(data.map(|x| x as i32).collect()),
+        DataType::U32 => ConstantVector::U32(data.collect()),
+        DataType::F32 => ConstantVector::F32(data.map(f32::from_bits).collect()),
+    }
+}
+
+impl Clone for SharedMemory {
+    fn clone(&self) -> SharedMemory {
+        SharedMemory {
+            scalars: self
+                .scalars
+                .iter()
+                .map(|x| AtomicU32::new(x.load(Ordering::Relaxed)))
+                .collect(),
+            arrays: self.arrays.clone(),
+        }
+    }
+}
+
+impl Clone for SharedArray {
+    fn clone(&self) -> SharedArray {
+        SharedArray {
+            ty: self.ty,
+            len: AtomicU32::new(self.len()),
+            data: self.data.clone(),
+        }
+    }
+}
+
+impl Clone for Elements {
+    fn clone(&self) -> Elements {
+        let copy = |data: &[AtomicU32]| {
+            data.iter()
+                .map(|x| AtomicU32::new(x.load(Ordering::Relaxed)))
+                .collect()
+        };
+        match *self {
+            Elements::Fixed(ref data) => Elements::Fixed(copy(data)),
+            Elements::Growable(ref data) => Elements::Growable(RwLock::new(copy(&data.read().unwrap()))),
+        }
+    }
+}
+
+impl SharedArray {
+    pub(crate) fn new(ty: DataType, max_size: u32) -> SharedArray {
+        let data = (0..max_size).map(|_| AtomicU32::new(0)).collect();
+        SharedArray {
+            ty,
+            len: AtomicU32::new(0),
+            data: if max_size == 0 {
+                Elements::Growable(RwLock::new(data))
+            } else {
+                Elements::Fixed(data)
+            },
+        }
+    }
+
+    pub(crate) fn from_vector(vector: &ConstantVector) -> SharedArray {
+        let (ty, data): (DataType, Vec<u32>) = match *vector {
+            ConstantVector::Bool(ref x) => (DataType::Bool, x.iter().map(|&v| v as u32).collect()),
+            ConstantVector::I32(ref x) => (DataType::I32, x.iter().map(|&v| v as u32).collect()),
+            ConstantVector::U32(ref x) => (DataType::U32, x.clone()),
+            ConstantVector::F32(ref x) => (DataType::F32, x.iter().map(|v| v.to_bits()).collect()),
+        };
+        SharedArray {
+            ty,
+            len: AtomicU32::new(data.len() as u32),
+            data: Elements::Fixed(data.into_iter().map(AtomicU32::new).collect()),
+        }
+    }
+
+    pub(crate) fn to_vector(&self) -> ConstantVector {
+        vector_from_bits(self.ty, &self.bits())
+    }
+
+    pub(crate) fn bits(&self) -> Vec<u32> {
+        let bits = |data: &[AtomicU32]| {
+            data[..self.len() as usize]
+                .iter()
+                .map(|x| x.load(Ordering::Relaxed))
+                .collect()
+        };
+        match self.data {
+            Elements::Fixed(ref data) => bits(data),
+            Elements::Growable(ref data) => bits(&data.read().unwrap()),
+        }
+    }
+
+    pub(crate) fn len(&self) -> u32 {
+        self.len.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn capacity(&self) -> u32 {
+        match self.data {
+            Elements::Fixed(ref data) => data.len() as u32,
+            Elements::Growable(ref data) => data.read().unwrap().len() as u32,
+        }
+    }
+
+    pub(crate) fn resize(&self, len: u32) -> Result<(), u32> {
+        match self.data {
+            Elements::Fixed(ref data) if len as usize > data.len() => {
+                return Err(data.len() as u32)
+            }
+            Elements::Fixed(_) => {}
+            Elements::Growable(ref data) => {
+                if len as usize > data.read().unwrap().len() {
+                    let mut data = data.write().unwrap();
+                    while data.len() < len as usize {
+                        data.push(AtomicU32::new(0));
+                    }
+                }
+            }
+        }
+        self.len.store(len, Ordering::Relaxed);
+        Ok(())
+    }
+
+    pub(crate) fn load(&self, index: u32) -> u32 {
+        match self.data {
+            Elements::Fixed(ref data) => data[index as usize].load(Ordering::Relaxed),
+            Elements::Growable(ref data) => {
+                data.read().unwrap()[index as usize].load(Ordering::Relaxed)
+            }
+        }
+    }
+
+    pub(crate) fn store(&self, index: u32, value: u32) {
+        match self.data {
+            Elements::Fixed(ref data) => data[index as usize].store(value, Ordering::Relaxed),
+            Elements::Growable(ref data) => {
+                data.read().unwrap()[index as usize].store(value, Ordering::Relaxed)
+            }
+        }
+    }
+}
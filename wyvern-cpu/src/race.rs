@@ -0,0 +1,143 @@
+// Copyright 2018 | Dario Ostuni <dario.ostuni@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use memory::SharedMemory;
+use std::collections::HashSet;
+use wcore::program::TokenId;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Access {
+    pub worker: u32,
+    pub op: u32,
+    pub write: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Race {
+    pub array: TokenId,
+    pub index: u32,
+    pub first: Access,
+    pub second: Access,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Stamp {
+    access: Access,
+    group: u32,
+    epoch: u32,
+}
+
+#[derive(Debug, Clone, Default)]
+struct Cell {
+    write: Option<Stamp>,
+    reads: Vec<Stamp>,
+}
+
+#[derive(Debug)]
+pub(crate) struct RaceDetector {
+    arrays: Vec<TokenId>,
+    shadow: Vec<Vec<Cell>>,
+    seen: HashSet<(u32, u32, bool, u32, bool)>,
+    pub(crate) races: Vec<Race>,
+}
+
+impl Stamp {
+    fn conflicts(&self, other: &Stamp) -> bool {
+        self.access.worker != other.access.worker
+            && (self.group != other.group || self.epoch == other.epoch)
+    }
+
+    fn precedes(&self, other: &Stamp) -> bool {
+        self.access.worker == other.access.worker
+            || (self.group == other.group && self.epoch < other.epoch)
+    }
+}
+
+impl RaceDetector {
+    pub(crate) fn new(shared: &SharedMemory, arrays: Vec<TokenId>) -> RaceDetector {
+        RaceDetector {
+            arrays,
+            shadow: shared
+                .arrays
+                .iter()
+                .map(|x| vec![Cell::default(); x.capacity() as usize])
+                .collect(),
+            seen: HashSet::new(),
+            races: Vec::new(),
+        }
+    }
+
+    pub(crate) fn record(
+        &mut self,
+        array: u32,
+        index: u32,
+        access: Access,
+        group: u32,
+        epoch: u32,
+    ) {
+        let stamp = Stamp {
+            access,
+            group,
+            epoch,
+        };
+        let mut conflict = None;
+        {
+            let cells = &mut self.shadow[array as usize];
+            if cells.len() <= index as usize {
+                cells.resize(index as usize + 1, Cell::default());
+            }
+            let cell = &mut cells[index as usize];
+            if let Some(write) = cell.write {
+                if write.conflicts(&stamp) {
+                    conflict = Some(write.access);
+                }
+            }
+            if access.write {
+                if conflict.is_none() {
+                    conflict = cell
+                        .reads
+                        .iter()
+                        .find(|x| x.conflicts(&stamp))
+                        .map(|x| x.access);
+                }
+                cell.write = Some(stamp);
+            } else {
+                cell.reads.retain(|x| !x.precedes(&stamp));
+                if cell.reads.len() < 2 {
+                    cell.reads.push(stamp);
+                }
+            }
+        }
+        if let Some(first) = conflict {
+            self.report(array, index, first, access);
+        }
+    }
+
+    fn report(&mut self, array: u32, index: u32, first: Access, second: Access) {
+        let (a, b) = (
+            (first.op, first.write).min((second.op, second.write)),
+            (first.op, first.write).max((second.op, second.write)),
+        );
+        let key = (array, a.0, a.1, b.0, b.1);
+        if !self.seen.insert(key) {
+            return;
+        }
+        self.races.push(Race {
+            array: self.arrays[array as usize],
+            index,
+            first,
+            second,
+        });
+    }
+}
@@ -14,8 +14,10 @@
 
 use std::hash::{Hash, Hasher};
 use std::sync::Mutex;
-use wcore::executor::Resource;
+use wcore::error::Error;
+use wcore::executor::{access_error, Resource};
 use wcore::program::{get_token_type, TokenType, TokenValue};
+use wcore::types::Type;
 
 #[derive(Debug)]
 pub struct CpuResource {
@@ -36,8 +38,43 @@ impl Resource for CpuResource {
         self.data.lock().unwrap().clone()
     }
 
-    fn set_data(&self, value: TokenValue) {
+    fn set_data(&self, value: TokenValue) -> Result<(), Error> {
         *self.data.lock().unwrap() = value;
+        Ok(())
+    }
+
+    fn map<T: Type, R, F: FnOnce(&[T]) -> R>(&self, f: F) -> Result<R, Error> {
+        let data = self.data.lock().unwrap();
+        match T::slice(&data) {
+            Some(x) => Ok(f(x)),
+            None => Err(access_error::<T>(get_token_type(&data))),
+        }
+    }
+
+    fn map_mut<T: Type, R, F: FnOnce(&mut [T]) -> R>(&self, f: F) -> Result<R, Error> {
+        let mut data = self.data.lock().unwrap();
+        match T::slice_mut(&mut data) {
+            Some(x) => Ok(f(x)),
+            None => Err(access_error::<T>(get_token_type(&data))),
+        }
+    }
+
+    fn resize<T: Type>(&self, len: usize) -> Result<(), Error> {
+        let mut data = self.data.lock().unwrap();
+        match T::vec_mut(&mut data) {
+            Some(x) => x.resize(len, T::default()),
+            None => *data = TokenValue::Vector(T::vector(vec![T::default(); len])),
+        }
+        Ok(())
+    }
+
+    fn reserve<T: Type>(&self, capacity: usize) -> Result<(), Error> {
+        let mut data = self.data.lock().unwrap();
+        match T::vec_mut(&mut data) {
+            Some(x) => x.reserve(capacity.saturating_sub(x.len())),
+            None => *data = TokenValue::Vector(T::vector(Vec::with_capacity(capacity))),
+        }
+        Ok(())
     }
 }
 
@@ -0,0 +1,139 @@
+// Copyright 2018 | Dario Ostuni <dario.ostuni@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use rand::prng::XorShiftRng;
+use rand::{Rng, SeedableRng};
+use std::str::FromStr;
+
+pub(crate) const MAX_SCHEDULES: usize = 4096;
+pub(crate) const MAX_EXHAUSTIVE_WORKERS: u32 = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Schedule {
+    RoundRobin,
+    Reverse,
+    Random(u64),
+    Exhaustive,
+}
+
+impl FromStr for Schedule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Schedule, String> {
+        if let Some(seed) = s.strip_prefix("random:") {
+            return seed
+                .parse()
+                .map(Schedule::Random)
+                .map_err(|x| format!("{:?}", x));
+        }
+        match s {
+            "round-robin" => Ok(Schedule::RoundRobin),
+            "reverse" => Ok(Schedule::Reverse),
+            "exhaustive" => Ok(Schedule::Exhaustive),
+            _ => Err(format!("Unknown schedule {}", s)),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct Scheduler {
+    schedule: Schedule,
+    rng: XorShiftRng,
+    last: Option<u32>,
+    choices: Vec<(usize, usize)>,
+    depth: usize,
+    run: u32,
+    runs: u32,
+}
+
+impl Scheduler {
+    pub(crate) fn new(schedule: Schedule, runs: u32) -> Scheduler {
+        let seed = match schedule {
+            Schedule::Random(seed) => seed,
+            _ => 0,
+        };
+        Scheduler {
+            schedule,
+            rng: XorShiftRng::seed_from_u64(seed),
+            last: None,
+            choices: Vec::new(),
+            depth: 0,
+            run: 0,
+            runs,
+        }
+    }
+
+    pub(crate) fn choose(&mut self, runnable: &[u32]) -> u32 {
+        let choice = match self.schedule {
+            Schedule::RoundRobin => match self.last {
+                Some(last) => runnable.iter().position(|&x| x > last).unwrap_or(0),
+                None => self.run as usize % runnable.len(),
+            },
+            Schedule::Reverse => runnable.len() - 1 - self.run as usize % runnable.len(),
+            Schedule::Random(_) => self.rng.gen_range(0, runnable.len()),
+            Schedule::Exhaustive if runnable.len() == 1 => 0,
+            Schedule::Exhaustive => {
+                if self.depth == self.choices.len() {
+                    self.choices.push((0, runnable.len()));
+                }
+                self.depth += 1;
+                self.choices[self.depth - 1].0
+            }
+        };
+        self.last = Some(runnable[choice]);
+        runnable[choice]
+    }
+
+    pub(crate) fn next(&mut self) -> bool {
+        self.last = None;
+        self.depth = 0;
+        if self.schedule != Schedule::Exhaustive {
+            self.run += 1;
+            if let Schedule::Random(seed) = self.current() {
+                self.rng = XorShiftRng::seed_from_u64(seed);
+            }
+            return self.run < self.runs;
+        }
+        while let Some(&(choice, options)) = self.choices.last() {
+            if choice + 1 < options {
+                self.choices.last_mut().unwrap().0 += 1;
+                return true;
+            }
+            self.choices.pop();
+        }
+        false
+    }
+
+    pub(crate) fn describe(&self) -> String {
+        match self.schedule {
+            Schedule::Exhaustive => format!(
+                "Exhaustive schedule with choices {:?}",
+                self.choices[..self.depth]
+                    .iter()
+                    .map(|x| x.0)
+                    .collect::<Vec<_>>()
+            ),
+            Schedule::Random(_) => format!("{:?} schedule", self.current()),
+            schedule if self.run == 0 => format!("{:?} schedule", schedule),
+            schedule => format!("{:?} schedule rotated by {}", schedule, self.run),
+        }
+    }
+
+    fn current(&self) -> Schedule {
+        match self.schedule {
+            Schedule::Random(seed) => Schedule::Random(seed.wrapping_add(u64::from(self.run))),
+            schedule => schedule,
+        }
+    }
+}
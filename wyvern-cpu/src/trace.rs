@@ -0,0 +1,105 @@
+// Copyright 2018 | Dario Ostuni <dario.ostuni@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use bytecode::{Bytecode, Instr, Reg};
+use memory::{from_bits, SharedMemory};
+use std::sync::atomic::AtomicBool;
+use wcore::program::{ConstantScalar, DataType, Program, TokenId, TokenType};
+use worker::{Event, Fault, Worker};
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TraceFilter {
+    pub workers: Option<Vec<u32>>,
+    pub tokens: Option<Vec<TokenId>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TraceEntry {
+    pub iteration: u32,
+    pub worker: u32,
+    pub op: u32,
+    pub operands: Vec<(TokenId, ConstantScalar)>,
+    pub result: Option<(TokenId, ConstantScalar)>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Trace {
+    pub entries: Vec<TraceEntry>,
+}
+
+impl TraceFilter {
+    pub(crate) fn accepts_worker(&self, worker: u32) -> bool {
+        self.workers.as_ref().is_none_or(|x| x.contains(&worker))
+    }
+
+    fn accepts(&self, entry: &TraceEntry) -> bool {
+        self.tokens.as_ref().is_none_or(|x| {
+            entry
+                .operands
+                .iter()
+                .chain(entry.result.iter())
+                .any(|&(id, _)| x.contains(&id))
+        })
+    }
+}
+
+pub(crate) fn value(
+    program: &Program,
+    bytecode: &Bytecode,
+    worker: &Worker,
+    reg: Reg,
+) -> (TokenId, ConstantScalar) {
+    let id = bytecode.registers[reg as usize];
+    let ty = match program.symbol.get(&id) {
+        Some(&TokenType::Constant(ty)) | Some(&TokenType::Variable(ty)) => ty,
+        _ => DataType::U32,
+    };
+    (id, from_bits(ty, worker.register(reg)))
+}
+
+pub(crate) fn run(
+    program: &Program,
+    bytecode: &Bytecode,
+    worker: &mut Worker,
+    shared: &SharedMemory,
+    abort: &AtomicBool,
+    filter: &TraceFilter,
+    entries: &mut Vec<TraceEntry>,
+) -> Result<Event, Fault> {
+    while let Some(&instr) = bytecode.code.get(worker.pc()) {
+        let op = bytecode.origin[worker.pc()];
+        let (result, operands) = instr.registers();
+        let operands = operands
+            .into_iter()
+            .map(|x| value(program, bytecode, worker, x))
+            .collect();
+        let event = worker.step(bytecode, shared, abort)?;
+        if !matches!(instr, Instr::Label(_)) {
+            let entry = TraceEntry {
+                iteration: 0,
+                worker: worker.worker_id(),
+                op,
+                operands,
+                result: result.map(|x| value(program, bytecode, worker, x)),
+            };
+            if filter.accepts(&entry) {
+                entries.push(entry);
+            }
+        }
+        if let Some(event) = event {
+            return Ok(event);
+        }
+    }
+    Ok(Event::Done)
+}
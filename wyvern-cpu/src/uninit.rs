@@ -0,0 +1,172 @@
+// Copyright 2018 | Dario Ostuni <dario.ostuni@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use bytecode::{Bytecode, Instr, SharedSlot};
+use memory::SharedMemory;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::RwLock;
+use wcore::program::{Program, TokenType};
+use worker::{Event, Fault, Worker};
+
+#[derive(Debug, Clone)]
+pub(crate) struct Shadow {
+    registers: Vec<bool>,
+    arrays: Vec<Option<Vec<bool>>>,
+}
+
+#[derive(Debug)]
+pub(crate) struct SharedShadow {
+    variables: Vec<bool>,
+    arrays: Vec<RwLock<Vec<AtomicBool>>>,
+}
+
+impl Shadow {
+    fn new(bytecode: &Bytecode) -> Shadow {
+        Shadow {
+            registers: vec![false; bytecode.registers.len()],
+            arrays: vec![None; bytecode.private_arrays.len()],
+        }
+    }
+}
+
+impl SharedShadow {
+    pub(crate) fn new(
+        program: &Program,
+        bytecode: &Bytecode,
+        shared: &SharedMemory,
+    ) -> SharedShadow {
+        let mut arrays: Vec<Vec<AtomicBool>> = shared
+            .arrays
+            .iter()
+            .map(|x| (0..x.capacity()).map(|_| AtomicBool::new(false)).collect())
+            .collect();
+        for &(_, _, slot) in &bytecode.io {
+            if let SharedSlot::Array(x) = slot {
+                for element in &mut arrays[x as usize] {
+                    *element = AtomicBool::new(true);
+                }
+            }
+        }
+        SharedShadow {
+            variables: bytecode
+                .registers
+                .iter()
+                .map(|x| matches!(program.symbol.get(x), Some(&TokenType::Variable(_))))
+                .collect(),
+            arrays: arrays.into_iter().map(RwLock::new).collect(),
+        }
+    }
+}
+
+pub(crate) fn run(
+    bytecode: &Bytecode,
+    worker: &mut Worker,
+    shared: &SharedMemory,
+    abort: &AtomicBool,
+    initialized: &SharedShadow,
+) -> Result<Event, Fault> {
+    let mut shadow = worker
+        .shadow
+        .take()
+        .unwrap_or_else(|| Shadow::new(bytecode));
+    let result = execute(bytecode, worker, &mut shadow, shared, abort, initialized);
+    worker.shadow = Some(shadow);
+    result
+}
+
+fn execute(
+    bytecode: &Bytecode,
+    worker: &mut Worker,
+    shadow: &mut Shadow,
+    shared: &SharedMemory,
+    abort: &AtomicBool,
+    initialized: &SharedShadow,
+) -> Result<Event, Fault> {
+    while let Some(&instr) = bytecode.code.get(worker.pc()) {
+        let uninitialized = check(instr, worker, shadow, shared, initialized);
+        let event = worker.step(bytecode, shared, abort)?;
+        if let Some(index) = uninitialized {
+            return Err(Fault::Uninitialized(index));
+        }
+        if let Some(event) = event {
+            return Ok(event);
+        }
+    }
+    Ok(Event::Done)
+}
+
+fn check(
+    instr: Instr,
+    worker: &Worker,
+    shadow: &mut Shadow,
+    shared: &SharedMemory,
+    initialized: &SharedShadow,
+) -> Option<Option<u32>> {
+    match instr {
+        Instr::Copy(r, a) => {
+            if initialized.variables[r as usize] {
+                shadow.registers[r as usize] = true;
+            }
+            if initialized.variables[a as usize] && !shadow.registers[a as usize] {
+                return Some(None);
+            }
+        }
+        Instr::PrivateArrayNew(v, s) => {
+            let array = &mut shadow.arrays[v as usize];
+            if array.is_none() {
+                *array = Some(vec![false; worker.register(s) as usize]);
+            }
+        }
+        Instr::PrivateArrayLoad(_, v, i) => {
+            let index = worker.register(i);
+            let array = shadow.arrays[v as usize].as_ref();
+            if array.and_then(|x| x.get(index as usize)) == Some(&false) {
+                return Some(Some(index));
+            }
+        }
+        Instr::PrivateArrayStore(v, i, _) => {
+            let index = worker.register(i);
+            let array = shadow.arrays[v as usize].as_mut();
+            if let Some(element) = array.and_then(|x| x.get_mut(index as usize)) {
+                *element = true;
+            }
+        }
+        Instr::SharedArrayLoad(_, v, i) => {
+            let index = worker.register(i);
+            let array = initialized.arrays[v as usize].read().unwrap();
+            if !array
+                .get(index as usize)
+                .is_some_and(|x| x.load(Ordering::Relaxed))
+            {
+                return Some(Some(index));
+            }
+        }
+        Instr::SharedArrayStore(v, i, _) => {
+            let index = worker.register(i) as usize;
+            if index >= shared.arrays[v as usize].len() as usize {
+                return None;
+            }
+            let array = &initialized.arrays[v as usize];
+            if index >= array.read().unwrap().len() {
+                let mut array = array.write().unwrap();
+                while array.len() <= index {
+                    array.push(AtomicBool::new(false));
+                }
+            }
+            array.read().unwrap()[index].store(true, Ordering::Relaxed);
+        }
+        _ => {}
+    }
+    None
+}
@@ -0,0 +1,293 @@
+// Copyright 2018 | Dario Ostuni <dario.ostuni@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use bytecode::{Bytecode, Instr, Reg};
+use executor::CpuConfig;
+use memory::SharedMemory;
+use std::sync::atomic::{fence, AtomicBool, Ordering};
+use uninit::Shadow;
+use wcore::program::LabelId;
+
+const ACCESS_LOG_SIZE: usize = 1 << 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Event {
+    Barrier,
+    Flush,
+    Done,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Fault {
+    OutOfBounds(u32, u32),
+    TooLarge(u32, u32),
+    Budget(u64),
+    Aborted,
+    Uninitialized(Option<u32>),
+    Error(String),
+}
+
+#[derive(Debug)]
+pub(crate) struct Worker {
+    pc: usize,
+    registers: Vec<u32>,
+    counts: Vec<u64>,
+    accesses: Option<Vec<(u32, u32, bool, usize)>>,
+    arrays: Vec<Option<Vec<u32>>>,
+    labels: (LabelId, LabelId),
+    iterations: u64,
+    budget: u64,
+    pub(crate) shadow: Option<Shadow>,
+    worker_id: u32,
+    num_workers: u32,
+}
+
+impl Worker {
+    pub(crate) fn new(bytecode: &Bytecode, num_workers: u32, config: &CpuConfig) -> Worker {
+        Worker {
+            pc: 0,
+            registers: vec![0; bytecode.registers.len()],
+            counts: if config.profile {
+                vec![0; bytecode.code.len()]
+            } else {
+                Vec::new()
+            },
+            accesses: None,
+            arrays: vec![None; bytecode.private_arrays.len()],
+            labels: (LabelId::default(), LabelId::default()),
+            iterations: 0,
+            budget: config.max_iterations.unwrap_or(u64::MAX),
+            shadow: None,
+            worker_id: 0,
+            num_workers,
+        }
+    }
+
+    pub(crate) fn reset(&mut self, worker_id: u32) {
+        self.pc = 0;
+        for x in &mut self.registers {
+            *x = 0;
+        }
+        for x in &mut self.arrays {
+            *x = None;
+        }
+        self.labels = (LabelId::default(), LabelId::default());
+        self.iterations = 0;
+        self.shadow = None;
+        self.worker_id = worker_id;
+    }
+
+    pub(crate) fn current(&self) -> usize {
+        self.pc.saturating_sub(1)
+    }
+
+    pub(crate) fn pc(&self) -> usize {
+        self.pc
+    }
+
+    pub(crate) fn worker_id(&self) -> u32 {
+        self.worker_id
+    }
+
+    pub(crate) fn counts(&self) -> &[u64] {
+        &self.counts
+    }
+
+    pub(crate) fn log_accesses(&mut self) {
+        self.accesses = Some(Vec::new());
+    }
+
+    pub(crate) fn take_accesses(&mut self) -> Vec<(u32, u32, bool, usize)> {
+        self.accesses
+            .as_mut()
+            .map(|x| x.split_off(0))
+            .unwrap_or_default()
+    }
+
+    pub(crate) fn register(&self, reg: Reg) -> u32 {
+        self.registers[reg as usize]
+    }
+
+    pub(crate) fn private_array(&self, slot: u32) -> Option<&Vec<u32>> {
+        self.arrays[slot as usize].as_ref()
+    }
+
+    pub(crate) fn run(
+        &mut self,
+        bytecode: &Bytecode,
+        shared: &SharedMemory,
+        abort: &AtomicBool,
+    ) -> Result<Event, Fault> {
+        let result = if self.counts.is_empty() {
+            self.execute(bytecode, shared, abort, false, false)
+        } else {
+            self.execute(bytecode, shared, abort, false, true)
+        };
+        result.map(|x| x.unwrap_or(Event::Done))
+    }
+
+    pub(crate) fn step(
+        &mut self,
+        bytecode: &Bytecode,
+        shared: &SharedMemory,
+        abort: &AtomicBool,
+    ) -> Result<Option<Event>, Fault> {
+        let profile = !self.counts.is_empty();
+        self.execute(bytecode, shared, abort, true, profile)
+    }
+
+    #[inline(always)]
+    fn execute(
+        &mut self,
+        bytecode: &Bytecode,
+        shared: &SharedMemory,
+        abort: &AtomicBool,
+        single: bool,
+        profile: bool,
+    ) -> Result<Option<Event>, Fault> {
+        let code = &bytecode.code;
+        let regs = &mut self.registers;
+        while let Some(&instr) = code.get(self.pc) {
+            if profile {
+                self.counts[self.pc] += 1;
+            }
+            self.pc += 1;
+            match instr {
+                Instr::Const(r, a) => regs[r as usize] = a,
+                Instr::Copy(r, a) => regs[r as usize] = regs[a as usize],
+                Instr::LoadShared(r, a) => {
+                    regs[r as usize] = shared.scalars[a as usize].load(Ordering::Relaxed)
+                }
+                Instr::StoreShared(r, a) => {
+                    shared.scalars[r as usize].store(regs[a as usize], Ordering::Relaxed)
+                }
+                Instr::WorkerId(r) => regs[r as usize] = self.worker_id,
+                Instr::NumWorkers(r) => regs[r as usize] = self.num_workers,
+                Instr::Unary(op, r, a) => regs[r as usize] = op.eval(regs[a as usize]),
+                Instr::Binary(op, r, a, b) => {
+                    regs[r as usize] = op.eval(regs[a as usize], regs[b as usize])
+                }
+                Instr::SharedArrayNew(v, s) => {
+                    let size = regs[s as usize];
+                    shared.arrays[v as usize]
+                        .resize(size)
+                        .map_err(|capacity| Fault::TooLarge(size, capacity))?;
+                }
+                Instr::SharedArrayLen(r, v) => regs[r as usize] = shared.arrays[v as usize].len(),
+                Instr::SharedArrayLoad(r, v, i) => {
+                    let array = &shared.arrays[v as usize];
+                    let index = check_index(regs[i as usize], array.len())?;
+                    regs[r as usize] = array.load(index);
+                    if let Some(ref mut accesses) = self.accesses {
+                        accesses.push((v, index, false, self.pc - 1));
+                        if accesses.len() >= ACCESS_LOG_SIZE {
+                            return Ok(Some(Event::Flush));
+                        }
+                    }
+                }
+                Instr::SharedArrayStore(v, i, a) => {
+                    let array = &shared.arrays[v as usize];
+                    let index = check_index(regs[i as usize], array.len())?;
+                    array.store(index, regs[a as usize]);
+                    if let Some(ref mut accesses) = self.accesses {
+                        accesses.push((v, index, true, self.pc - 1));
+                        if accesses.len() >= ACCESS_LOG_SIZE {
+                            return Ok(Some(Event::Flush));
+                        }
+                    }
+                }
+                Instr::PrivateArrayNew(v, s) => {
+                    let size = regs[s as usize];
+                    let max_size = bytecode.private_arrays[v as usize];
+                    if max_size > 0 && size > max_size {
+                        return Err(Fault::TooLarge(size, max_size));
+                    }
+                    let array = &mut self.arrays[v as usize];
+                    if array.is_none() {
+                        *array = Some(vec![0; size as usize]);
+                    }
+                }
+                Instr::PrivateArrayLen(r, v) => {
+                    regs[r as usize] = private_array(&mut self.arrays, v)?.len() as u32
+                }
+                Instr::PrivateArrayLoad(r, v, i) => {
+                    let array = private_array(&mut self.arrays, v)?;
+                    let index = check_index(regs[i as usize], array.len() as u32)?;
+                    regs[r as usize] = array[index as usize];
+                }
+                Instr::PrivateArrayStore(v, i, a) => {
+                    let array = private_array(&mut self.arrays, v)?;
+                    let index = check_index(regs[i as usize], array.len() as u32)?;
+                    array[index as usize] = regs[a as usize];
+                }
+                Instr::Jump(target) => {
+                    if (target as usize) < self.pc {
+                        if abort.load(Ordering::Relaxed) {
+                            return Err(Fault::Aborted);
+                        }
+                        self.iterations += 1;
+                        if self.iterations > self.budget {
+                            return Err(Fault::Budget(self.budget));
+                        }
+                    }
+                    self.pc = target as usize;
+                }
+                Instr::JumpUnless(c, target) => {
+                    if regs[c as usize] == 0 {
+                        self.pc = target as usize;
+                    }
+                }
+                Instr::Label(l) => {
+                    self.labels.0 = self.labels.1;
+                    self.labels.1 = l;
+                }
+                Instr::Phi(r, a0, l0, a1, l1) => {
+                    regs[r as usize] = if self.labels.0 == l0 {
+                        regs[a0 as usize]
+                    } else if self.labels.0 == l1 {
+                        regs[a1 as usize]
+                    } else {
+                        return Err(Fault::Error(format!(
+                            "Phi reached from unknown {:?}",
+                            self.labels.0
+                        )));
+                    };
+                }
+                Instr::MemoryBarrier => fence(Ordering::SeqCst),
+                Instr::ControlBarrier => {
+                    fence(Ordering::SeqCst);
+                    return Ok(Some(Event::Barrier));
+                }
+            }
+            if single {
+                return Ok(None);
+            }
+        }
+        Ok(Some(Event::Done))
+    }
+}
+
+fn private_array(arrays: &mut [Option<Vec<u32>>], v: u32) -> Result<&mut Vec<u32>, Fault> {
+    arrays[v as usize]
+        .as_mut()
+        .ok_or_else(|| Fault::Error(format!("Private array {} used before allocation", v)))
+}
+
+fn check_index(index: u32, len: u32) -> Result<u32, Fault> {
+    if index < len {
+        Ok(index)
+    } else {
+        Err(Fault::OutOfBounds(index, len))
+    }
+}
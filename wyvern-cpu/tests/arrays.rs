@@ -0,0 +1,49 @@
+// Copyright 2018 | Dario Ostuni <dario.ostuni@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+extern crate wyvern_core as wcore;
+extern crate wyvern_cpu;
+
+use wcore::builder::ProgramBuilder;
+use wcore::executor::{Executable, Executor, Resource, WorkSize, IO};
+use wcore::types::{Array, Constant};
+use wyvern_cpu::executor::{CpuConfig, CpuExecutor, CpuMode};
+
+#[test]
+fn shared_array_without_max_size_grows() {
+    let b = ProgramBuilder::new();
+    let zero = Constant::new(0_u32, &b);
+    let out = Array::<u32>::new(zero, 0, true, &b).mark_as_inout("out");
+    let id = b.worker_id();
+    let shared = Array::<u32>::new(b.num_workers() * Constant::new(2_u32, &b), 0, true, &b);
+    shared.at(id).store(id * Constant::new(3_u32, &b));
+    out.at(id).store(shared.at(id).load() + shared.len());
+    let program = b.finalize().unwrap();
+    let configs = vec![
+        CpuConfig::default(),
+        CpuConfig::default().mode(CpuMode::Parallel).threads(4),
+        CpuConfig::default().detect_races(true),
+        CpuConfig::default().detect_uninit(true),
+    ];
+    for config in configs {
+        let executor = CpuExecutor::new(config).unwrap();
+        let mut executable = executor.compile(program.clone()).unwrap();
+        executable.set_work_size(Some(WorkSize::new(4, 5)));
+        let out = executor.new_resource().unwrap();
+        out.write_slice(&[0_u32; 20]).unwrap();
+        executable.bind("out", IO::InOut, out.clone()).unwrap();
+        executable.run().unwrap();
+        let expected: Vec<u32> = (0..20).map(|i| i * 3 + 40).collect();
+        assert_eq!(out.map(|x: &[u32]| x.to_vec()).unwrap(), expected, "{:?}", config);
+    }
+}
@@ -0,0 +1,92 @@
+// Copyright 2018 | Dario Ostuni <dario.ostuni@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+extern crate wyvern_core as wcore;
+extern crate wyvern_cpu;
+
+use std::time::Duration;
+use wcore::builder::ProgramBuilder;
+use wcore::error::Error;
+use wcore::executor::{Executable, Executor, Resource, WorkSize, IO};
+use wcore::program::{Op, Program};
+use wcore::types::{Array, Constant};
+use wyvern_cpu::executor::{CpuConfig, CpuExecutor, CpuMode};
+
+fn spin() -> Program {
+    let b = ProgramBuilder::new();
+    let zero = Constant::new(0_u32, &b);
+    let data = Array::<u32>::new(zero, 0, true, &b).mark_as_inout("data");
+    let id = b.worker_id();
+    b.while_loop(
+        |_| data.at(id).load().lt(Constant::new(10_u32, &b)),
+        |_| {
+            data.at(id)
+                .store(data.at(id).load() * Constant::new(2_u32, &b))
+        },
+    );
+    b.finalize().unwrap()
+}
+
+fn run(config: CpuConfig, initial: u32) -> Result<Vec<u32>, Error> {
+    let executor = CpuExecutor::new(config)?;
+    let mut executable = executor.compile(spin())?;
+    executable.set_work_size(Some(WorkSize::new(2, 2)));
+    let data = executor.new_resource()?;
+    data.write_slice(&[initial; 4])?;
+    executable.bind("data", IO::InOut, data.clone())?;
+    executable.run()?;
+    data.map(|x: &[u32]| x.to_vec())
+}
+
+#[test]
+fn loop_budget_points_at_the_loop() {
+    let op = spin()
+        .operation
+        .iter()
+        .position(|x| matches!(*x, Op::While(..)))
+        .unwrap();
+    for &mode in &[CpuMode::Sequential, CpuMode::Parallel] {
+        let config = CpuConfig::default().mode(mode).max_iterations(Some(100));
+        assert_eq!(run(config, 1).unwrap(), vec![16; 4]);
+        match run(config, 0) {
+            Err(Error::Execution(message)) => assert!(
+                message.starts_with(&format!(
+                    "Loop budget of 100 iterations exceeded at op #{} (While on ",
+                    op
+                )),
+                "{}",
+                message
+            ),
+            x => panic!("Expected the loop budget to be exceeded, got {:?}", x),
+        }
+    }
+}
+
+#[test]
+fn timeout_aborts_runaway_loops() {
+    for &mode in &[CpuMode::Sequential, CpuMode::Parallel] {
+        let timeout = Duration::from_millis(50);
+        let config = CpuConfig::default().mode(mode).timeout(Some(timeout));
+        assert_eq!(run(config, 3).unwrap(), vec![12; 4]);
+        match run(config, 0) {
+            Err(Error::Execution(message)) => {
+                assert!(
+                    message.starts_with("Timeout of 50ms exceeded"),
+                    "{}",
+                    message
+                )
+            }
+            x => panic!("Expected the timeout to be exceeded, got {:?}", x),
+        }
+    }
+}
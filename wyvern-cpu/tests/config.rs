@@ -0,0 +1,53 @@
+// Copyright 2018 | Dario Ostuni <dario.ostuni@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+extern crate wyvern_core as wcore;
+extern crate wyvern_cpu;
+
+use std::env;
+use std::time::Duration;
+use wcore::error::Error;
+use wcore::executor::Executor;
+use wyvern_cpu::executor::{CpuConfig, CpuExecutor, CpuMode};
+use wyvern_cpu::schedule::Schedule;
+
+#[test]
+fn environment_overrides_are_opt_in() {
+    env::set_var("WYVERN_CPU_MODE", "scheduled:random:3");
+    env::set_var("WYVERN_CPU_THREADS", "0");
+    env::set_var("WYVERN_CPU_PROFILE", "yes");
+    env::set_var("WYVERN_CPU_TIMEOUT_MS", "250");
+    let config = CpuConfig::from_env().unwrap();
+    assert_eq!(config.mode, CpuMode::Scheduled(Schedule::Random(3)));
+    assert_eq!(config.threads, 0);
+    assert!(config.profile);
+    assert_eq!(config.timeout, Some(Duration::from_millis(250)));
+    assert!(!config.detect_races);
+    match CpuExecutor::new(config) {
+        Err(Error::Validation(_)) => {}
+        x => panic!("Expected a validation error, got {:?}", x),
+    }
+    let explicit = CpuConfig::default().threads(2);
+    assert_eq!(explicit.mode, CpuMode::Sequential);
+    assert!(CpuExecutor::new(explicit).is_ok());
+    let config = CpuConfig::default().env_overrides().unwrap().threads(2);
+    assert_eq!(config.threads, 2);
+    assert!(CpuExecutor::new(config).is_ok());
+    env::set_var("WYVERN_CPU_PROFILE", "maybe");
+    match CpuConfig::from_env() {
+        Err(Error::Validation(message)) => {
+            assert_eq!(message, "Invalid value \"maybe\" for WYVERN_CPU_PROFILE")
+        }
+        x => panic!("Expected a validation error, got {:?}", x),
+    }
+}
@@ -0,0 +1,118 @@
+// Copyright 2018 | Dario Ostuni <dario.ostuni@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+extern crate wyvern_core as wcore;
+extern crate wyvern_cpu;
+
+use wcore::builder::ProgramBuilder;
+use wcore::error::Error;
+use wcore::executor::{Executable, Executor, Resource, WorkSize, IO};
+use wcore::program::{Op, Program};
+use wcore::types::{Array, Constant};
+use wyvern_cpu::executable::CpuExecutable;
+use wyvern_cpu::executor::{CpuConfig, CpuExecutor, CpuMode};
+
+fn executables(program: &Program, work_size: WorkSize) -> Vec<(CpuExecutor, CpuExecutable)> {
+    [CpuMode::Sequential, CpuMode::Parallel]
+        .iter()
+        .map(|&mode| {
+            let config = CpuConfig::default()
+                .mode(mode)
+                .threads(4)
+                .detect_races(true);
+            let executor = CpuExecutor::new(config).unwrap();
+            let mut executable = executor.compile(program.clone()).unwrap();
+            executable.set_work_size(Some(work_size));
+            let data = executor.new_resource().unwrap();
+            data.write_slice(&[0_u32; 8]).unwrap();
+            executable.bind("data", IO::InOut, data).unwrap();
+            (executor, executable)
+        })
+        .collect()
+}
+
+fn neighbour(stride: u32, barrier: bool) -> Program {
+    let b = ProgramBuilder::new();
+    let zero = Constant::new(0_u32, &b);
+    let data = Array::<u32>::new(zero, 0, true, &b).mark_as_inout("data");
+    let out = Array::<u32>::new(zero, 0, true, &b).mark_as_inout("out");
+    let id = b.worker_id();
+    data.at(id).store(id);
+    if barrier {
+        b.barrier();
+    }
+    let other = (id + Constant::new(stride, &b)) % b.num_workers();
+    out.at(id).store(data.at(other).load());
+    b.finalize().unwrap()
+}
+
+fn run(executable: &mut CpuExecutable, executor: &CpuExecutor) -> Result<(), Error> {
+    let out = executor.new_resource().unwrap();
+    out.write_slice(&[0_u32; 8]).unwrap();
+    executable.bind("out", IO::InOut, out).unwrap();
+    executable.run().map(|_| ())
+}
+
+#[test]
+fn concurrent_writes_are_reported() {
+    let b = ProgramBuilder::new();
+    let zero = Constant::new(0_u32, &b);
+    let data = Array::<u32>::new(zero, 0, true, &b).mark_as_inout("data");
+    data.at(zero).store(b.worker_id());
+    let program = b.finalize().unwrap();
+    let store = program
+        .operation
+        .iter()
+        .position(|x| matches!(*x, Op::ArrayStore(..)))
+        .unwrap() as u32;
+    for (_, mut executable) in executables(&program, WorkSize::new(1, 2)) {
+        match executable.run() {
+            Err(Error::Execution(message)) => assert!(message.starts_with("Data race on")),
+            x => panic!("Expected a data race, got {:?}", x),
+        }
+        let race = executable.races()[0];
+        assert_eq!(race.index, 0);
+        assert!(race.first.write && race.second.write);
+        assert_eq!((race.first.op, race.second.op), (store, store));
+        assert_ne!(race.first.worker, race.second.worker);
+    }
+}
+
+#[test]
+fn barrier_orders_accesses_within_a_group() {
+    let program = neighbour(1, true);
+    for (executor, mut executable) in executables(&program, WorkSize::new(1, 8)) {
+        run(&mut executable, &executor).unwrap();
+        assert!(executable.races().is_empty());
+    }
+}
+
+#[test]
+fn missing_barrier_is_reported() {
+    let program = neighbour(1, false);
+    for (executor, mut executable) in executables(&program, WorkSize::new(1, 8)) {
+        assert!(run(&mut executable, &executor).is_err());
+        let race = executable.races()[0];
+        assert!(race.first.write != race.second.write);
+    }
+}
+
+#[test]
+fn barrier_does_not_order_other_groups() {
+    let program = neighbour(4, true);
+    for (executor, mut executable) in executables(&program, WorkSize::new(2, 4)) {
+        assert!(run(&mut executable, &executor).is_err());
+        let race = executable.races()[0];
+        assert!(race.first.worker / 4 != race.second.worker / 4);
+    }
+}
@@ -0,0 +1,59 @@
+// Copyright 2018 | Dario Ostuni <dario.ostuni@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+extern crate wyvern_core as wcore;
+extern crate wyvern_cpu;
+
+use wcore::builder::ProgramBuilder;
+use wcore::executor::{Executable, Executor, Resource, WorkSize, IO};
+use wcore::types::{Array, Constant};
+use wyvern_cpu::executor::{CpuConfig, CpuExecutor, CpuMode};
+
+#[test]
+fn profile_counts_executed_ops() {
+    let b = ProgramBuilder::new();
+    let zero = Constant::new(0_u32, &b);
+    let data = Array::<u32>::new(zero, 0, true, &b).mark_as_inout("data");
+    let id = b.worker_id();
+    b.if_then(
+        |_| id.lt(Constant::new(3_u32, &b)),
+        |_| data.at(id).store(id + Constant::new(1_u32, &b)),
+    );
+    let program = b.finalize().unwrap();
+    for &mode in &[CpuMode::Sequential, CpuMode::Parallel] {
+        for &profile in &[false, true] {
+            let executor =
+                CpuExecutor::new(CpuConfig::default().mode(mode).profile(profile)).unwrap();
+            let mut executable = executor.compile(program.clone()).unwrap();
+            executable.set_work_size(Some(WorkSize::new(4, 2)));
+            let data = executor.new_resource().unwrap();
+            data.write_slice(&[0_u32; 8]).unwrap();
+            executable.bind("data", IO::InOut, data.clone()).unwrap();
+            let report = executable.run().unwrap();
+            assert_eq!(
+                data.map(|x: &[u32]| x.to_vec()).unwrap(),
+                vec![1, 2, 3, 0, 0, 0, 0, 0]
+            );
+            if !profile {
+                assert_eq!(report.op_counts, None);
+                continue;
+            }
+            assert!(report.to_string().contains("\n  ArrayStore: 3"));
+            let counts = report.op_counts.unwrap();
+            assert_eq!(counts["WorkerId"], 8);
+            assert_eq!(counts["If"], 8);
+            assert_eq!(counts["Add"], 3);
+            assert_eq!(counts["ArrayStore"], 3);
+        }
+    }
+}
@@ -0,0 +1,116 @@
+// Copyright 2018 | Dario Ostuni <dario.ostuni@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+extern crate wyvern_core as wcore;
+extern crate wyvern_cpu;
+
+use wcore::builder::ProgramBuilder;
+use wcore::error::Error;
+use wcore::executor::{Executable, Executor, Resource, WorkSize, IO};
+use wcore::program::Program;
+use wcore::types::{Array, Constant};
+use wyvern_cpu::executor::{CpuConfig, CpuExecutor, CpuMode};
+use wyvern_cpu::schedule::Schedule;
+
+fn last_writer() -> Program {
+    let b = ProgramBuilder::new();
+    let zero = Constant::new(0_u32, &b);
+    let data = Array::<u32>::new(zero, 0, true, &b).mark_as_inout("data");
+    data.at(zero).store(b.worker_id());
+    b.finalize().unwrap()
+}
+
+fn run(program: &Program, schedule: Schedule, schedules: u32) -> Result<u32, Error> {
+    let config = CpuConfig::default()
+        .mode(CpuMode::Scheduled(schedule))
+        .schedules(schedules);
+    let executor = CpuExecutor::new(config)?;
+    let mut executable = executor.compile(program.clone())?;
+    executable.set_work_size(Some(WorkSize::new(1, 4)));
+    let data = executor.new_resource()?;
+    data.write_slice(&[0_u32])?;
+    executable.bind("data", IO::InOut, data.clone())?;
+    executable.run()?;
+    data.map(|x: &[u32]| x[0])
+}
+
+#[test]
+fn single_schedules_are_deterministic() {
+    let program = last_writer();
+    assert_eq!(run(&program, Schedule::RoundRobin, 1).unwrap(), 3);
+    assert_eq!(run(&program, Schedule::Reverse, 1).unwrap(), 0);
+    let random = run(&program, Schedule::Random(7), 1).unwrap();
+    for _ in 0..4 {
+        assert_eq!(run(&program, Schedule::Random(7), 1).unwrap(), random);
+    }
+}
+
+#[test]
+fn schedule_counts_explore_more_interleavings() {
+    let program = last_writer();
+    for &schedule in &[Schedule::RoundRobin, Schedule::Reverse, Schedule::Random(7)] {
+        match run(&program, schedule, 16) {
+            Err(Error::Execution(message)) => {
+                assert!(message.starts_with("Output data depends on the schedule under the "))
+            }
+            x => panic!("Expected {:?} to expose the race, got {:?}", schedule, x),
+        }
+    }
+    match run(&program, Schedule::Exhaustive, 1) {
+        Err(Error::Execution(message)) => assert!(message.contains("Exhaustive schedule")),
+        x => panic!(
+            "Expected the exhaustive schedule to expose the race, got {:?}",
+            x
+        ),
+    }
+}
+
+#[test]
+fn failing_random_seeds_reproduce() {
+    let program = last_writer();
+    let message = match run(&program, Schedule::Random(7), 16) {
+        Err(Error::Execution(message)) => message,
+        x => panic!(
+            "Expected the random schedule to expose the race, got {:?}",
+            x
+        ),
+    };
+    let seed = message
+        .trim_start_matches("Output data depends on the schedule under the Random(")
+        .trim_end_matches(") schedule")
+        .parse()
+        .unwrap();
+    assert_ne!(
+        run(&program, Schedule::Random(seed), 1).unwrap(),
+        run(&program, Schedule::Random(7), 1).unwrap()
+    );
+}
+
+#[test]
+fn schedule_counts_are_validated() {
+    for &schedules in &[0, 4097] {
+        match CpuExecutor::new(CpuConfig::default().schedules(schedules)) {
+            Err(Error::Validation(_)) => {}
+            x => panic!("Expected a validation error, got {:?}", x),
+        }
+    }
+    assert_eq!(
+        "scheduled:random:42".parse::<CpuMode>(),
+        Ok(CpuMode::Scheduled(Schedule::Random(42)))
+    );
+    assert_eq!(
+        "scheduled:round-robin".parse::<CpuMode>(),
+        Ok(CpuMode::Scheduled(Schedule::RoundRobin))
+    );
+    assert!("scheduled:sideways".parse::<CpuMode>().is_err());
+}
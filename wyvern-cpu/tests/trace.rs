@@ -0,0 +1,118 @@
+// Copyright 2018 | Dario Ostuni <dario.ostuni@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+extern crate wyvern_core as wcore;
+extern crate wyvern_cpu;
+
+use wcore::builder::ProgramBuilder;
+use wcore::executor::{Executable, Executor, Resource, WorkSize, IO};
+use wcore::program::{ConstantScalar, Op, Program, TokenValue};
+use wcore::types::{Array, Constant, Variable};
+use wyvern_cpu::debugger::{Breakpoint, DebugEvent};
+use wyvern_cpu::executor::{CpuConfig, CpuExecutor};
+use wyvern_cpu::trace::TraceFilter;
+
+fn counter() -> Program {
+    let b = ProgramBuilder::new();
+    let n = Variable::<u32>::new(&b).mark_as_inout("n");
+    n.store(n.load() + Constant::new(5_u32, &b));
+    b.finalize().unwrap()
+}
+
+fn scalar(value: u32) -> TokenValue {
+    TokenValue::Scalar(ConstantScalar::U32(value))
+}
+
+#[test]
+fn run_until_traces_every_iteration() {
+    let executor = CpuExecutor::new(CpuConfig::default()).unwrap();
+    let mut executable = executor.compile(counter()).unwrap();
+    executable.set_work_size(Some(WorkSize::new(1, 1)));
+    let n = executor.new_resource().unwrap();
+    n.set_data(scalar(0)).unwrap();
+    executable.bind("n", IO::InOut, n.clone()).unwrap();
+    executable.set_trace(Some(TraceFilter::default()));
+    let convergence = executable
+        .run_until::<u32, _>("n", None, |x| x >= 15)
+        .unwrap();
+    assert_eq!(convergence.iterations, 3);
+    assert_eq!(n.get_data(), scalar(15));
+    let trace = executable.trace().unwrap();
+    let per_iteration = trace.entries.len() / 3;
+    assert!(per_iteration > 0);
+    for (iteration, entries) in trace.entries.chunks(per_iteration).enumerate() {
+        assert!(entries.iter().all(|x| x.iteration == iteration as u32));
+        let loaded = ConstantScalar::U32(iteration as u32 * 5);
+        assert!(entries.iter().any(|x| x.result.map(|x| x.1) == Some(loaded)));
+    }
+}
+
+#[test]
+fn debugger_stops_at_breakpoints() {
+    let program = counter();
+    let (op, add) = program
+        .operation
+        .iter()
+        .enumerate()
+        .find_map(|(i, x)| match *x {
+            Op::Add(result, a, b) => Some((i as u32, (result, a, b))),
+            _ => None,
+        })
+        .unwrap();
+    let executor = CpuExecutor::new(CpuConfig::default()).unwrap();
+    let mut executable = executor.compile(program).unwrap();
+    executable.set_work_size(Some(WorkSize::new(2, 1)));
+    let n = executor.new_resource().unwrap();
+    n.set_data(scalar(1)).unwrap();
+    executable.bind("n", IO::InOut, n.clone()).unwrap();
+    let mut debugger = executable.debug().unwrap();
+    debugger.add_breakpoint(Breakpoint::Op(op));
+    assert_eq!(debugger.resume().unwrap(), DebugEvent::Breakpoint(Breakpoint::Op(op)));
+    assert_eq!(debugger.worker_id(), Some(0));
+    assert_eq!(debugger.op(), Some(&Op::Add(add.0, add.1, add.2)));
+    assert_eq!(debugger.value(add.1), Some(ConstantScalar::U32(1)));
+    assert_eq!(debugger.value(add.2), Some(ConstantScalar::U32(5)));
+    assert_eq!(debugger.step().unwrap(), DebugEvent::Stepped);
+    assert_eq!(debugger.value(add.0), Some(ConstantScalar::U32(6)));
+    assert_eq!(debugger.resume().unwrap(), DebugEvent::Breakpoint(Breakpoint::Op(op)));
+    assert_eq!(debugger.worker_id(), Some(1));
+    assert!(debugger.remove_breakpoint(Breakpoint::Op(op)));
+    assert_eq!(debugger.resume().unwrap(), DebugEvent::Finished);
+    assert!(debugger.is_finished());
+    drop(debugger);
+    assert_eq!(n.get_data(), scalar(11));
+}
+
+#[test]
+fn debugger_runs_every_phase() {
+    let b = ProgramBuilder::new();
+    let zero = Constant::new(0_u32, &b);
+    let one = Constant::new(1_u32, &b);
+    let a = Array::<u32>::new(zero, 0, true, &b).mark_as_inout("a");
+    let id = b.worker_id();
+    let v = a.at((id + one) % b.num_workers()).load();
+    b.global_barrier();
+    a.at(id).store(v + one);
+    let executor = CpuExecutor::new(CpuConfig::default()).unwrap();
+    let mut executable = executor.compile(b.finalize().unwrap()).unwrap();
+    executable.set_work_size(Some(WorkSize::new(2, 2)));
+    let a = executor.new_resource().unwrap();
+    a.write_slice(&[0_u32, 1, 2, 3]).unwrap();
+    executable.bind("a", IO::InOut, a.clone()).unwrap();
+    let mut debugger = executable.debug().unwrap();
+    assert_eq!(debugger.resume().unwrap(), DebugEvent::Finished);
+    drop(debugger);
+    assert_eq!(a.map(|x: &[u32]| x.to_vec()).unwrap(), vec![2, 3, 4, 1]);
+    executable.run().unwrap();
+    assert_eq!(a.map(|x: &[u32]| x.to_vec()).unwrap(), vec![4, 5, 2, 3]);
+}
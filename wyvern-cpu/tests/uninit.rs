@@ -0,0 +1,124 @@
+// Copyright 2018 | Dario Ostuni <dario.ostuni@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+extern crate wyvern_core as wcore;
+extern crate wyvern_cpu;
+
+use wcore::builder::ProgramBuilder;
+use wcore::error::Error;
+use wcore::executor::{Executable, Executor, Resource, WorkSize, IO};
+use wcore::program::Program;
+use wcore::types::{Array, Constant, Variable};
+use wyvern_cpu::executor::{CpuConfig, CpuExecutor, CpuMode};
+
+fn run(program: Program, mode: CpuMode, detect_uninit: bool) -> Result<Vec<u32>, Error> {
+    let config = CpuConfig::default()
+        .mode(mode)
+        .threads(4)
+        .detect_uninit(detect_uninit);
+    let executor = CpuExecutor::new(config)?;
+    let mut executable = executor.compile(program)?;
+    executable.set_work_size(Some(WorkSize::new(1, 4)));
+    let out = executor.new_resource()?;
+    out.write_slice(&[7_u32; 4])?;
+    executable.bind("out", IO::InOut, out.clone())?;
+    executable.run()?;
+    out.map(|x: &[u32]| x.to_vec())
+}
+
+fn modes() -> Vec<CpuMode> {
+    vec![CpuMode::Sequential, CpuMode::Parallel]
+}
+
+#[test]
+fn variables_written_on_some_paths_are_reported() {
+    let b = ProgramBuilder::new();
+    let zero = Constant::new(0_u32, &b);
+    let out = Array::<u32>::new(zero, 0, true, &b).mark_as_inout("out");
+    let id = b.worker_id();
+    let v = Variable::<u32>::new(&b);
+    b.if_then(|_| id.lt(Constant::new(2_u32, &b)), |_| v.store(id));
+    out.at(id).store(v.load());
+    let program = b.finalize().unwrap();
+    for mode in modes() {
+        assert!(run(program.clone(), mode, false).is_ok());
+        match run(program.clone(), mode, true) {
+            Err(Error::Execution(message)) => {
+                assert!(message.starts_with("Read of uninitialized "), "{}", message)
+            }
+            x => panic!("Expected an uninitialized read, got {:?}", x),
+        }
+    }
+}
+
+#[test]
+fn unwritten_shared_elements_are_reported() {
+    let b = ProgramBuilder::new();
+    let zero = Constant::new(0_u32, &b);
+    let out = Array::<u32>::new(zero, 0, true, &b).mark_as_inout("out");
+    let id = b.worker_id();
+    let shared = Array::<u32>::new(Constant::new(4_u32, &b), 4, true, &b);
+    b.if_then(
+        |_| id.ne(Constant::new(3_u32, &b)),
+        |_| shared.at(id).store(id),
+    );
+    b.barrier();
+    out.at(id).store(shared.at(id).load());
+    let program = b.finalize().unwrap();
+    for mode in modes() {
+        match run(program.clone(), mode, true) {
+            Err(Error::Execution(message)) => assert!(
+                message.starts_with("Read of uninitialized element 3 of "),
+                "{}",
+                message
+            ),
+            x => panic!("Expected an uninitialized read, got {:?}", x),
+        }
+    }
+}
+
+#[test]
+fn fully_initialized_programs_run() {
+    let b = ProgramBuilder::new();
+    let zero = Constant::new(0_u32, &b);
+    let out = Array::<u32>::new(zero, 0, true, &b).mark_as_inout("out");
+    let id = b.worker_id();
+    let shared = Array::<u32>::new(Constant::new(4_u32, &b), 4, true, &b);
+    shared.at(id).store(id + out.at(id).load());
+    b.barrier();
+    let other = (id + Constant::new(1_u32, &b)) % b.num_workers();
+    out.at(id).store(shared.at(other).load());
+    let program = b.finalize().unwrap();
+    for mode in modes() {
+        assert_eq!(run(program.clone(), mode, true).unwrap(), vec![8, 9, 10, 7]);
+    }
+}
+
+#[test]
+fn definitely_uninitialized_loads_fail_to_compile() {
+    let b = ProgramBuilder::new();
+    let zero = Constant::new(0_u32, &b);
+    let out = Array::<u32>::new(zero, 0, true, &b).mark_as_inout("out");
+    let v = Variable::<u32>::new(&b);
+    out.at(b.worker_id()).store(v.load());
+    let program = b.finalize().unwrap();
+    for mode in modes() {
+        match run(program.clone(), mode, true) {
+            Err(Error::Validation(message)) => {
+                assert!(message.starts_with("Load of uninitialized "), "{}", message)
+            }
+            x => panic!("Expected a validation error, got {:?}", x),
+        }
+        assert!(run(program.clone(), mode, false).is_ok());
+    }
+}
@@ -0,0 +1,875 @@
+// Copyright 2018 | Dario Ostuni <dario.ostuni@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use cranelift_codegen::ir::condcodes::{FloatCC, IntCC};
+use cranelift_codegen::ir::{
+    types, AbiParam, Block, FuncRef, InstBuilder, MemFlags, Type, UserFuncName, Value,
+};
+use cranelift_codegen::isa::TargetFrontendConfig;
+use cranelift_codegen::settings::{self, Configurable};
+use cranelift_codegen::Context;
+use cranelift_frontend::{FunctionBuilder, FunctionBuilderContext, Variable};
+use cranelift_jit::{JITBuilder, JITModule};
+use cranelift_module::{default_libcall_names, Linkage, Module};
+use runtime::{self, ArrayDesc, KernelFn};
+use std::collections::HashMap;
+use std::fmt;
+use std::mem;
+use wcore::executor::IO;
+use wcore::program::TokenType;
+use wcore::program::{ConstantScalar, DataType, LabelId, Op, Program, StorageType, TokenId};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum SharedSlot {
+    Scalar(u32),
+    Array(u32),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ArraySlot {
+    Shared(u32),
+    Private(u32),
+}
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Layout {
+    pub(crate) scalars: Vec<DataType>,
+    pub(crate) arrays: Vec<(DataType, u32)>,
+    pub(crate) private: Vec<(u32, u32)>,
+    pub(crate) private_size: usize,
+    pub(crate) io: Vec<(IO, String, SharedSlot)>,
+    pub(crate) has_barrier: bool,
+}
+
+pub(crate) struct Kernel {
+    module: Option<JITModule>,
+    pub(crate) function: KernelFn,
+    pub(crate) layout: Layout,
+}
+
+struct Translator<'a, 'b> {
+    program: &'a Program,
+    builder: FunctionBuilder<'b>,
+    config: TargetFrontendConfig,
+    ptr: Type,
+    next_var: u32,
+    vars: HashMap<TokenId, Variable>,
+    scalars: HashMap<TokenId, u32>,
+    arrays: HashMap<TokenId, ArraySlot>,
+    io_arrays: Vec<TokenId>,
+    shared_arrays: Vec<(Value, u32)>,
+    private_arrays: Vec<(u32, u32, Variable, Variable)>,
+    labels: Option<(Variable, Variable)>,
+    params: Params,
+    error_block: Block,
+    barrier_fn: FuncRef,
+    fmod_fn: FuncRef,
+    has_barrier: bool,
+    op: u32,
+    next_op: u32,
+}
+
+struct Params {
+    private: Value,
+    barrier: Value,
+    fault: Value,
+    worker_id: Value,
+    num_workers: Value,
+    scalars: Value,
+    arrays: Value,
+    abort: Value,
+}
+
+impl Drop for Kernel {
+    fn drop(&mut self) {
+        if let Some(module) = self.module.take() {
+            unsafe { module.free_memory() };
+        }
+    }
+}
+
+unsafe impl Send for Kernel {}
+unsafe impl Sync for Kernel {}
+
+impl fmt::Debug for Kernel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Kernel")
+            .field("layout", &self.layout)
+            .finish()
+    }
+}
+
+pub(crate) fn compile(program: &Program) -> Result<Kernel, String> {
+    let mut layout = Layout::default();
+    let (scalars, arrays, io_arrays) = allocate_storage(program, &mut layout)?;
+    let mut flags = settings::builder();
+    flags
+        .set("opt_level", "speed")
+        .map_err(|e| format!("{:?}", e))?;
+    flags
+        .set("use_colocated_libcalls", "false")
+        .map_err(|e| format!("{:?}", e))?;
+    flags
+        .set("is_pic", "false")
+        .map_err(|e| format!("{:?}", e))?;
+    let isa = cranelift_native::builder()?
+        .finish(settings::Flags::new(flags))
+        .map_err(|e| format!("{:?}", e))?;
+    let mut jit_builder = JITBuilder::with_isa(isa, default_libcall_names());
+    jit_builder.symbol("wyvern_barrier", runtime::wyvern_barrier as *const u8);
+    jit_builder.symbol("wyvern_fmodf", runtime::wyvern_fmodf as *const u8);
+    let mut module = JITModule::new(jit_builder);
+    let ptr = module.target_config().pointer_type();
+
+    let mut barrier_sig = module.make_signature();
+    barrier_sig.params.push(AbiParam::new(ptr));
+    let barrier_id = module
+        .declare_function("wyvern_barrier", Linkage::Import, &barrier_sig)
+        .map_err(|e| format!("{:?}", e))?;
+    let mut fmod_sig = module.make_signature();
+    fmod_sig.params.push(AbiParam::new(types::F32));
+    fmod_sig.params.push(AbiParam::new(types::F32));
+    fmod_sig.returns.push(AbiParam::new(types::F32));
+    let fmod_id = module
+        .declare_function("wyvern_fmodf", Linkage::Import, &fmod_sig)
+        .map_err(|e| format!("{:?}", e))?;
+    let mut kernel_sig = module.make_signature();
+    for ty in &[ptr, ptr, ptr, ptr, types::I32, types::I32] {
+        kernel_sig.params.push(AbiParam::new(*ty));
+    }
+    kernel_sig.returns.push(AbiParam::new(types::I32));
+    let kernel_id = module
+        .declare_function("kernel", Linkage::Local, &kernel_sig)
+        .map_err(|e| format!("{:?}", e))?;
+
+    let mut ctx: Context = module.make_context();
+    ctx.func.signature = kernel_sig;
+    ctx.func.name = UserFuncName::user(0, kernel_id.as_u32());
+    let mut func_ctx = FunctionBuilderContext::new();
+    {
+        let barrier_fn = module.declare_func_in_func(barrier_id, &mut ctx.func);
+        let fmod_fn = module.declare_func_in_func(fmod_id, &mut ctx.func);
+        let mut builder = FunctionBuilder::new(&mut ctx.func, &mut func_ctx);
+        let entry = builder.create_block();
+        builder.append_block_params_for_function_params(entry);
+        builder.switch_to_block(entry);
+        let error_block = builder.create_block();
+        for _ in 0..4 {
+            builder.append_block_param(error_block, types::I32);
+        }
+        let args = builder.block_params(entry).to_vec();
+        let flags = MemFlags::trusted();
+        let ptr_size = i32::from(ptr.bytes() as u8);
+        let params = Params {
+            private: args[1],
+            barrier: args[2],
+            fault: args[3],
+            worker_id: args[4],
+            num_workers: args[5],
+            scalars: builder.ins().load(ptr, flags, args[0], 0),
+            arrays: builder.ins().load(ptr, flags, args[0], ptr_size),
+            abort: builder.ins().load(ptr, flags, args[0], 2 * ptr_size),
+        };
+        let mut translator = Translator {
+            program,
+            builder,
+            config: module.target_config(),
+            ptr,
+            next_var: 0,
+            vars: HashMap::new(),
+            scalars,
+            arrays,
+            io_arrays,
+            shared_arrays: Vec::new(),
+            private_arrays: Vec::new(),
+            labels: None,
+            params,
+            error_block,
+            barrier_fn,
+            fmod_fn,
+            has_barrier: false,
+            op: 0,
+            next_op: 0,
+        };
+        translator.prepare(&layout);
+        translator.lower_block(&program.operation)?;
+        layout.has_barrier = translator.has_barrier;
+        translator.finish();
+    }
+    module
+        .define_function(kernel_id, &mut ctx)
+        .map_err(|e| format!("{:?}", e))?;
+    module.clear_context(&mut ctx);
+    module
+        .finalize_definitions()
+        .map_err(|e| format!("{:?}", e))?;
+    let code = module.get_finalized_function(kernel_id);
+    let function = unsafe { mem::transmute::<*const u8, KernelFn>(code) };
+    Ok(Kernel {
+        module: Some(module),
+        function,
+        layout,
+    })
+}
+
+type Storage = (
+    HashMap<TokenId, u32>,
+    HashMap<TokenId, ArraySlot>,
+    Vec<TokenId>,
+);
+
+fn allocate_storage(program: &Program, layout: &mut Layout) -> Result<Storage, String> {
+    let mut scalars = HashMap::new();
+    let mut arrays = HashMap::new();
+    let mut io_arrays = Vec::new();
+    for (kind, name, id) in program.io() {
+        let slot = match program.storage.get(&id) {
+            Some(&StorageType::Variable(ty)) => {
+                let shared_scalars = &mut layout.scalars;
+                let slot = *scalars.entry(id).or_insert_with(|| {
+                    shared_scalars.push(ty);
+                    shared_scalars.len() as u32 - 1
+                });
+                SharedSlot::Scalar(slot)
+            }
+            Some(&StorageType::SharedArray(ty, max_size)) => {
+                let shared_arrays = &mut layout.arrays;
+                let slot = arrays.entry(id).or_insert_with(|| {
+                    shared_arrays.push((ty, max_size));
+                    ArraySlot::Shared(shared_arrays.len() as u32 - 1)
+                });
+                io_arrays.push(id);
+                match *slot {
+                    ArraySlot::Shared(slot) => SharedSlot::Array(slot),
+                    ArraySlot::Private(_) => unreachable!(),
+                }
+            }
+            storage => {
+                return Err(format!(
+                    "Invalid storage {:?} for IO {} ({:?})",
+                    storage, name, id
+                ))
+            }
+        };
+        layout.io.push((kind, name.clone(), slot));
+    }
+    let mut storage: Vec<_> = program.storage.iter().collect();
+    storage.sort_by_key(|&(id, _)| id.0);
+    for (id, storage) in storage {
+        if arrays.contains_key(id) {
+            continue;
+        }
+        match *storage {
+            StorageType::SharedArray(ty, max_size) => {
+                layout.arrays.push((ty, max_size));
+                let slot = layout.arrays.len() as u32 - 1;
+                arrays.insert(*id, ArraySlot::Shared(slot));
+            }
+            StorageType::PrivateArray(_, 0) => {
+                return Err(format!("Private array {:?} needs a maximum size", id));
+            }
+            StorageType::PrivateArray(_, max_size) => {
+                layout.private.push((layout.private_size as u32, max_size));
+                layout.private_size += max_size as usize;
+                let slot = layout.private.len() as u32 - 1;
+                arrays.insert(*id, ArraySlot::Private(slot));
+            }
+            StorageType::Variable(_) => continue,
+        }
+    }
+    Ok((scalars, arrays, io_arrays))
+}
+
+impl<'a, 'b> Translator<'a, 'b> {
+    fn prepare(&mut self, layout: &Layout) {
+        let flags = MemFlags::trusted();
+        for (slot, &(_, max_size)) in layout.arrays.iter().enumerate() {
+            let offset = (slot * mem::size_of::<ArrayDesc>()) as i32;
+            let data = self
+                .builder
+                .ins()
+                .load(self.ptr, flags, self.params.arrays, offset);
+            self.shared_arrays.push((data, max_size));
+        }
+        for &(offset, capacity) in &layout.private {
+            let len = self.declare_var(types::I32);
+            let allocated = self.declare_var(types::I32);
+            let zero = self.builder.ins().iconst(types::I32, 0);
+            self.builder.def_var(len, zero);
+            self.builder.def_var(allocated, zero);
+            self.private_arrays.push((offset, capacity, len, allocated));
+        }
+        if has_phi(&self.program.operation) {
+            let prev = self.declare_var(types::I32);
+            let current = self.declare_var(types::I32);
+            let zero = self.builder.ins().iconst(types::I32, 0);
+            self.builder.def_var(prev, zero);
+            self.builder.def_var(current, zero);
+            self.labels = Some((prev, current));
+        }
+    }
+
+    fn finish(mut self) {
+        let success = self
+            .builder
+            .ins()
+            .iconst(types::I32, i64::from(runtime::STATUS_OK));
+        self.builder.ins().return_(&[success]);
+        self.builder.switch_to_block(self.error_block);
+        let params = self.builder.block_params(self.error_block).to_vec();
+        let fault = self.params.fault;
+        for (field, &value) in params[1..].iter().enumerate() {
+            self.builder
+                .ins()
+                .store(MemFlags::trusted(), value, fault, field as i32 * 4);
+        }
+        self.builder.ins().return_(&[params[0]]);
+        self.builder.seal_all_blocks();
+        self.builder.finalize();
+    }
+
+    fn lower_block(&mut self, block: &[Op]) -> Result<(), String> {
+        for op in block {
+            self.op = self.next_op;
+            self.next_op += 1;
+            self.lower(op)?;
+        }
+        Ok(())
+    }
+
+    fn lower(&mut self, op: &Op) -> Result<(), String> {
+        match *op {
+            Op::Phi(r, a0, l0, a1, l1) => {
+                let prev = match self.labels {
+                    Some((prev, _)) => self.builder.use_var(prev),
+                    None => unreachable!(),
+                };
+                let is_l0 = self
+                    .builder
+                    .ins()
+                    .icmp_imm(IntCC::Equal, prev, i64::from(l0.0));
+                let is_l1 = self
+                    .builder
+                    .ins()
+                    .icmp_imm(IntCC::Equal, prev, i64::from(l1.0));
+                let known = self.builder.ins().bor(is_l0, is_l1);
+                self.check(known, runtime::STATUS_INVALID_PHI, None);
+                let a0 = self.get(a0)?;
+                let a1 = self.get(a1)?;
+                let v = self.builder.ins().select(is_l0, a0, a1);
+                self.set(r, v)?;
+            }
+            Op::If(ref cond_op, cond, l0, ref a0, lend) => {
+                self.lower_block(cond_op)?;
+                let cond = self.get(cond)?;
+                let then_block = self.builder.create_block();
+                let end_block = self.builder.create_block();
+                self.builder
+                    .ins()
+                    .brif(cond, then_block, &[], end_block, &[]);
+                self.builder.switch_to_block(then_block);
+                self.label(l0);
+                self.lower_block(a0)?;
+                self.builder.ins().jump(end_block, &[]);
+                self.builder.switch_to_block(end_block);
+                self.label(lend);
+            }
+            Op::IfElse(ref cond_op, cond, l0, ref a0, l1, ref a1, lend) => {
+                self.lower_block(cond_op)?;
+                let cond = self.get(cond)?;
+                let then_block = self.builder.create_block();
+                let else_block = self.builder.create_block();
+                let end_block = self.builder.create_block();
+                self.builder
+                    .ins()
+                    .brif(cond, then_block, &[], else_block, &[]);
+                self.builder.switch_to_block(then_block);
+                self.label(l0);
+                self.lower_block(a0)?;
+                self.builder.ins().jump(end_block, &[]);
+                self.builder.switch_to_block(else_block);
+                self.label(l1);
+                self.lower_block(a1)?;
+                self.builder.ins().jump(end_block, &[]);
+                self.builder.switch_to_block(end_block);
+                self.label(lend);
+            }
+            Op::While(lcond, ref cond_op, cond, l0, ref a0, lend) => {
+                let id = self.op;
+                let header = self.builder.create_block();
+                let body = self.builder.create_block();
+                let exit = self.builder.create_block();
+                self.builder.ins().jump(header, &[]);
+                self.builder.switch_to_block(header);
+                self.label(lcond);
+                self.lower_block(cond_op)?;
+                let cond = self.get(cond)?;
+                self.builder.ins().brif(cond, body, &[], exit, &[]);
+                self.builder.switch_to_block(body);
+                self.label(l0);
+                self.lower_block(a0)?;
+                self.op = id;
+                let abort = self.abort_flag();
+                let fault = self.fault(runtime::STATUS_ABORTED, None);
+                self.builder
+                    .ins()
+                    .brif(abort, self.error_block, &fault, header, &[]);
+                self.builder.switch_to_block(exit);
+                self.label(lend);
+            }
+            Op::MemoryBarrier => {
+                self.builder.ins().fence();
+            }
+            Op::GlobalBarrier => {
+                return Err("GlobalBarrier must be split before code generation".into());
+            }
+            Op::ControlBarrier => {
+                self.has_barrier = true;
+                self.builder.ins().fence();
+                let barrier = self.params.barrier;
+                self.builder.ins().call(self.barrier_fn, &[barrier]);
+                let abort = self.abort_flag();
+                let running = self.builder.ins().icmp_imm(IntCC::Equal, abort, 0);
+                self.check(running, runtime::STATUS_ABORTED, None);
+            }
+            Op::WorkerId(r) => {
+                let v = self.params.worker_id;
+                self.set(r, v)?;
+            }
+            Op::NumWorkers(r) => {
+                let v = self.params.num_workers;
+                self.set(r, v)?;
+            }
+            Op::Load(r, a) => {
+                let v = match self.scalars.get(&a) {
+                    Some(&slot) => {
+                        let ty = self.cl_type(r)?;
+                        let base = self.params.scalars;
+                        self.builder
+                            .ins()
+                            .load(ty, MemFlags::trusted(), base, slot as i32 * 4)
+                    }
+                    None => self.get(a)?,
+                };
+                self.set(r, v)?;
+            }
+            Op::Store(r, a) => {
+                let v = self.get(a)?;
+                match self.scalars.get(&r) {
+                    Some(&slot) => {
+                        let base = self.params.scalars;
+                        self.builder
+                            .ins()
+                            .store(MemFlags::trusted(), v, base, slot as i32 * 4);
+                    }
+                    None => self.set(r, v)?,
+                }
+            }
+            Op::ArrayNew(r, s, _, _, _) => {
+                let size = self.get(s)?;
+                match self.array(r)? {
+                    ArraySlot::Shared(_) if self.io_arrays.contains(&r) => {}
+                    ArraySlot::Shared(slot) => {
+                        let capacity = self.shared_arrays[slot as usize].1;
+                        self.check_size(size, capacity);
+                        let (base, offset) = self.shared_len(slot);
+                        self.builder
+                            .ins()
+                            .store(MemFlags::trusted(), size, base, offset);
+                    }
+                    ArraySlot::Private(slot) => self.private_new(slot, size),
+                }
+            }
+            Op::ArrayLen(r, v) => {
+                let slot = self.array(v)?;
+                let len = self.array_len(slot);
+                self.set(r, len)?;
+            }
+            Op::ArrayLoad(r, v, i) => {
+                let ty = self.cl_type(r)?;
+                let slot = self.array(v)?;
+                let index = self.get(i)?;
+                let address = self.element(slot, index);
+                let v = self.builder.ins().load(ty, MemFlags::trusted(), address, 0);
+                self.set(r, v)?;
+            }
+            Op::ArrayStore(v, i, a) => {
+                let slot = self.array(v)?;
+                let index = self.get(i)?;
+                let value = self.get(a)?;
+                let address = self.element(slot, index);
+                self.builder
+                    .ins()
+                    .store(MemFlags::trusted(), value, address, 0);
+            }
+            Op::Constant(r, a) => {
+                let v = match a {
+                    ConstantScalar::F32(x) => self.builder.ins().f32const(x),
+                    ConstantScalar::I32(x) => self.builder.ins().iconst(types::I32, i64::from(x)),
+                    ConstantScalar::U32(x) => self.builder.ins().iconst(types::I32, i64::from(x)),
+                    ConstantScalar::Bool(x) => self.builder.ins().iconst(types::I32, x as i64),
+                };
+                self.set(r, v)?;
+            }
+            Op::U32fromF32(r, a) => {
+                let a = self.get(a)?;
+                let v = self.builder.ins().fcvt_to_uint_sat(types::I32, a);
+                self.set(r, v)?;
+            }
+            Op::I32fromF32(r, a) => {
+                let a = self.get(a)?;
+                let v = self.builder.ins().fcvt_to_sint_sat(types::I32, a);
+                self.set(r, v)?;
+            }
+            Op::F32fromU32(r, a) => {
+                let a = self.get(a)?;
+                let v = self.builder.ins().fcvt_from_uint(types::F32, a);
+                self.set(r, v)?;
+            }
+            Op::F32fromI32(r, a) => {
+                let a = self.get(a)?;
+                let v = self.builder.ins().fcvt_from_sint(types::F32, a);
+                self.set(r, v)?;
+            }
+            Op::I32fromU32(r, a) | Op::U32fromI32(r, a) => {
+                let a = self.get(a)?;
+                self.set(r, a)?;
+            }
+            Op::Neg(r, a) => {
+                let ty = self.data_type(a)?;
+                let a = self.get(a)?;
+                let v = match ty {
+                    DataType::I32 => self.builder.ins().ineg(a),
+                    DataType::F32 => self.builder.ins().fneg(a),
+                    _ => return Err(format!("Invalid operand type for {:?}", op)),
+                };
+                self.set(r, v)?;
+            }
+            Op::Not(r, a) => {
+                let ty = self.data_type(a)?;
+                let a = self.get(a)?;
+                let v = match ty {
+                    DataType::I32 | DataType::U32 => self.builder.ins().bnot(a),
+                    DataType::Bool => {
+                        let v = self.builder.ins().icmp_imm(IntCC::Equal, a, 0);
+                        self.builder.ins().uextend(types::I32, v)
+                    }
+                    DataType::F32 => return Err(format!("Invalid operand type for {:?}", op)),
+                };
+                self.set(r, v)?;
+            }
+            Op::Add(r, a, b)
+            | Op::Sub(r, a, b)
+            | Op::Mul(r, a, b)
+            | Op::Div(r, a, b)
+            | Op::Rem(r, a, b)
+            | Op::Shl(r, a, b)
+            | Op::Shr(r, a, b)
+            | Op::BitAnd(r, a, b)
+            | Op::BitOr(r, a, b)
+            | Op::BitXor(r, a, b) => {
+                let ty = self.data_type(a)?;
+                let a = self.get(a)?;
+                let b = self.get(b)?;
+                let v = self
+                    .arithmetic(op, ty, a, b)
+                    .ok_or_else(|| format!("Invalid operand type for {:?}", op))?;
+                self.set(r, v)?;
+            }
+            Op::Eq(r, a, b)
+            | Op::Ne(r, a, b)
+            | Op::Lt(r, a, b)
+            | Op::Le(r, a, b)
+            | Op::Gt(r, a, b)
+            | Op::Ge(r, a, b) => {
+                let ty = self.data_type(a)?;
+                let a = self.get(a)?;
+                let b = self.get(b)?;
+                let v = self.compare(op, ty, a, b);
+                let v = self.builder.ins().uextend(types::I32, v);
+                self.set(r, v)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn arithmetic(&mut self, op: &Op, ty: DataType, a: Value, b: Value) -> Option<Value> {
+        let ins = match (op, ty) {
+            (&Op::Add(..), DataType::F32) => self.builder.ins().fadd(a, b),
+            (&Op::Sub(..), DataType::F32) => self.builder.ins().fsub(a, b),
+            (&Op::Mul(..), DataType::F32) => self.builder.ins().fmul(a, b),
+            (&Op::Div(..), DataType::F32) => self.builder.ins().fdiv(a, b),
+            (&Op::Rem(..), DataType::F32) => {
+                let call = self.builder.ins().call(self.fmod_fn, &[a, b]);
+                self.builder.inst_results(call)[0]
+            }
+            (_, DataType::F32) => return None,
+            (&Op::Add(..), DataType::I32) | (&Op::Add(..), DataType::U32) => {
+                self.builder.ins().iadd(a, b)
+            }
+            (&Op::Sub(..), DataType::I32) | (&Op::Sub(..), DataType::U32) => {
+                self.builder.ins().isub(a, b)
+            }
+            (&Op::Mul(..), DataType::I32) | (&Op::Mul(..), DataType::U32) => {
+                self.builder.ins().imul(a, b)
+            }
+            (&Op::Div(..), DataType::I32) | (&Op::Rem(..), DataType::I32) => {
+                let zero = self.builder.ins().icmp_imm(IntCC::Equal, b, 0);
+                let minus_one = self.builder.ins().icmp_imm(IntCC::Equal, b, -1);
+                let special = self.builder.ins().bor(zero, minus_one);
+                let one = self.builder.ins().iconst(types::I32, 1);
+                let divisor = self.builder.ins().select(special, one, b);
+                let (v, by_minus_one, by_zero) = match *op {
+                    Op::Div(..) => (
+                        self.builder.ins().sdiv(a, divisor),
+                        self.builder.ins().ineg(a),
+                        self.builder.ins().iconst(types::I32, -1),
+                    ),
+                    _ => (
+                        self.builder.ins().srem(a, divisor),
+                        self.builder.ins().iconst(types::I32, 0),
+                        a,
+                    ),
+                };
+                let v = self.builder.ins().select(minus_one, by_minus_one, v);
+                self.builder.ins().select(zero, by_zero, v)
+            }
+            (&Op::Div(..), DataType::U32) | (&Op::Rem(..), DataType::U32) => {
+                let zero = self.builder.ins().icmp_imm(IntCC::Equal, b, 0);
+                let one = self.builder.ins().iconst(types::I32, 1);
+                let divisor = self.builder.ins().select(zero, one, b);
+                let (v, by_zero) = match *op {
+                    Op::Div(..) => (
+                        self.builder.ins().udiv(a, divisor),
+                        self.builder.ins().iconst(types::I32, -1),
+                    ),
+                    _ => (self.builder.ins().urem(a, divisor), a),
+                };
+                self.builder.ins().select(zero, by_zero, v)
+            }
+            (&Op::Shl(..), DataType::I32) | (&Op::Shl(..), DataType::U32) => {
+                self.builder.ins().ishl(a, b)
+            }
+            (&Op::Shr(..), DataType::I32) => self.builder.ins().sshr(a, b),
+            (&Op::Shr(..), DataType::U32) => self.builder.ins().ushr(a, b),
+            (&Op::BitAnd(..), _) => self.builder.ins().band(a, b),
+            (&Op::BitOr(..), _) => self.builder.ins().bor(a, b),
+            (&Op::BitXor(..), _) => self.builder.ins().bxor(a, b),
+            _ => return None,
+        };
+        Some(ins)
+    }
+
+    fn compare(&mut self, op: &Op, ty: DataType, a: Value, b: Value) -> Value {
+        if ty == DataType::F32 {
+            let cc = match *op {
+                Op::Eq(..) => FloatCC::Equal,
+                Op::Ne(..) => FloatCC::NotEqual,
+                Op::Lt(..) => FloatCC::LessThan,
+                Op::Le(..) => FloatCC::LessThanOrEqual,
+                Op::Gt(..) => FloatCC::GreaterThan,
+                Op::Ge(..) => FloatCC::GreaterThanOrEqual,
+                _ => unreachable!(),
+            };
+            return self.builder.ins().fcmp(cc, a, b);
+        }
+        let signed = ty == DataType::I32;
+        let cc = match (op, signed) {
+            (&Op::Eq(..), _) => IntCC::Equal,
+            (&Op::Ne(..), _) => IntCC::NotEqual,
+            (&Op::Lt(..), true) => IntCC::SignedLessThan,
+            (&Op::Le(..), true) => IntCC::SignedLessThanOrEqual,
+            (&Op::Gt(..), true) => IntCC::SignedGreaterThan,
+            (&Op::Ge(..), true) => IntCC::SignedGreaterThanOrEqual,
+            (&Op::Lt(..), false) => IntCC::UnsignedLessThan,
+            (&Op::Le(..), false) => IntCC::UnsignedLessThanOrEqual,
+            (&Op::Gt(..), false) => IntCC::UnsignedGreaterThan,
+            (&Op::Ge(..), false) => IntCC::UnsignedGreaterThanOrEqual,
+            _ => unreachable!(),
+        };
+        self.builder.ins().icmp(cc, a, b)
+    }
+
+    fn check(&mut self, condition: Value, status: u32, detail: Option<(Value, Value)>) {
+        let ok = self.builder.create_block();
+        let fault = self.fault(status, detail);
+        self.builder
+            .ins()
+            .brif(condition, ok, &[], self.error_block, &fault);
+        self.builder.switch_to_block(ok);
+    }
+
+    fn fault(&mut self, status: u32, detail: Option<(Value, Value)>) -> [Value; 4] {
+        let status = self.builder.ins().iconst(types::I32, i64::from(status));
+        let op = self.builder.ins().iconst(types::I32, i64::from(self.op));
+        let (value, limit) = match detail {
+            Some(detail) => detail,
+            None => {
+                let zero = self.builder.ins().iconst(types::I32, 0);
+                (zero, zero)
+            }
+        };
+        [status, op, value, limit]
+    }
+
+    fn abort_flag(&mut self) -> Value {
+        let abort = self.params.abort;
+        self.builder
+            .ins()
+            .load(types::I8, MemFlags::trusted(), abort, 0)
+    }
+
+    fn label(&mut self, label: LabelId) {
+        if let Some((prev, current)) = self.labels {
+            let v = self.builder.use_var(current);
+            self.builder.def_var(prev, v);
+            let v = self.builder.ins().iconst(types::I32, i64::from(label.0));
+            self.builder.def_var(current, v);
+        }
+    }
+
+    fn check_size(&mut self, size: Value, capacity: u32) {
+        let fits =
+            self.builder
+                .ins()
+                .icmp_imm(IntCC::UnsignedLessThanOrEqual, size, i64::from(capacity));
+        let capacity = self.builder.ins().iconst(types::I32, i64::from(capacity));
+        self.check(fits, runtime::STATUS_TOO_LARGE, Some((size, capacity)));
+    }
+
+    fn shared_len(&self, slot: u32) -> (Value, i32) {
+        let offset = slot as usize * mem::size_of::<ArrayDesc>() + mem::offset_of!(ArrayDesc, len);
+        (self.params.arrays, offset as i32)
+    }
+
+    fn array_len(&mut self, slot: ArraySlot) -> Value {
+        match slot {
+            ArraySlot::Shared(slot) => {
+                let (base, offset) = self.shared_len(slot);
+                self.builder
+                    .ins()
+                    .load(types::I32, MemFlags::trusted(), base, offset)
+            }
+            ArraySlot::Private(slot) => {
+                let len = self.private_arrays[slot as usize].2;
+                self.builder.use_var(len)
+            }
+        }
+    }
+
+    fn element(&mut self, slot: ArraySlot, index: Value) -> Value {
+        let len = self.array_len(slot);
+        let in_bounds = self.builder.ins().icmp(IntCC::UnsignedLessThan, index, len);
+        self.check(in_bounds, runtime::STATUS_OUT_OF_BOUNDS, Some((index, len)));
+        let base = match slot {
+            ArraySlot::Shared(slot) => self.shared_arrays[slot as usize].0,
+            ArraySlot::Private(slot) => {
+                let offset = self.private_arrays[slot as usize].0;
+                let private = self.params.private;
+                self.builder.ins().iadd_imm(private, i64::from(offset) * 4)
+            }
+        };
+        let index = self.builder.ins().uextend(self.ptr, index);
+        let offset = self.builder.ins().imul_imm(index, 4);
+        self.builder.ins().iadd(base, offset)
+    }
+
+    fn private_new(&mut self, slot: u32, size: Value) {
+        let (offset, capacity, len, allocated) = self.private_arrays[slot as usize];
+        let allocate = self.builder.create_block();
+        let done = self.builder.create_block();
+        let is_allocated = self.builder.use_var(allocated);
+        self.builder
+            .ins()
+            .brif(is_allocated, done, &[], allocate, &[]);
+        self.builder.switch_to_block(allocate);
+        self.check_size(size, capacity);
+        self.builder.def_var(len, size);
+        let one = self.builder.ins().iconst(types::I32, 1);
+        self.builder.def_var(allocated, one);
+        let private = self.params.private;
+        let base = self.builder.ins().iadd_imm(private, i64::from(offset) * 4);
+        let bytes = self.builder.ins().uextend(self.ptr, size);
+        let bytes = self.builder.ins().imul_imm(bytes, 4);
+        let zero = self.builder.ins().iconst(types::I8, 0);
+        self.builder.call_memset(self.config, base, zero, bytes);
+        self.builder.ins().jump(done, &[]);
+        self.builder.switch_to_block(done);
+    }
+
+    fn declare_var(&mut self, ty: Type) -> Variable {
+        let var = Variable::from_u32(self.next_var);
+        self.next_var += 1;
+        self.builder.declare_var(var, ty);
+        var
+    }
+
+    fn var(&mut self, id: TokenId) -> Result<Variable, String> {
+        if let Some(&var) = self.vars.get(&id) {
+            return Ok(var);
+        }
+        if self.scalars.contains_key(&id) || self.arrays.contains_key(&id) {
+            return Err(format!("{:?} is not a private value", id));
+        }
+        let ty = self.cl_type(id)?;
+        let var = self.declare_var(ty);
+        self.vars.insert(id, var);
+        Ok(var)
+    }
+
+    fn get(&mut self, id: TokenId) -> Result<Value, String> {
+        let var = self.var(id)?;
+        Ok(self.builder.use_var(var))
+    }
+
+    fn set(&mut self, id: TokenId, value: Value) -> Result<(), String> {
+        let var = self.var(id)?;
+        self.builder.def_var(var, value);
+        Ok(())
+    }
+
+    fn array(&self, id: TokenId) -> Result<ArraySlot, String> {
+        self.arrays
+            .get(&id)
+            .cloned()
+            .ok_or_else(|| format!("{:?} is not an array", id))
+    }
+
+    fn data_type(&self, id: TokenId) -> Result<DataType, String> {
+        match self.program.symbol.get(&id) {
+            Some(&TokenType::Constant(ty))
+            | Some(&TokenType::Variable(ty))
+            | Some(&TokenType::ArrayPointer(ty))
+            | Some(&TokenType::Array(ty)) => Ok(ty),
+            _ => Err(format!("{:?} has no data type", id)),
+        }
+    }
+
+    fn cl_type(&self, id: TokenId) -> Result<Type, String> {
+        Ok(match self.data_type(id)? {
+            DataType::F32 => types::F32,
+            _ => types::I32,
+        })
+    }
+}
+
+fn has_phi(block: &[Op]) -> bool {
+    block.iter().any(|op| match *op {
+        Op::Phi(..) => true,
+        Op::If(ref c, _, _, ref a, _) => has_phi(c) || has_phi(a),
+        Op::IfElse(ref c, _, _, ref a, _, ref b, _) => has_phi(c) || has_phi(a) || has_phi(b),
+        Op::While(_, ref c, _, _, ref a, _) => has_phi(c) || has_phi(a),
+        _ => false,
+    })
+}
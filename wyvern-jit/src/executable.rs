@@ -0,0 +1,485 @@
+// Copyright 2018 | Dario Ostuni <dario.ostuni@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use codegen::{Kernel, SharedSlot};
+use executor::JitConfig;
+use memory::{bits_vector, from_bits, to_bits, vector_bits, SharedMemory};
+use resource::JitResource;
+use runtime::{self, Fault, KernelFn, SharedEnv};
+use std::collections::{BTreeMap, HashMap};
+use std::ptr;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Barrier, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use sync::GroupBarrier;
+use wcore::error::Error;
+use wcore::executor::{
+    check_binding, check_bindings, check_override, convergence_flag, BatchRun, Convergence,
+    Executable, Overrides, Report, Resource, WorkSize, IO,
+};
+use wcore::program::{byte_size, get_token_type, Parameter, Program, TokenType, TokenValue};
+use wcore::split::{is_hidden, next_phase, Phases, DONE, NEXT, PHASE};
+use wcore::submission::Submission;
+use wcore::types::Type;
+
+#[derive(Debug)]
+pub struct JitExecutable {
+    pub(crate) kernel: Arc<Kernel>,
+    pub(crate) program: Program,
+    pub(crate) phases: Option<Phases>,
+    pub(crate) binding: HashMap<(IO, String), Arc<JitResource>>,
+    pub(crate) work_size: Option<WorkSize>,
+    pub(crate) config: JitConfig,
+    pub(crate) compile_time: Duration,
+}
+
+struct Context<'a> {
+    function: KernelFn,
+    program: &'a Program,
+    env: &'a SharedEnv,
+    memory: &'a SharedMemory,
+    private_size: usize,
+    num_workers: u32,
+    error: &'a Mutex<Option<Error>>,
+}
+
+struct Lane {
+    barrier: GroupBarrier,
+    handoff: Barrier,
+    group: AtomicU32,
+}
+
+impl Executable for JitExecutable {
+    type Error = Error;
+    type Report = Report;
+    type Resource = JitResource;
+
+    fn bind<S: ToString>(
+        &mut self,
+        name: S,
+        kind: IO,
+        resource: Arc<JitResource>,
+    ) -> Result<Option<Arc<Self::Resource>>, Error> {
+        let name = name.to_string();
+        check_binding(&self.program, &name, kind, &*resource)?;
+        Ok(self.binding.insert((kind, name), resource))
+    }
+
+    fn unbind<S: ToString>(&mut self, name: S, kind: IO) -> Option<Arc<Self::Resource>> {
+        self.binding.remove(&(kind, name.to_string()))
+    }
+
+    fn binding<S: ToString>(&self, name: S, kind: IO) -> Option<Arc<Self::Resource>> {
+        self.binding.get(&(kind, name.to_string())).cloned()
+    }
+
+    fn parameters(&self) -> Vec<Parameter> {
+        self.program.parameters()
+    }
+
+    fn check_bindings(&self) -> Result<(), Error> {
+        check_bindings(&self.program, |kind, name| self.binding(name, kind))
+    }
+
+    fn work_size(&self) -> WorkSize {
+        self.work_size
+            .unwrap_or_else(|| WorkSize::for_workers(self.config.threads as u32, 1))
+    }
+
+    fn set_work_size(&mut self, work_size: Option<WorkSize>) {
+        self.work_size = work_size;
+    }
+
+    fn run(&mut self) -> Result<Report, Error> {
+        self.check_bindings()?;
+        self.launch(|_| true)
+    }
+
+    fn run_batch(&mut self, runs: &[Overrides]) -> Result<Vec<BatchRun<Report>>, Error> {
+        self.check_bindings()?;
+        let parameters = self.program.parameters();
+        for &(ref name, kind, ref value) in runs.iter().flatten() {
+            check_override(&parameters, name, kind, value)?;
+        }
+        let executable = &*self;
+        let chunk_size = runs.len().div_ceil(self.config.threads.max(1)).max(1);
+        let results: Vec<_> = thread::scope(|s| {
+            let handles: Vec<_> = runs
+                .chunks(chunk_size)
+                .map(|chunk| {
+                    s.spawn(move || {
+                        chunk
+                            .iter()
+                            .map(|x| executable.batch_run(x))
+                            .collect::<Vec<_>>()
+                    })
+                })
+                .collect();
+            handles
+                .into_iter()
+                .flat_map(|x| x.join().unwrap())
+                .collect()
+        });
+        results.into_iter().collect()
+    }
+
+    fn submit(&mut self) -> Result<Submission<Report, Error>, Error> {
+        self.check_bindings()?;
+        let executable = JitExecutable {
+            kernel: self.kernel.clone(),
+            program: self.program.clone(),
+            phases: self.phases.clone(),
+            binding: self.binding.clone(),
+            work_size: self.work_size,
+            config: self.config,
+            compile_time: self.compile_time,
+        };
+        let (locked, acquired) = mpsc::channel();
+        let submission = Submission::spawn(move || executable.run_locked(locked));
+        let _ = acquired.recv();
+        Ok(submission)
+    }
+
+    fn run_until<T: Type, F: FnMut(T) -> bool>(
+        &mut self,
+        flag: &str,
+        max_iterations: Option<u32>,
+        mut predicate: F,
+    ) -> Result<Convergence<Report>, Error> {
+        self.check_bindings()?;
+        let parameter = convergence_flag::<T>(&self.program.parameters(), flag)?;
+        let layout = &self.kernel.layout;
+        let (slot, ty) = match layout
+            .io
+            .iter()
+            .find(|x| x.0 == parameter.kind && x.1 == flag)
+        {
+            Some(&(_, _, SharedSlot::Scalar(x))) => (x, layout.scalars[x as usize]),
+            _ => return Err(Error::MissingBinding(parameter.kind, flag.to_string())),
+        };
+        let (mut iterations, mut converged) = (0, false);
+        let report = self.launch(|memory| {
+            iterations += 1;
+            let value = TokenValue::Scalar(from_bits(ty, memory.scalar(slot)));
+            converged = T::slice(&value).is_some_and(|x| predicate(x[0]));
+            converged || max_iterations.is_some_and(|x| iterations >= x)
+        })?;
+        Ok(Convergence {
+            iterations,
+            converged,
+            report,
+        })
+    }
+}
+
+impl JitExecutable {
+    fn launch<F: FnMut(&SharedMemory) -> bool>(&mut self, stop: F) -> Result<Report, Error> {
+        let start = Instant::now();
+        let (memory, uploaded) = self.load_shared(&[])?;
+        let mut report = self.execute(&memory, stop)?;
+        report.wall_time = start.elapsed();
+        report.uploaded = uploaded;
+        report.downloaded = self.store_shared(&memory)?;
+        Ok(report)
+    }
+
+    fn batch_run(&self, overrides: &[(String, IO, TokenValue)]) -> Result<BatchRun<Report>, Error> {
+        let start = Instant::now();
+        let (memory, uploaded) = self.load_shared(overrides)?;
+        let mut report = self.execute(&memory, |_| true)?;
+        let mut outputs = BTreeMap::new();
+        for (_, name, value) in self.read_outputs(&memory) {
+            report.downloaded += byte_size(&value);
+            outputs.insert(name.clone(), value);
+        }
+        report.wall_time = start.elapsed();
+        report.uploaded = uploaded;
+        Ok(BatchRun { outputs, report })
+    }
+
+    fn run_locked(&self, locked: Sender<()>) -> Result<Report, Error> {
+        let start = Instant::now();
+        let mut resources: Vec<_> = self.binding.values().collect();
+        resources.sort_by_key(|x| x.id);
+        resources.dedup_by_key(|x| x.id);
+        let mut data: Vec<_> = resources.iter().map(|x| x.data.lock().unwrap()).collect();
+        drop(locked);
+        let position = |resource: &JitResource| {
+            resources
+                .binary_search_by_key(&resource.id, |x| x.id)
+                .unwrap()
+        };
+        let values: Vec<_> = self
+            .binding
+            .iter()
+            .map(|(&(kind, ref name), x)| (name.clone(), kind, data[position(x)].clone()))
+            .collect();
+        let (memory, uploaded) = self.load_shared(&values)?;
+        let mut report = self.execute(&memory, |_| true)?;
+        for (kind, name, value) in self.read_outputs(&memory) {
+            report.downloaded += byte_size(&value);
+            *data[position(&self.binding[&(kind, name.clone())])] = value;
+        }
+        report.wall_time = start.elapsed();
+        report.uploaded = uploaded;
+        Ok(report)
+    }
+
+    fn execute<F: FnMut(&SharedMemory) -> bool>(
+        &self,
+        memory: &SharedMemory,
+        mut stop: F,
+    ) -> Result<Report, Error> {
+        let start = Instant::now();
+        let work_size = self.work_size();
+        let num_workers = work_size
+            .num_workers()
+            .ok_or_else(|| Error::Validation(format!("Invalid work size {:?}", work_size)))?;
+        let env = memory.env();
+        let error = Mutex::new(None);
+        let context = Context {
+            function: self.kernel.function,
+            program: &self.program,
+            env: &env,
+            memory,
+            private_size: self.kernel.layout.private_size,
+            num_workers,
+            error: &error,
+        };
+        let phases = self.phase_slots();
+        let mut run_time = Duration::default();
+        loop {
+            let run_start = Instant::now();
+            if self.kernel.layout.has_barrier && work_size.group_size > 1 {
+                self.run_groups(work_size, &context);
+            } else {
+                self.run_workers(&context);
+            }
+            run_time += run_start.elapsed();
+            if let Some(error) = error.lock().unwrap().take() {
+                return Err(error);
+            }
+            if let Some((current, next)) = phases {
+                let phase = next_phase(memory.array(next))?;
+                if phase != DONE {
+                    memory.store_scalar(current, phase);
+                    continue;
+                }
+                memory.store_scalar(current, 0);
+            }
+            if stop(memory) {
+                break;
+            }
+        }
+        Ok(Report {
+            wall_time: start.elapsed(),
+            compile_time: self.compile_time,
+            run_time,
+            work_size,
+            workers: num_workers,
+            uploaded: 0,
+            downloaded: 0,
+            op_counts: None,
+        })
+    }
+
+    fn phase_slots(&self) -> Option<(u32, u32)> {
+        self.phases.as_ref()?;
+        let slot = |kind: IO, name: &str| match self
+            .kernel
+            .layout
+            .io
+            .iter()
+            .find(|x| x.0 == kind && x.1 == name)
+        {
+            Some(&(_, _, slot)) => Some(slot),
+            _ => None,
+        };
+        match (slot(IO::Input, PHASE)?, slot(IO::Output, NEXT)?) {
+            (SharedSlot::Scalar(phase), SharedSlot::Array(next)) => Some((phase, next)),
+            _ => None,
+        }
+    }
+
+    fn load_shared(
+        &self,
+        overrides: &[(String, IO, TokenValue)],
+    ) -> Result<(SharedMemory, u64), Error> {
+        let layout = &self.kernel.layout;
+        let mut uploaded = 0;
+        let hidden = match self.phases {
+            Some(ref phases) => phases.initial(self.work_size().num_workers().unwrap_or(0)),
+            None => Vec::new(),
+        };
+        let mut memory = SharedMemory::new(layout);
+        for &(kind, ref name, slot) in &layout.io {
+            let value = match overrides
+                .iter()
+                .chain(&hidden)
+                .find(|x| x.1 == kind && x.0 == *name)
+            {
+                Some(x) => x.2.clone(),
+                None => self
+                    .binding
+                    .get(&(kind, name.clone()))
+                    .ok_or_else(|| Error::MissingBinding(kind, name.clone()))?
+                    .get_data(),
+            };
+            if !is_hidden(name) {
+                uploaded += byte_size(&value);
+            }
+            match (slot, get_token_type(&value), value) {
+                (SharedSlot::Scalar(x), TokenType::Variable(ty), TokenValue::Scalar(v))
+                    if ty == layout.scalars[x as usize] =>
+                {
+                    memory.set_scalar(x, to_bits(v));
+                }
+                (SharedSlot::Scalar(x), TokenType::Null, _) => memory.set_scalar(x, 0),
+                (SharedSlot::Array(x), TokenType::Array(ty), TokenValue::Vector(v))
+                    if ty == layout.arrays[x as usize].0 =>
+                {
+                    memory.set_array(x, vector_bits(&v));
+                }
+                (SharedSlot::Array(x), TokenType::Null, _) => memory.set_array(x, Vec::new()),
+                _ => return Err(Error::TypeMismatch(name.clone())),
+            }
+        }
+        Ok((memory, uploaded))
+    }
+
+    fn store_shared(&self, memory: &SharedMemory) -> Result<u64, Error> {
+        let mut downloaded = 0;
+        for (kind, name, value) in self.read_outputs(memory) {
+            downloaded += byte_size(&value);
+            self.binding[&(kind, name.clone())].set_data(value)?;
+        }
+        Ok(downloaded)
+    }
+
+    fn read_outputs(&self, memory: &SharedMemory) -> Vec<(IO, &String, TokenValue)> {
+        let layout = &self.kernel.layout;
+        layout
+            .io
+            .iter()
+            .filter(|x| x.0.writes() && !is_hidden(&x.1))
+            .map(|&(kind, ref name, slot)| {
+                let value = match slot {
+                    SharedSlot::Scalar(x) => {
+                        TokenValue::Scalar(from_bits(layout.scalars[x as usize], memory.scalar(x)))
+                    }
+                    SharedSlot::Array(x) => TokenValue::Vector(bits_vector(
+                        layout.arrays[x as usize].0,
+                        memory.array(x),
+                    )),
+                };
+                (kind, name, value)
+            })
+            .collect()
+    }
+
+    fn run_workers(&self, context: &Context) {
+        let next = AtomicU32::new(0);
+        thread::scope(|s| {
+            for _ in 0..self.config.threads.min(context.num_workers as usize) {
+                s.spawn(|| {
+                    let mut private = vec![0; context.private_size];
+                    loop {
+                        let worker_id = next.fetch_add(1, Ordering::Relaxed);
+                        if worker_id >= context.num_workers
+                            || context.memory.abort.load(Ordering::SeqCst)
+                        {
+                            break;
+                        }
+                        Self::run_worker(worker_id, &mut private, None, context);
+                    }
+                });
+            }
+        });
+    }
+
+    fn run_groups(&self, work_size: WorkSize, context: &Context) {
+        let next = AtomicU32::new(0);
+        let group_size = work_size.group_size;
+        let lanes = (self.config.threads / group_size as usize)
+            .max(1)
+            .min(work_size.groups as usize);
+        let lanes: Vec<_> = (0..lanes)
+            .map(|_| Lane {
+                barrier: GroupBarrier::new(group_size as usize),
+                handoff: Barrier::new(group_size as usize),
+                group: AtomicU32::new(0),
+            })
+            .collect();
+        thread::scope(|s| {
+            for lane in &lanes {
+                for local_id in 0..group_size {
+                    let next = &next;
+                    s.spawn(move || {
+                        let mut private = vec![0; context.private_size];
+                        loop {
+                            if lane.handoff.wait().is_leader() {
+                                let group = if context.memory.abort.load(Ordering::SeqCst) {
+                                    work_size.groups
+                                } else {
+                                    next.fetch_add(1, Ordering::Relaxed)
+                                };
+                                lane.barrier.reset(group_size as usize);
+                                lane.group.store(group, Ordering::SeqCst);
+                            }
+                            lane.handoff.wait();
+                            let group = lane.group.load(Ordering::SeqCst);
+                            if group >= work_size.groups {
+                                break;
+                            }
+                            let worker_id = group * group_size + local_id;
+                            Self::run_worker(worker_id, &mut private, Some(&lane.barrier), context);
+                        }
+                    });
+                }
+            }
+        });
+    }
+
+    fn run_worker(
+        worker_id: u32,
+        private: &mut [u32],
+        barrier: Option<&GroupBarrier>,
+        context: &Context,
+    ) {
+        let barrier_ptr = barrier.map_or(ptr::null(), |x| x as *const GroupBarrier);
+        let mut fault = Fault::default();
+        let status = unsafe {
+            (context.function)(
+                context.env,
+                private.as_mut_ptr(),
+                barrier_ptr,
+                &mut fault,
+                worker_id,
+                context.num_workers,
+            )
+        };
+        if let Some(barrier) = barrier {
+            barrier.retire();
+        }
+        if status != runtime::STATUS_OK {
+            let mut error = context.error.lock().unwrap();
+            if error.is_none() {
+                *error = Some(runtime::status_error(status, &fault, context.program));
+            }
+            context.memory.abort.store(true, Ordering::SeqCst);
+        }
+    }
+}
@@ -0,0 +1,81 @@
+// Copyright 2018 | Dario Ostuni <dario.ostuni@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use codegen;
+use executable::JitExecutable;
+use rand::{thread_rng, Rng};
+use resource::JitResource;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Instant;
+use wcore::error::Error;
+use wcore::executor::Executor;
+use wcore::program::{Program, TokenValue};
+use wcore::split::split;
+
+#[derive(Debug)]
+pub struct JitExecutor {
+    config: JitConfig,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct JitConfig {
+    pub threads: usize,
+}
+
+impl Default for JitConfig {
+    fn default() -> JitConfig {
+        JitConfig {
+            threads: thread::available_parallelism()
+                .map(|x| x.get())
+                .unwrap_or(1),
+        }
+    }
+}
+
+impl Executor for JitExecutor {
+    type Config = JitConfig;
+    type Error = Error;
+    type Resource = JitResource;
+    type Executable = JitExecutable;
+
+    fn new(config: JitConfig) -> Result<JitExecutor, Error> {
+        if config.threads == 0 {
+            return Err(Error::Validation("At least one thread is required".into()));
+        }
+        Ok(JitExecutor { config })
+    }
+
+    fn compile(&self, program: Program) -> Result<JitExecutable, Error> {
+        let start = Instant::now();
+        let (program, phases) = split(program)?;
+        Ok(JitExecutable {
+            kernel: Arc::new(codegen::compile(&program).map_err(Error::Compilation)?),
+            program,
+            phases,
+            binding: HashMap::new(),
+            work_size: None,
+            config: self.config,
+            compile_time: start.elapsed(),
+        })
+    }
+
+    fn new_resource(&self) -> Result<Arc<JitResource>, Error> {
+        Ok(Arc::new(JitResource {
+            id: thread_rng().gen(),
+            data: Mutex::new(TokenValue::Null),
+        }))
+    }
+}
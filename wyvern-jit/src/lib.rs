@@ -0,0 +1,29 @@
+// Copyright 2018 | Dario Ostuni <dario.ostuni@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+extern crate cranelift_codegen;
+extern crate cranelift_frontend;
+extern crate cranelift_jit;
+extern crate cranelift_module;
+extern crate cranelift_native;
+extern crate rand;
+extern crate wyvern_core as wcore;
+
+mod codegen;
+pub mod executable;
+pub mod executor;
+mod memory;
+pub mod resource;
+mod runtime;
+mod sync;
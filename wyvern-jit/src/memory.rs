@@ -0,0 +1,116 @@
+// Copyright 2018 | Dario Ostuni <dario.ostuni@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use codegen::Layout;
+use runtime::{ArrayDesc, SharedEnv};
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use wcore::program::{ConstantScalar, ConstantVector, DataType};
+
+#[derive(Debug)]
+pub(crate) struct SharedMemory {
+    scalars: Vec<AtomicU32>,
+    buffers: Vec<Vec<u32>>,
+    arrays: Vec<ArrayDesc>,
+    pub(crate) abort: AtomicBool,
+}
+
+pub(crate) fn to_bits(value: ConstantScalar) -> u32 {
+    match value {
+        ConstantScalar::Bool(x) => x as u32,
+        ConstantScalar::I32(x) => x as u32,
+        ConstantScalar::U32(x) => x,
+        ConstantScalar::F32(x) => x.to_bits(),
+    }
+}
+
+pub(crate) fn from_bits(ty: DataType, bits: u32) -> ConstantScalar {
+    match ty {
+        DataType::Bool => ConstantScalar::Bool(bits != 0),
+        DataType::I32 => ConstantScalar::I32(bits as i32),
+        DataType::U32 => ConstantScalar::U32(bits),
+        DataType::F32 => ConstantScalar::F32(f32::from_bits(bits)),
+    }
+}
+
+pub(crate) fn vector_bits(vector: &ConstantVector) -> Vec<u32> {
+    match *vector {
+        ConstantVector::Bool(ref x) => x.iter().map(|&v| v as u32).collect(),
+        ConstantVector::I32(ref x) => x.iter().map(|&v| v as u32).collect(),
+        ConstantVector::U32(ref x) => x.clone(),
+        ConstantVector::F32(ref x) => x.iter().map(|v| v.to_bits()).collect(),
+    }
+}
+
+pub(crate) fn bits_vector(ty: DataType, bits: &[u32]) -> ConstantVector {
+    let bits = bits.iter().cloned();
+    match ty {
+        DataType::Bool => ConstantVector::Bool(bits.map(|x| x != 0).collect()),
+        DataType::I32 => ConstantVector::I32(bits.map(|x| x as i32).collect()),
+        DataType::U32 => ConstantVector::U32(bits.collect()),
+        DataType::F32 => ConstantVector::F32(bits.map(f32::from_bits).collect()),
+    }
+}
+
+impl SharedMemory {
+    pub(crate) fn new(layout: &Layout) -> SharedMemory {
+        let mut memory = SharedMemory {
+            scalars: layout.scalars.iter().map(|_| AtomicU32::new(0)).collect(),
+            buffers: Vec::new(),
+            arrays: Vec::new(),
+            abort: AtomicBool::new(false),
+        };
+        for &(_, max_size) in &layout.arrays {
+            memory.buffers.push(vec![0; max_size as usize]);
+            memory.arrays.push(ArrayDesc {
+                data: memory.buffers.last_mut().unwrap().as_mut_ptr(),
+                capacity: max_size,
+                len: AtomicU32::new(0),
+            });
+        }
+        memory
+    }
+
+    pub(crate) fn env(&self) -> SharedEnv {
+        SharedEnv {
+            scalars: self.scalars.as_ptr(),
+            arrays: self.arrays.as_ptr(),
+            abort: &self.abort,
+        }
+    }
+
+    pub(crate) fn scalar(&self, slot: u32) -> u32 {
+        self.scalars[slot as usize].load(Ordering::SeqCst)
+    }
+
+    pub(crate) fn store_scalar(&self, slot: u32, bits: u32) {
+        self.scalars[slot as usize].store(bits, Ordering::SeqCst);
+    }
+
+    pub(crate) fn set_scalar(&mut self, slot: u32, bits: u32) {
+        self.scalars[slot as usize] = AtomicU32::new(bits);
+    }
+
+    pub(crate) fn array(&self, slot: u32) -> &[u32] {
+        let len = self.arrays[slot as usize].len.load(Ordering::SeqCst);
+        &self.buffers[slot as usize][..len as usize]
+    }
+
+    pub(crate) fn set_array(&mut self, slot: u32, mut bits: Vec<u32>) {
+        let desc = &mut self.arrays[slot as usize];
+        desc.data = bits.as_mut_ptr();
+        desc.capacity = bits.len() as u32;
+        desc.len = AtomicU32::new(bits.len() as u32);
+        self.buffers[slot as usize] = bits;
+    }
+}
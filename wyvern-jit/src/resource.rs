@@ -0,0 +1,93 @@
+// Copyright 2018 | Dario Ostuni <dario.ostuni@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::hash::{Hash, Hasher};
+use std::sync::Mutex;
+use wcore::error::Error;
+use wcore::executor::{access_error, Resource};
+use wcore::program::{get_token_type, TokenType, TokenValue};
+use wcore::types::Type;
+
+#[derive(Debug)]
+pub struct JitResource {
+    pub(crate) id: u64,
+    pub(crate) data: Mutex<TokenValue>,
+}
+
+impl Resource for JitResource {
+    fn clear(&self) {
+        *self.data.lock().unwrap() = TokenValue::Null;
+    }
+
+    fn token_type(&self) -> TokenType {
+        get_token_type(&self.data.lock().unwrap())
+    }
+
+    fn get_data(&self) -> TokenValue {
+        self.data.lock().unwrap().clone()
+    }
+
+    fn set_data(&self, value: TokenValue) -> Result<(), Error> {
+        *self.data.lock().unwrap() = value;
+        Ok(())
+    }
+
+    fn map<T: Type, R, F: FnOnce(&[T]) -> R>(&self, f: F) -> Result<R, Error> {
+        let data = self.data.lock().unwrap();
+        match T::slice(&data) {
+            Some(x) => Ok(f(x)),
+            None => Err(access_error::<T>(get_token_type(&data))),
+        }
+    }
+
+    fn map_mut<T: Type, R, F: FnOnce(&mut [T]) -> R>(&self, f: F) -> Result<R, Error> {
+        let mut data = self.data.lock().unwrap();
+        match T::slice_mut(&mut data) {
+            Some(x) => Ok(f(x)),
+            None => Err(access_error::<T>(get_token_type(&data))),
+        }
+    }
+
+    fn resize<T: Type>(&self, len: usize) -> Result<(), Error> {
+        let mut data = self.data.lock().unwrap();
+        match T::vec_mut(&mut data) {
+            Some(x) => x.resize(len, T::default()),
+            None => *data = TokenValue::Vector(T::vector(vec![T::default(); len])),
+        }
+        Ok(())
+    }
+
+    fn reserve<T: Type>(&self, capacity: usize) -> Result<(), Error> {
+        let mut data = self.data.lock().unwrap();
+        match T::vec_mut(&mut data) {
+            Some(x) => x.reserve(capacity.saturating_sub(x.len())),
+            None => *data = TokenValue::Vector(T::vector(Vec::with_capacity(capacity))),
+        }
+        Ok(())
+    }
+}
+
+impl PartialEq for JitResource {
+    fn eq(&self, other: &JitResource) -> bool {
+        self.id == other.id
+    }
+}
+
+impl Eq for JitResource {}
+
+impl Hash for JitResource {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.id.hash(state);
+    }
+}
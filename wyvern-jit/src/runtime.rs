@@ -0,0 +1,98 @@
+// Copyright 2018 | Dario Ostuni <dario.ostuni@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::atomic::{AtomicBool, AtomicU32};
+use sync::GroupBarrier;
+use wcore::error::Error;
+use wcore::program::{Op, Program};
+
+pub(crate) const STATUS_OK: u32 = 0;
+pub(crate) const STATUS_OUT_OF_BOUNDS: u32 = 1;
+pub(crate) const STATUS_ABORTED: u32 = 2;
+pub(crate) const STATUS_INVALID_PHI: u32 = 3;
+pub(crate) const STATUS_TOO_LARGE: u32 = 4;
+
+pub(crate) type KernelFn = unsafe extern "C" fn(
+    *const SharedEnv,
+    *mut u32,
+    *const GroupBarrier,
+    *mut Fault,
+    u32,
+    u32,
+) -> u32;
+
+#[repr(C)]
+#[derive(Debug)]
+pub(crate) struct SharedEnv {
+    pub(crate) scalars: *const AtomicU32,
+    pub(crate) arrays: *const ArrayDesc,
+    pub(crate) abort: *const AtomicBool,
+}
+
+#[repr(C)]
+#[derive(Debug, Default)]
+pub(crate) struct Fault {
+    pub(crate) op: u32,
+    pub(crate) value: u32,
+    pub(crate) limit: u32,
+}
+
+#[repr(C)]
+#[derive(Debug)]
+pub(crate) struct ArrayDesc {
+    pub(crate) data: *mut u32,
+    pub(crate) capacity: u32,
+    pub(crate) len: AtomicU32,
+}
+
+unsafe impl Sync for SharedEnv {}
+unsafe impl Sync for ArrayDesc {}
+
+pub(crate) extern "C" fn wyvern_barrier(barrier: *const GroupBarrier) {
+    if let Some(barrier) = unsafe { barrier.as_ref() } {
+        barrier.wait();
+    }
+}
+
+pub(crate) extern "C" fn wyvern_fmodf(a: f32, b: f32) -> f32 {
+    a % b
+}
+
+pub(crate) fn status_error(status: u32, fault: &Fault, program: &Program) -> Error {
+    let id = fault.op;
+    match (status, program.op(id as usize)) {
+        (STATUS_OUT_OF_BOUNDS, Some(op @ &Op::ArrayLoad(_, v, _)))
+        | (STATUS_OUT_OF_BOUNDS, Some(op @ &Op::ArrayStore(v, _, _))) => {
+            Error::OutOfBounds(format!(
+                "Index {} out of bounds for {:?} of length {} at op #{} ({:?})",
+                fault.value, v, fault.limit, id, op
+            ))
+        }
+        (STATUS_TOO_LARGE, Some(op @ &Op::ArrayNew(v, _, _, _, _))) => {
+            Error::OutOfBounds(format!(
+                "Size {} exceeds the max size {} of {:?} at op #{} ({:?})",
+                fault.value, fault.limit, v, id, op
+            ))
+        }
+        (STATUS_ABORTED, Some(&Op::While(_, _, cond, _, _, _))) => {
+            Error::Execution(format!("Aborted at op #{} (While on {:?})", id, cond))
+        }
+        (STATUS_ABORTED, _) => Error::Execution(format!("Aborted at op #{}", id)),
+        (STATUS_INVALID_PHI, Some(op)) => Error::Execution(format!(
+            "Phi reached from an unknown label at op #{} ({:?})",
+            id, op
+        )),
+        (x, _) => Error::Execution(format!("Unknown kernel status {} at op #{}", x, id)),
+    }
+}
@@ -0,0 +1,77 @@
+// Copyright 2018 | Dario Ostuni <dario.ostuni@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::{Condvar, Mutex};
+
+#[derive(Debug)]
+pub(crate) struct GroupBarrier {
+    state: Mutex<BarrierState>,
+    condvar: Condvar,
+}
+
+#[derive(Debug)]
+struct BarrierState {
+    active: usize,
+    waiting: usize,
+    generation: u64,
+}
+
+impl GroupBarrier {
+    pub(crate) fn new(workers: usize) -> GroupBarrier {
+        GroupBarrier {
+            state: Mutex::new(BarrierState {
+                active: workers,
+                waiting: 0,
+                generation: 0,
+            }),
+            condvar: Condvar::new(),
+        }
+    }
+
+    pub(crate) fn reset(&self, workers: usize) {
+        let mut state = self.state.lock().unwrap();
+        state.active = workers;
+        state.waiting = 0;
+    }
+
+    pub(crate) fn wait(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.waiting += 1;
+        if state.waiting >= state.active {
+            state.release();
+            self.condvar.notify_all();
+        } else {
+            let generation = state.generation;
+            while state.generation == generation {
+                state = self.condvar.wait(state).unwrap();
+            }
+        }
+    }
+
+    pub(crate) fn retire(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.active -= 1;
+        if state.waiting > 0 && state.waiting >= state.active {
+            state.release();
+            self.condvar.notify_all();
+        }
+    }
+}
+
+impl BarrierState {
+    fn release(&mut self) {
+        self.waiting = 0;
+        self.generation += 1;
+    }
+}
@@ -0,0 +1,145 @@
+// Copyright 2018 | Dario Ostuni <dario.ostuni@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+extern crate wyvern_core as wcore;
+extern crate wyvern_jit;
+
+use wcore::conformance;
+use wcore::executor::Executor;
+use wyvern_jit::executor::{JitConfig, JitExecutor};
+
+fn executors() -> Vec<JitExecutor> {
+    vec![JitConfig { threads: 1 }, JitConfig { threads: 4 }]
+        .into_iter()
+        .map(|x| JitExecutor::new(x).unwrap())
+        .collect()
+}
+
+#[test]
+fn work_size() {
+    for executor in executors() {
+        conformance::work_size(&executor);
+    }
+}
+
+#[test]
+fn control_flow() {
+    for executor in executors() {
+        conformance::control_flow(&executor);
+    }
+}
+
+#[test]
+fn global_barrier() {
+    for executor in executors() {
+        conformance::global_barrier(&executor);
+    }
+}
+
+#[test]
+fn faults() {
+    for executor in executors() {
+        conformance::faults(&executor);
+    }
+}
+
+#[test]
+fn semantics() {
+    for executor in executors() {
+        conformance::semantics(&executor);
+    }
+}
+
+#[test]
+fn report() {
+    for executor in executors() {
+        conformance::report(&executor);
+    }
+}
+
+#[test]
+fn errors() {
+    for executor in executors() {
+        conformance::errors(&executor);
+    }
+}
+
+#[test]
+fn inout() {
+    for executor in executors() {
+        conformance::inout(&executor);
+    }
+}
+
+#[test]
+fn parameters() {
+    for executor in executors() {
+        conformance::parameters(&executor);
+    }
+}
+
+#[test]
+fn null_inputs() {
+    for executor in executors() {
+        conformance::null_inputs(&executor);
+    }
+}
+
+#[test]
+fn typed_access() {
+    for executor in executors() {
+        conformance::typed_access(&executor);
+    }
+}
+
+#[test]
+fn updates() {
+    for executor in executors() {
+        conformance::updates(&executor);
+    }
+}
+
+#[test]
+fn pipeline() {
+    for executor in executors() {
+        conformance::pipeline(&executor);
+    }
+}
+
+#[test]
+fn run_until() {
+    for executor in executors() {
+        conformance::run_until(&executor);
+    }
+}
+
+#[test]
+fn batch() {
+    for executor in executors() {
+        conformance::batch(&executor);
+    }
+}
+
+#[test]
+fn submit() {
+    for executor in executors() {
+        conformance::submit(&executor);
+    }
+}
+
+#[test]
+fn group_barrier() {
+    for executor in executors() {
+        conformance::group_barrier(&executor);
+    }
+}
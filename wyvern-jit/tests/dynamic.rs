@@ -0,0 +1,123 @@
+// Copyright 2018 | Dario Ostuni <dario.ostuni@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+extern crate wyvern_core as wcore;
+extern crate wyvern_cpu;
+extern crate wyvern_jit;
+
+use wcore::builder::ProgramBuilder;
+use wcore::dynamic::{DynExecutor, Registry};
+use wcore::error::Error;
+use wcore::executor::{WorkSize, IO};
+use wcore::program::{ConstantScalar, ConstantVector, Program, TokenValue};
+use wcore::types::{Array, Constant, Variable};
+use wyvern_cpu::executor::{CpuConfig, CpuExecutor, CpuMode};
+use wyvern_jit::executor::{JitConfig, JitExecutor};
+
+fn registry() -> Registry {
+    let mut registry = Registry::new();
+    registry.register_executor::<CpuExecutor, _>("cpu", CpuConfig::default());
+    registry.register_executor::<CpuExecutor, _>(
+        "cpu-parallel",
+        CpuConfig::default().mode(CpuMode::Parallel).threads(4),
+    );
+    registry.register_executor::<JitExecutor, _>("jit", JitConfig { threads: 4 });
+    registry.register("broken", || {
+        Err(Error::Device("No device available".into()))
+    });
+    registry
+}
+
+fn program() -> Program {
+    let b = ProgramBuilder::new();
+    let zero = Constant::new(0_u32, &b);
+    let k = Variable::<u32>::new(&b).mark_as_input("k");
+    let out = Array::<u32>::new(zero, 0, true, &b).mark_as_inout("out");
+    let id = b.worker_id();
+    out.at(id).store(id * k.load());
+    b.finalize().unwrap()
+}
+
+fn run(executor: &dyn DynExecutor) -> Result<TokenValue, Error> {
+    let mut executable = executor.compile(program())?;
+    let (k, out) = (executor.new_resource()?, executor.new_resource()?);
+    k.set_data(TokenValue::Scalar(ConstantScalar::U32(3)))?;
+    out.set_data(TokenValue::Vector(ConstantVector::U32(vec![0; 6])))?;
+    executable.bind("k", IO::Input, k)?;
+    executable.bind("out", IO::InOut, out.clone())?;
+    executable.set_work_size(Some(WorkSize::new(2, 3)));
+    let report = executable.run()?;
+    assert_eq!(report.workers, 6);
+    Ok(out.get_data())
+}
+
+#[test]
+fn registry_selects_backends_by_name() {
+    let registry = registry();
+    assert_eq!(
+        registry.names(),
+        vec!["broken", "cpu", "cpu-parallel", "jit"]
+    );
+    for name in &["cpu", "cpu-parallel", "jit"] {
+        let executor = registry.create(name).unwrap();
+        assert_eq!(
+            run(&*executor).unwrap(),
+            TokenValue::Vector(ConstantVector::U32(vec![0, 3, 6, 9, 12, 15])),
+            "{}",
+            name
+        );
+    }
+    match registry.create("broken") {
+        Err(Error::Device(message)) => assert_eq!(message, "No device available"),
+        Err(e) => panic!("Expected a device error, got {:?}", e),
+        Ok(_) => panic!("Expected the broken backend to fail"),
+    }
+    match registry.create("vulkan") {
+        Err(Error::Validation(message)) => assert_eq!(
+            message,
+            "Unknown backend vulkan, available backends are [\"broken\", \"cpu\", \"cpu-parallel\", \"jit\"]"
+        ),
+        Err(e) => panic!("Expected a validation error, got {:?}", e),
+        Ok(_) => panic!("Expected the vulkan backend to be unknown"),
+    }
+}
+
+#[test]
+fn resources_stay_with_their_backend() {
+    let registry = registry();
+    let (cpu, jit) = (
+        registry.create("cpu").unwrap(),
+        registry.create("jit").unwrap(),
+    );
+    let mut executable = jit.compile(program()).unwrap();
+    let k = cpu.new_resource().unwrap();
+    k.set_data(TokenValue::Scalar(ConstantScalar::U32(3)))
+        .unwrap();
+    match executable.bind("k", IO::Input, k) {
+        Err(Error::Validation(message)) => {
+            assert_eq!(message, "Resource bound to k belongs to another backend")
+        }
+        Err(e) => panic!("Expected a validation error, got {:?}", e),
+        Ok(_) => panic!("Expected the CPU resource to be rejected"),
+    }
+    let k = jit.new_resource().unwrap();
+    k.set_data(TokenValue::Scalar(ConstantScalar::U32(3)))
+        .unwrap();
+    assert!(executable.bind("k", IO::Input, k).unwrap().is_none());
+    assert!(executable.binding("k", IO::Input).is_some());
+    assert!(executable.unbind("k", IO::Input).is_some());
+    match executable.check_bindings() {
+        Err(Error::MissingBinding(IO::Input, ref name)) if name == "k" => {}
+        x => panic!("Expected a missing binding, got {:?}", x),
+    }
+}
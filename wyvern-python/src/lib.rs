@@ -2,15 +2,21 @@ extern crate pyo3;
 extern crate serde_json;
 extern crate wyvern;
 
+use pyo3::exceptions::{PyRuntimeError, PyTypeError, PyValueError};
 use pyo3::prelude::*;
 use std::sync::Arc;
+use wyvern::core::error::Error;
 use wyvern::core::executor::{Executable, Executor, Resource, IO};
 use wyvern::core::program::Program;
 use wyvern::core::program::{ConstantScalar, ConstantVector, TokenValue};
 use wyvern::vk::executable::VkExecutable;
-use wyvern::vk::executor::VkExecutor;
+use wyvern::vk::executor::{VkConfig, VkExecutor};
 use wyvern::vk::resource::VkResource;
 
+fn to_py(error: Error) -> PyErr {
+    PyRuntimeError::new_err(error.to_string())
+}
+
 #[pyclass]
 struct WyVkExecutor {
     data: VkExecutor,
@@ -26,49 +32,63 @@ struct WyVkResource {
     data: Arc<VkResource>,
 }
 
+#[pyclass]
+struct WyParameter {
+    #[pyo3(get)]
+    name: String,
+    #[pyo3(get)]
+    kind: String,
+    #[pyo3(get)]
+    data_type: String,
+    #[pyo3(get)]
+    array: bool,
+    #[pyo3(get)]
+    max_size: Option<u32>,
+}
+
 #[pymethods]
 impl WyVkResource {
     fn set_data_uint32(&self, value: u32) -> PyResult<()> {
         self.data
-            .set_data(TokenValue::Scalar(ConstantScalar::U32(value)));
-        Ok(())
+            .set_data(TokenValue::Scalar(ConstantScalar::U32(value)))
+            .map_err(to_py)
     }
 
     fn set_data_int32(&self, value: i32) -> PyResult<()> {
         self.data
-            .set_data(TokenValue::Scalar(ConstantScalar::I32(value)));
-        Ok(())
+            .set_data(TokenValue::Scalar(ConstantScalar::I32(value)))
+            .map_err(to_py)
     }
 
     fn set_data_float32(&self, value: f32) -> PyResult<()> {
         self.data
-            .set_data(TokenValue::Scalar(ConstantScalar::F32(value)));
-        Ok(())
+            .set_data(TokenValue::Scalar(ConstantScalar::F32(value)))
+            .map_err(to_py)
     }
 
     fn set_data_array_uint32(&self, value: Vec<u32>) -> PyResult<()> {
         self.data
-            .set_data(TokenValue::Vector(ConstantVector::U32(value)));
-        Ok(())
+            .set_data(TokenValue::Vector(ConstantVector::U32(value)))
+            .map_err(to_py)
     }
 
     fn set_data_array_int32(&self, value: Vec<i32>) -> PyResult<()> {
         self.data
-            .set_data(TokenValue::Vector(ConstantVector::I32(value)));
-        Ok(())
+            .set_data(TokenValue::Vector(ConstantVector::I32(value)))
+            .map_err(to_py)
     }
 
     fn set_data_array_float32(&self, value: Vec<f32>) -> PyResult<()> {
         self.data
-            .set_data(TokenValue::Vector(ConstantVector::F32(value)));
-        Ok(())
+            .set_data(TokenValue::Vector(ConstantVector::F32(value)))
+            .map_err(to_py)
     }
 
     fn get_data_uint32(&self) -> PyResult<u32> {
         if let TokenValue::Scalar(ConstantScalar::U32(value)) = self.data.get_data() {
             Ok(value)
         } else {
-            panic!("Wrong type requested!");
+            Err(PyTypeError::new_err("Wrong type requested!"))
         }
     }
 
@@ -76,7 +96,7 @@ impl WyVkResource {
         if let TokenValue::Scalar(ConstantScalar::I32(value)) = self.data.get_data() {
             Ok(value)
         } else {
-            panic!("Wrong type requested!");
+            Err(PyTypeError::new_err("Wrong type requested!"))
         }
     }
 
@@ -84,7 +104,7 @@ impl WyVkResource {
         if let TokenValue::Scalar(ConstantScalar::F32(value)) = self.data.get_data() {
             Ok(value)
         } else {
-            panic!("Wrong type requested!");
+            Err(PyTypeError::new_err("Wrong type requested!"))
         }
     }
 
@@ -92,7 +112,7 @@ impl WyVkResource {
         if let TokenValue::Vector(ConstantVector::U32(value)) = self.data.get_data() {
             Ok(value)
         } else {
-            panic!("Wrong type requested!");
+            Err(PyTypeError::new_err("Wrong type requested!"))
         }
     }
 
@@ -100,7 +120,7 @@ impl WyVkResource {
         if let TokenValue::Vector(ConstantVector::I32(value)) = self.data.get_data() {
             Ok(value)
         } else {
-            panic!("Wrong type requested!");
+            Err(PyTypeError::new_err("Wrong type requested!"))
         }
     }
 
@@ -108,7 +128,7 @@ impl WyVkResource {
         if let TokenValue::Vector(ConstantVector::F32(value)) = self.data.get_data() {
             Ok(value)
         } else {
-            panic!("Wrong type requested!");
+            Err(PyTypeError::new_err("Wrong type requested!"))
         }
     }
 }
@@ -120,10 +140,12 @@ impl WyVkExecutable {
             IO::Input
         } else if kind == "output" {
             IO::Output
+        } else if kind == "inout" {
+            IO::InOut
         } else {
-            unreachable!();
+            return Err(PyValueError::new_err(format!("Invalid kind {}", kind)));
         };
-        self.data.bind(name, kind, resource.data.clone());
+        self.data.bind(name, kind, resource.data.clone()).map_err(to_py)?;
         Ok(())
     }
 
@@ -132,15 +154,31 @@ impl WyVkExecutable {
             IO::Input
         } else if kind == "output" {
             IO::Output
+        } else if kind == "inout" {
+            IO::InOut
         } else {
-            unreachable!();
+            return Err(PyValueError::new_err(format!("Invalid kind {}", kind)));
         };
         self.data.unbind(name, kind);
         Ok(())
     }
 
+    fn parameters(&self) -> Vec<WyParameter> {
+        self.data
+            .parameters()
+            .into_iter()
+            .map(|x| WyParameter {
+                name: x.name,
+                kind: x.kind.to_string(),
+                data_type: format!("{:?}", x.data_type),
+                array: x.array,
+                max_size: x.max_size,
+            })
+            .collect()
+    }
+
     fn run(&mut self) -> PyResult<()> {
-        self.data.run().unwrap();
+        self.data.run().map_err(to_py)?;
         Ok(())
     }
 }
@@ -151,20 +189,23 @@ impl WyVkExecutor {
     #[new]
     fn __new__() -> PyResult<WyVkExecutor> {
         Ok(WyVkExecutor {
-            data: VkExecutor::new(Default::default()).unwrap(),
+            data: VkConfig::from_env()
+                .and_then(VkExecutor::new)
+                .map_err(to_py)?,
         })
     }
 
     fn compile(&self, program: String) -> PyResult<WyVkExecutable> {
-        let program: Program = serde_json::from_str(&program).unwrap();
+        let program: Program =
+            serde_json::from_str(&program).map_err(|x| PyValueError::new_err(x.to_string()))?;
         Ok(WyVkExecutable {
-            data: self.data.compile(program).unwrap(),
+            data: self.data.compile(program).map_err(to_py)?,
         })
     }
 
     fn newResource(&self) -> PyResult<WyVkResource> {
         Ok(WyVkResource {
-            data: self.data.new_resource().unwrap(),
+            data: self.data.new_resource().map_err(to_py)?,
         })
     }
 }
@@ -174,5 +215,6 @@ fn pywyvern(_py: Python, m: &PyModule) -> PyResult<()> {
     m.add_class::<WyVkExecutor>()?;
     m.add_class::<WyVkExecutable>()?;
     m.add_class::<WyVkResource>()?;
+    m.add_class::<WyParameter>()?;
     Ok(())
 }
@@ -28,6 +28,7 @@ class ProgramBuilder:
         self._program["storage"] = {}
         self._program["input"] = {}
         self._program["output"] = {}
+        self._program["inout"] = {}
         self._label_id = 1
         self._token_id = 0
         self._stack = [[]]
@@ -151,7 +152,7 @@ class Context:
         tid = self._new_variable(ty, name)
         if io_type == IoType.private:
             pass
-        elif io_type in (IoType.input, IoType.output):
+        elif io_type in (IoType.input, IoType.output, IoType.inout):
             self._builder._program[io_type.value][name] = tid
         else:
             raise UnreachableError
@@ -167,7 +168,7 @@ class Context:
             "ArrayNew": [tid, size._tid, ty.value,
                          max_size, io_type != IoType.private]
         })
-        if io_type in (IoType.input, IoType.output):
+        if io_type in (IoType.input, IoType.output, IoType.inout):
             array_type = "SharedArray"
             self._builder._program[io_type.value][name] = tid
         else:
@@ -28,6 +28,7 @@ class DataType(Enum):
 class IoType(Enum):
     input = "input"
     output = "output"
+    inout = "inout"
     private = "private"
 
 
@@ -24,7 +24,15 @@ fn main() {
     let builder = ProgramBuilder::new();
     program(&builder);
     let p = builder.finalize().unwrap();
-    write("foo.spv", u32tou8(&generate(&p, VkVersion::Vulkan11).unwrap().0)).unwrap();
+    write(
+        "foo.spv",
+        u32tou8(
+            &generate(&p, VkVersion::Vulkan11, 128, false, None)
+                .unwrap()
+                .0,
+        ),
+    )
+    .unwrap();
 }
 
 fn program(builder: &ProgramBuilder) {
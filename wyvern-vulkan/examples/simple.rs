@@ -18,12 +18,12 @@ fn main() {
     let in1 = executor.new_resource().unwrap();
     let in2 = executor.new_resource().unwrap();
     let out = executor.new_resource().unwrap();
-    in1.set_data(TokenValue::Vector(ConstantVector::U32(vec![2; 10000])));
-    in2.set_data(TokenValue::Scalar(ConstantScalar::U32(3)));
-    out.set_data(TokenValue::Scalar(ConstantScalar::U32(0)));
-    executable.bind("out", IO::Output, out.clone());
-    executable.bind("in1", IO::Input, in1.clone());
-    executable.bind("in2", IO::Input, in2.clone());
+    in1.set_data(TokenValue::Vector(ConstantVector::U32(vec![2; 10000]))).unwrap();
+    in2.set_data(TokenValue::Scalar(ConstantScalar::U32(3))).unwrap();
+    out.set_data(TokenValue::Scalar(ConstantScalar::U32(0))).unwrap();
+    executable.bind("out", IO::Output, out.clone()).unwrap();
+    executable.bind("in1", IO::Input, in1.clone()).unwrap();
+    executable.bind("in2", IO::Input, in2.clone()).unwrap();
     executable.run().unwrap();
     println!("{:?}", out.get_data());
 }
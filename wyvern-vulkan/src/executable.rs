@@ -12,54 +12,67 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use executor::ModuleLayout;
+use executor::{
+    build_pipeline, create_resource, device_error, ModuleLayout, VkConfig, VkPipeline, WorkLimits,
+};
 use generator::{BindType, Binding, VkVersion};
 use resource::ResourceType;
 use resource::VkResource;
+use std::collections::{BTreeMap, HashMap};
 use std::iter::empty;
-use std::mem::swap;
+use std::mem::{replace, swap};
+use std::sync::mpsc;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use vulkano::buffer::cpu_access::CpuAccessibleBuffer;
 use vulkano::buffer::BufferAccess;
 use vulkano::buffer::BufferUsage;
 use vulkano::command_buffer::AutoCommandBufferBuilder;
 use vulkano::descriptor::descriptor::DescriptorDesc;
 use vulkano::descriptor::descriptor_set::DescriptorWrite;
-use vulkano::descriptor::descriptor_set::UnsafeDescriptorPool;
 use vulkano::descriptor::descriptor_set::UnsafeDescriptorSet;
 use vulkano::descriptor::descriptor_set::UnsafeDescriptorSetLayout;
 use vulkano::descriptor::descriptor_set::{DescriptorSet, DescriptorSetDesc};
-use vulkano::descriptor::pipeline_layout::PipelineLayout;
+use vulkano::descriptor::descriptor_set::{DescriptorsCount, UnsafeDescriptorPool};
 use vulkano::descriptor::pipeline_layout::PipelineLayoutDesc;
 use vulkano::device::DeviceOwned;
 use vulkano::device::{Device, Queue};
 use vulkano::image::ImageViewAccess;
 use vulkano::pipeline::shader::ShaderModule;
-use vulkano::pipeline::ComputePipeline;
 use vulkano::sync::now;
 use vulkano::sync::GpuFuture;
-use wcore::executor::{Executable, IO};
-use wcore::program::{DataType, Program};
+use wcore::error::Error;
+use wcore::executor::{
+    check_binding, check_bindings, check_override, check_parameter, convergence_flag, BatchRun,
+    Convergence, Executable, Overrides, Report, Resource, WorkSize, IO,
+};
+use wcore::pipeline::Pipeline;
+use wcore::program::{ConstantScalar, DataType, Op, Parameter, Program, TokenValue};
+use wcore::split::{is_hidden, next_phase, Phases, DONE, NEXT, PHASE};
+use wcore::submission::Submission;
+use wcore::types::Type;
 
 pub struct VkExecutable {
-    #[allow(dead_code)]
-    pub(crate) module: Arc<ShaderModule>,
-    #[allow(dead_code)]
     pub(crate) program: Program,
+    pub(crate) phases: Option<Phases>,
     pub(crate) bindings: Vec<Binding>,
     pub(crate) assoc: Vec<Option<Arc<VkResource>>>,
     pub(crate) pool: UnsafeDescriptorPool,
-    pub(crate) pipeline: Arc<ComputePipeline<PipelineLayout<ModuleLayout>>>,
+    pub(crate) pipelines: HashMap<u32, (Arc<ShaderModule>, VkPipeline)>,
     pub(crate) layout: ModuleLayout,
     pub(crate) device: Arc<Device>,
     pub(crate) queue: Arc<Queue>,
     pub(crate) version: VkVersion,
-    pub(crate) work_size: u32,
+    pub(crate) limits: WorkLimits,
+    pub(crate) config: VkConfig,
+    pub(crate) default_work_size: WorkSize,
+    pub(crate) work_size: Option<WorkSize>,
+    pub(crate) compile_time: Duration,
 }
 
 impl Executable for VkExecutable {
-    type Error = String;
-    type Report = String;
+    type Error = Error;
+    type Report = Report;
     type Resource = VkResource;
 
     fn bind<S: ToString>(
@@ -67,19 +80,20 @@ impl Executable for VkExecutable {
         name: S,
         kind: IO,
         resource: Arc<VkResource>,
-    ) -> Option<Arc<Self::Resource>> {
+    ) -> Result<Option<Arc<Self::Resource>>, Error> {
         let name = name.to_string();
+        check_binding(&self.program, &name, kind, &*resource)?;
         for b in &self.bindings {
             match b.1 {
                 BindType::Public(k, ref n) if k == kind && *n == name => {
                     let mut result = Some(resource);
                     swap(&mut result, &mut self.assoc[b.0 as usize]);
-                    return result;
+                    return Ok(result);
                 }
                 _ => {}
             }
         }
-        unreachable!();
+        Err(Error::MissingBinding(kind, name))
     }
 
     fn unbind<S: ToString>(&mut self, name: S, kind: IO) -> Option<Arc<Self::Resource>> {
@@ -97,19 +111,295 @@ impl Executable for VkExecutable {
         None
     }
 
-    fn run(&mut self) -> Result<String, String> {
+    fn binding<S: ToString>(&self, name: S, kind: IO) -> Option<Arc<Self::Resource>> {
+        let name = name.to_string();
+        self.bindings
+            .iter()
+            .find(|x| match x.1 {
+                BindType::Public(k, ref n) => k == kind && *n == name,
+                _ => false,
+            })
+            .and_then(|x| self.assoc[x.0 as usize].clone())
+    }
+
+    fn parameters(&self) -> Vec<Parameter> {
+        self.program.parameters()
+    }
+
+    fn check_bindings(&self) -> Result<(), Error> {
+        check_bindings(&self.program, |kind, name| self.binding(name, kind))
+    }
+
+    fn work_size(&self) -> WorkSize {
+        self.work_size.unwrap_or(self.default_work_size)
+    }
+
+    fn set_work_size(&mut self, work_size: Option<WorkSize>) {
+        self.work_size = work_size;
+    }
+
+    fn run(&mut self) -> Result<Report, Error> {
+        let (dispatch, pipeline, set) = self.prepare(None)?;
+        let set = Arc::new(set);
+        let run_time = execute(&self.queue, &pipeline, &set, &dispatch)?;
+        drop(set);
+        self.finish(dispatch, run_time)
+    }
+
+    fn run_pipeline(pipeline: &mut Pipeline<VkExecutable>) -> Result<Vec<Report>, Error> {
+        if pipeline.is_empty() {
+            return Ok(Vec::new());
+        }
+        let device = pipeline.node(0).device.clone();
+        let queue = pipeline.node(0).queue.clone();
+        if pipeline
+            .nodes()
+            .iter()
+            .any(|x| !Arc::ptr_eq(&x.device, &device))
+        {
+            return Err(Error::Validation(
+                "Pipeline nodes belong to different devices".into(),
+            ));
+        }
+        let schedule = pipeline.stages().concat();
+        if pipeline.nodes().iter().any(|x| x.phases.is_some()) {
+            let mut reports: Vec<_> = (0..pipeline.len()).map(|_| None).collect();
+            for index in schedule {
+                reports[index] = Some(pipeline.node_mut(index).run()?);
+            }
+            return Ok(reports.into_iter().flatten().collect());
+        }
+        let mut dispatches = Vec::new();
+        let cmd = {
+            let mut command_buffer =
+                AutoCommandBufferBuilder::primary_one_time_submit(device.clone(), queue.family())
+                    .map_err(device_error)?;
+            for &index in &schedule {
+                let (dispatch, compute, set) = pipeline.node_mut(index).prepare(None)?;
+                command_buffer
+                    .dispatch(
+                        [dispatch.work_size.groups, 1, 1],
+                        compute,
+                        set,
+                        empty::<u32>(),
+                        empty(),
+                    )
+                    .map_err(device_error)?;
+                dispatches.push(dispatch);
+            }
+            command_buffer.build().map_err(device_error)?
+        };
+        let run_start = Instant::now();
+        let future = now(device)
+            .then_execute(queue, cmd)
+            .map_err(device_error)?
+            .then_signal_fence_and_flush()
+            .map_err(device_error)?;
+        future.wait(None).map_err(device_error)?;
+        let run_time = run_start.elapsed();
+        let results: Vec<_> = schedule
+            .into_iter()
+            .zip(dispatches)
+            .map(|(index, dispatch)| (index, pipeline.node_mut(index).finish(dispatch, run_time)))
+            .collect();
+        let mut reports: Vec<_> = (0..pipeline.len()).map(|_| None).collect();
+        for (index, result) in results {
+            reports[index] = Some(result?);
+        }
+        Ok(reports.into_iter().flatten().collect())
+    }
+
+    fn run_batch(&mut self, runs: &[Overrides]) -> Result<Vec<BatchRun<Report>>, Error> {
+        self.check_bindings()?;
+        let parameters = self.program.parameters();
+        for &(ref name, kind, ref value) in runs.iter().flatten() {
+            check_override(&parameters, name, kind, value)?;
+        }
+        if runs.is_empty() {
+            return Ok(Vec::new());
+        }
+        let count = runs.len() as u32;
+        let pool = UnsafeDescriptorPool::new(
+            self.device.clone(),
+            &DescriptorsCount {
+                uniform_buffer: 128 * count,
+                storage_buffer: 128 * count,
+                ..DescriptorsCount::zero()
+            },
+            count,
+            true,
+        )
+        .map_err(device_error)?;
+        let pool = replace(&mut self.pool, pool);
+        let assoc = self.assoc.clone();
+        let result = self.dispatch_batch(runs, &parameters);
+        self.assoc = assoc;
+        self.pool = pool;
+        result
+    }
+
+    fn submit(&mut self) -> Result<Submission<Report, Error>, Error> {
+        self.check_bindings()?;
+        let pool = UnsafeDescriptorPool::new(
+            self.device.clone(),
+            &DescriptorsCount {
+                uniform_buffer: 128,
+                storage_buffer: 128,
+                ..DescriptorsCount::zero()
+            },
+            1,
+            true,
+        )
+        .map_err(device_error)?;
+        let mut resources: Vec<_> = self
+            .parameters()
+            .iter()
+            .filter_map(|x| self.binding(&x.name, x.kind))
+            .collect();
+        resources.sort_by_key(|x| x.id);
+        resources.dedup_by_key(|x| x.id);
+        let (program, compile_time) = (self.program.clone(), self.compile_time);
+        let queue = self.queue.clone();
+        let (locked, acquired) = mpsc::channel();
+        let (prepared, received) = mpsc::channel();
+        let submission = Submission::spawn(move || {
+            let guards: Vec<_> = resources
+                .iter()
+                .map(|x| x.resource.lock().unwrap())
+                .collect();
+            let _ = locked.send(
+                resources
+                    .iter()
+                    .zip(&guards)
+                    .map(|(x, y)| (x.id, (**y).clone()))
+                    .collect::<HashMap<_, _>>(),
+            );
+            let (dispatch, pipeline, set, pool) = received
+                .recv()
+                .map_err(|_| Error::Execution("Submission was not prepared".into()))?;
+            let set = Arc::new(set);
+            let result = execute(&queue, &pipeline, &set, &dispatch)
+                .and_then(|run_time| report(&program, compile_time, dispatch, run_time));
+            drop(set);
+            drop(pool);
+            drop(guards);
+            result
+        });
+        let locked = acquired
+            .recv()
+            .map_err(|_| Error::Execution("Submission failed to lock its resources".into()))?;
+        let pool = replace(&mut self.pool, pool);
+        let prepared_set = self.prepare(Some(&locked));
+        let pool = replace(&mut self.pool, pool);
+        let (dispatch, pipeline, set) = prepared_set?;
+        let _ = prepared.send((dispatch, pipeline, set, pool));
+        Ok(submission)
+    }
+
+    fn run_until<T: Type, F: FnMut(T) -> bool>(
+        &mut self,
+        flag: &str,
+        max_iterations: Option<u32>,
+        mut predicate: F,
+    ) -> Result<Convergence<Report>, Error> {
+        let parameter = convergence_flag::<T>(&self.program.parameters(), flag)?;
+        let resource = self
+            .binding(flag, parameter.kind)
+            .ok_or_else(|| Error::MissingBinding(parameter.kind, flag.to_string()))?;
+        let (dispatch, pipeline, set) = self.prepare(None)?;
+        let set = Arc::new(set);
+        let mut run_time = Duration::default();
+        let (mut iterations, mut converged) = (0, false);
+        loop {
+            run_time += execute(&self.queue, &pipeline, &set, &dispatch)?;
+            iterations += 1;
+            if dispatch.failed()? {
+                break;
+            }
+            converged = predicate(resource.map(|x: &[T]| x[0])?);
+            if converged || max_iterations.is_some_and(|x| iterations >= x) {
+                break;
+            }
+        }
+        drop(set);
+        let report = self.finish(dispatch, run_time)?;
+        Ok(Convergence {
+            iterations,
+            converged,
+            report,
+        })
+    }
+}
+
+impl VkExecutable {
+    fn prepare(
+        &mut self,
+        locked: Option<&HashMap<u32, ResourceType>>,
+    ) -> Result<(Dispatch, VkPipeline, MyDescriptorSet), Error> {
+        match locked {
+            Some(locked) => self.check_locked(locked)?,
+            None => self.check_bindings()?,
+        }
+        let start = Instant::now();
+        let work_size = self.work_size();
+        if work_size.num_workers().is_none()
+            || work_size.groups > self.limits.max_groups
+            || work_size.group_size > self.limits.max_group_size
+        {
+            return Err(Error::Validation(format!(
+                "Invalid work size {:?}",
+                work_size
+            )));
+        }
+        let pipeline = self.pipeline(work_size.group_size)?;
+        let phases = self.bind_hidden(work_size.num_workers().unwrap_or(0))?;
         let unsafe_layout_object = UnsafeDescriptorSetLayout::new(
             self.device.clone(),
             (0..self.layout.num_bindings_in_set(0).unwrap()).map(|x| self.layout.descriptor(0, x)),
         )
-        .unwrap();
+        .map_err(device_error)?;
         let layout = (0..1).map(|_| &unsafe_layout_object);
-        let mut set = unsafe { self.pool.alloc(layout) }.unwrap().next().unwrap();
+        let mut set = unsafe { self.pool.alloc(layout) }
+            .map_err(device_error)?
+            .next()
+            .unwrap();
         let mut buffers = Vec::new();
+        let mut diagnostic = None;
+        let mut uploaded = 0;
+        let mut downloaded = 0;
         for i in 0..self.assoc.len() {
+            let kind = self
+                .bindings
+                .iter()
+                .find(|x| x.0 == i as u32)
+                .map(|x| x.1.clone());
             if let Some(ref x) = self.assoc[i] {
-                buffers.push(x.get_handle().lock().unwrap().clone())
-            } else if let BindType::Private(size, ty) = self.bindings[i].1 {
+                let buffer = match locked.and_then(|locked| locked.get(&x.id)) {
+                    Some(buffer) => buffer.clone(),
+                    None => x.get_handle().lock().unwrap().clone(),
+                };
+                match kind {
+                    Some(BindType::Public(_, ref name)) if is_hidden(name) => {}
+                    Some(BindType::Public(IO::Input, _)) => uploaded += buffer.size(),
+                    Some(BindType::Public(IO::Output, _)) => downloaded += buffer.size(),
+                    Some(BindType::Public(IO::InOut, _)) => {
+                        uploaded += buffer.size();
+                        downloaded += buffer.size();
+                    }
+                    _ => {}
+                }
+                buffers.push(buffer)
+            } else if let Some(BindType::Diagnostic) = kind {
+                let buffer = CpuAccessibleBuffer::from_iter(
+                    self.device.clone(),
+                    BufferUsage::all(),
+                    true,
+                    (0..4).map(|_| 0_u32),
+                )
+                .map_err(device_error)?;
+                diagnostic = Some(buffer.clone());
+                buffers.push(ResourceType::VU32(buffer));
+            } else if let Some(BindType::Private(size, ty)) = kind {
                 let size = size + 1;
                 match ty {
                     DataType::U32 => buffers.push(ResourceType::VU32(
@@ -119,7 +409,7 @@ impl Executable for VkExecutable {
                             true,
                             (0..size).map(|_| 0_u32),
                         )
-                        .unwrap(),
+                        .map_err(device_error)?,
                     )),
                     DataType::I32 => buffers.push(ResourceType::VI32(
                         CpuAccessibleBuffer::from_iter(
@@ -128,7 +418,7 @@ impl Executable for VkExecutable {
                             true,
                             (0..size).map(|_| 0_i32),
                         )
-                        .unwrap(),
+                        .map_err(device_error)?,
                     )),
                     DataType::F32 => buffers.push(ResourceType::VF32(
                         CpuAccessibleBuffer::from_iter(
@@ -137,10 +427,12 @@ impl Executable for VkExecutable {
                             true,
                             (0..size).map(|_| 0_f32),
                         )
-                        .unwrap(),
+                        .map_err(device_error)?,
                     )),
                     DataType::Bool => unreachable!(),
                 };
+            } else if let Some(BindType::Public(kind, name)) = kind {
+                return Err(Error::MissingBinding(kind, name));
             } else {
                 unreachable!();
             }
@@ -179,31 +471,275 @@ impl Executable for VkExecutable {
             buffers,
             device: self.device.clone(),
         };
+        let dispatch = Dispatch {
+            start,
+            work_size,
+            diagnostic,
+            phases,
+            uploaded,
+            downloaded,
+        };
+        Ok((dispatch, pipeline, sanitized_set))
+    }
+
+    fn check_locked(&self, locked: &HashMap<u32, ResourceType>) -> Result<(), Error> {
+        for parameter in self.program.parameters() {
+            let (kind, name) = (parameter.kind, parameter.name.clone());
+            let value = self
+                .binding(&name, kind)
+                .and_then(|x| locked.get(&x.id))
+                .ok_or_else(|| Error::MissingBinding(kind, name.clone()))?;
+            check_parameter(Some(parameter), &name, kind, value.token_type(), |_| {
+                value.len()
+            })?;
+        }
+        Ok(())
+    }
+
+    fn bind_hidden(
+        &mut self,
+        num_workers: u32,
+    ) -> Result<Option<(Arc<VkResource>, Arc<VkResource>)>, Error> {
+        let initial = match self.phases {
+            Some(ref phases) => phases.initial(num_workers),
+            None => return Ok(None),
+        };
+        for (name, kind, value) in initial {
+            let resource = create_resource(self.device.clone(), self.version);
+            resource.set_data(value)?;
+            self.bind(name, kind, resource)?;
+        }
+        match (
+            self.binding(PHASE, IO::Input),
+            self.binding(NEXT, IO::Output),
+        ) {
+            (Some(phase), Some(next)) => Ok(Some((phase, next))),
+            _ => Err(Error::MissingBinding(IO::Input, PHASE.to_string())),
+        }
+    }
+
+    fn dispatch_batch(
+        &mut self,
+        runs: &[Overrides],
+        parameters: &[Parameter],
+    ) -> Result<Vec<BatchRun<Report>>, Error> {
+        let assoc = self.assoc.clone();
+        if self.phases.is_some() {
+            let mut results = Vec::new();
+            for overrides in runs {
+                self.assoc = assoc.clone();
+                let outputs = self.bind_overrides(overrides, parameters)?;
+                let report = self.run()?;
+                let outputs: BTreeMap<_, _> = outputs
+                    .into_iter()
+                    .map(|(name, resource)| (name, resource.get_data()))
+                    .collect();
+                results.push(BatchRun { outputs, report });
+            }
+            return Ok(results);
+        }
+        let mut pending = Vec::new();
         let cmd = {
             let mut command_buffer = AutoCommandBufferBuilder::primary_one_time_submit(
                 self.device.clone(),
                 self.queue.family(),
             )
-            .unwrap();
+            .map_err(device_error)?;
+            for overrides in runs {
+                self.assoc = assoc.clone();
+                let outputs = self.bind_overrides(overrides, parameters)?;
+                let (dispatch, pipeline, set) = self.prepare(None)?;
+                command_buffer
+                    .dispatch(
+                        [dispatch.work_size.groups, 1, 1],
+                        pipeline,
+                        set,
+                        empty::<u32>(),
+                        empty(),
+                    )
+                    .map_err(device_error)?;
+                pending.push((dispatch, outputs));
+            }
+            command_buffer.build().map_err(device_error)?
+        };
+        let run_start = Instant::now();
+        let future = now(self.device.clone())
+            .then_execute(self.queue.clone(), cmd)
+            .map_err(device_error)?
+            .then_signal_fence_and_flush()
+            .map_err(device_error)?;
+        future.wait(None).map_err(device_error)?;
+        let run_time = run_start.elapsed();
+        let mut results = Vec::new();
+        for (dispatch, outputs) in pending {
+            let report = self.finish(dispatch, run_time)?;
+            let outputs: BTreeMap<_, _> = outputs
+                .into_iter()
+                .map(|(name, resource)| (name, resource.get_data()))
+                .collect();
+            results.push(BatchRun { outputs, report });
+        }
+        Ok(results)
+    }
+
+    fn bind_overrides(
+        &mut self,
+        overrides: &[(String, IO, TokenValue)],
+        parameters: &[Parameter],
+    ) -> Result<Vec<(String, Arc<VkResource>)>, Error> {
+        let mut outputs = Vec::new();
+        for parameter in parameters {
+            let (name, kind) = (&parameter.name, parameter.kind);
+            let value = match overrides.iter().find(|x| x.0 == *name && x.1 == kind) {
+                Some(x) => x.2.clone(),
+                None if kind.writes() => self
+                    .binding(name, kind)
+                    .map_or(TokenValue::Null, |x| x.get_data()),
+                None => continue,
+            };
+            let resource = create_resource(self.device.clone(), self.version);
+            resource.set_data(value)?;
+            self.bind(name, kind, resource.clone())?;
+            if kind.writes() {
+                outputs.push((name.clone(), resource));
+            }
+        }
+        Ok(outputs)
+    }
+
+    fn finish(&mut self, dispatch: Dispatch, run_time: Duration) -> Result<Report, Error> {
+        unsafe { self.pool.reset() }.map_err(device_error)?;
+        report(&self.program, self.compile_time, dispatch, run_time)
+    }
+
+    fn pipeline(&mut self, local_size: u32) -> Result<VkPipeline, Error> {
+        if !self.pipelines.contains_key(&local_size) {
+            let start = Instant::now();
+            let (module, pipeline, _) = build_pipeline(
+                &self.device,
+                &self.program,
+                self.version,
+                local_size,
+                self.config,
+            )?;
+            self.pipelines.insert(local_size, (module, pipeline));
+            self.compile_time += start.elapsed();
+        }
+        Ok(self.pipelines[&local_size].1.clone())
+    }
+}
+
+fn execute(
+    queue: &Arc<Queue>,
+    pipeline: &VkPipeline,
+    set: &Arc<MyDescriptorSet>,
+    dispatch: &Dispatch,
+) -> Result<Duration, Error> {
+    let mut run_time = Duration::default();
+    loop {
+        let cmd = {
+            let mut command_buffer = AutoCommandBufferBuilder::primary_one_time_submit(
+                queue.device().clone(),
+                queue.family(),
+            )
+            .map_err(device_error)?;
             command_buffer
                 .dispatch(
-                    [self.work_size / 12, 1, 1], // FIXME: this assumes 12 CU
-                    self.pipeline.clone(),
-                    sanitized_set,
+                    [dispatch.work_size.groups, 1, 1],
+                    pipeline.clone(),
+                    set.clone(),
                     empty::<u32>(),
                     empty(),
                 )
-                .unwrap();
-            command_buffer.build().unwrap()
+                .map_err(device_error)?;
+            command_buffer.build().map_err(device_error)?
         };
-        let future = now(self.device.clone())
-            .then_execute(self.queue.clone(), cmd)
-            .unwrap()
+        let run_start = Instant::now();
+        let future = now(queue.device().clone())
+            .then_execute(queue.clone(), cmd)
+            .map_err(device_error)?
             .then_signal_fence_and_flush()
-            .unwrap();
-        future.wait(None).unwrap();
-        unsafe { self.pool.reset() }.unwrap();
-        Ok("".into())
+            .map_err(device_error)?;
+        future.wait(None).map_err(device_error)?;
+        run_time += run_start.elapsed();
+        let (phase, next) = match dispatch.phases {
+            Some(ref phases) if !dispatch.failed()? => phases,
+            _ => break,
+        };
+        let target = next.map(|x: &[u32]| next_phase(x))??;
+        let value = if target == DONE { 0 } else { target };
+        phase.set_data(TokenValue::Scalar(ConstantScalar::U32(value)))?;
+        if target == DONE {
+            break;
+        }
+    }
+    Ok(run_time)
+}
+
+fn report(
+    program: &Program,
+    compile_time: Duration,
+    dispatch: Dispatch,
+    run_time: Duration,
+) -> Result<Report, Error> {
+    let mut downloaded = dispatch.downloaded;
+    if let Some(diagnostic) = dispatch.diagnostic {
+        let words = diagnostic.read().map_err(device_error)?;
+        if words[0] > 0 {
+            return Err(describe(program, words[0], words[1], words[2], words[3]));
+        }
+        downloaded += 16;
+    }
+    Ok(Report {
+        wall_time: dispatch.start.elapsed(),
+        compile_time,
+        run_time,
+        work_size: dispatch.work_size,
+        workers: dispatch.work_size.groups * dispatch.work_size.group_size,
+        uploaded: dispatch.uploaded,
+        downloaded,
+        op_counts: None,
+    })
+}
+
+fn describe(program: &Program, count: u32, id: u32, index: u32, length: u32) -> Error {
+    match program.op(id as usize) {
+        Some(op @ &Op::ArrayNew(v, _, _, _, _)) => Error::OutOfBounds(format!(
+            "Size {} exceeds the max size {} of {:?} at op #{} ({:?}), {} violations in total",
+            index, length, v, id, op, count
+        )),
+        Some(&Op::While(_, _, cond, _, _, _)) => Error::Execution(format!(
+            "Loop budget of {} iterations exceeded at op #{} (While on {:?}), {} violations in total",
+            length, id, cond, count
+        )),
+        Some(op @ &Op::ArrayLoad(_, v, _)) | Some(op @ &Op::ArrayStore(v, _, _)) => {
+            Error::OutOfBounds(format!(
+                "Index {} out of bounds for {:?} of length {} at op #{} ({:?}), {} violations in total",
+                index, v, length, id, op, count
+            ))
+        }
+        _ => Error::OutOfBounds(format!(
+            "{} out of bounds accesses, first at op #{}",
+            count, id
+        )),
+    }
+}
+
+struct Dispatch {
+    start: Instant,
+    work_size: WorkSize,
+    diagnostic: Option<Arc<CpuAccessibleBuffer<[u32]>>>,
+    phases: Option<(Arc<VkResource>, Arc<VkResource>)>,
+    uploaded: u64,
+    downloaded: u64,
+}
+
+impl Dispatch {
+    fn failed(&self) -> Result<bool, Error> {
+        Ok(match self.diagnostic {
+            Some(ref x) => x.read().map_err(device_error)?[0] > 0,
+            None => false,
+        })
     }
 }
 
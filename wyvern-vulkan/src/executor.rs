@@ -17,21 +17,35 @@ use generator::{generate, Binding, VkVersion};
 use rand::{thread_rng, Rng};
 use resource::ResourceType;
 use resource::VkResource;
+use std::collections::HashMap;
 use std::ffi::CString;
+use std::fmt::Debug;
 use std::io::Write;
 use std::process::Command;
+use std::str::FromStr;
 use std::sync::{Arc, Mutex};
+use std::time::Instant;
 use tempfile::NamedTempFile;
 use vulkano::descriptor::descriptor::DescriptorDesc;
 use vulkano::descriptor::descriptor::{DescriptorBufferDesc, DescriptorDescTy, ShaderStages};
 use vulkano::descriptor::descriptor_set::{DescriptorsCount, UnsafeDescriptorPool};
+use vulkano::descriptor::pipeline_layout::PipelineLayout;
 use vulkano::descriptor::pipeline_layout::{PipelineLayoutDesc, PipelineLayoutDescPcRange};
 use vulkano::device::{Device, DeviceExtensions, Queue};
 use vulkano::instance::{Instance, InstanceExtensions, PhysicalDevice, Version};
 use vulkano::pipeline::shader::ShaderModule;
 use vulkano::pipeline::ComputePipeline;
-use wcore::executor::Executor;
+use wcore::error::Error;
+use wcore::executor::{env_flag, env_override, Executor, WorkSize};
 use wcore::program::Program;
+use wcore::split::split;
+
+const DEFAULT_GROUP_SIZE: u32 = 128;
+const DEFAULT_GROUPS: u32 = 1024;
+
+pub(crate) fn device_error<E: Debug>(error: E) -> Error {
+    Error::Device(format!("{:?}", error))
+}
 
 #[derive(Debug)]
 pub struct VkExecutor {
@@ -39,25 +53,150 @@ pub struct VkExecutor {
     queue: Arc<Queue>,
     device: Arc<Device>,
     version: VkVersion,
-    work_size: u32,
+    limits: WorkLimits,
+    config: VkConfig,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Optimization {
+    Disabled,
+    Performance,
+    Size,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VkConfig {
+    pub device: usize,
+    pub checked: bool,
+    pub max_iterations: Option<u32>,
+    pub validate: bool,
+    pub optimization: Optimization,
+    pub verbose: bool,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct WorkLimits {
+    pub(crate) max_groups: u32,
+    pub(crate) max_group_size: u32,
+}
+
+pub(crate) type VkPipeline = Arc<ComputePipeline<PipelineLayout<ModuleLayout>>>;
+
+impl Optimization {
+    fn flag(self) -> Option<&'static str> {
+        match self {
+            Optimization::Disabled => None,
+            Optimization::Performance => Some("-O"),
+            Optimization::Size => Some("-Os"),
+        }
+    }
+}
+
+impl FromStr for Optimization {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Optimization, String> {
+        match s {
+            "none" => Ok(Optimization::Disabled),
+            "performance" => Ok(Optimization::Performance),
+            "size" => Ok(Optimization::Size),
+            _ => Err(format!("Unknown optimization {}", s)),
+        }
+    }
+}
+
+impl Default for VkConfig {
+    fn default() -> VkConfig {
+        VkConfig {
+            device: 0,
+            checked: false,
+            max_iterations: None,
+            validate: true,
+            optimization: Optimization::Performance,
+            verbose: false,
+        }
+    }
+}
+
+impl VkConfig {
+    pub fn device(mut self, device: usize) -> VkConfig {
+        self.device = device;
+        self
+    }
+
+    pub fn checked(mut self, checked: bool) -> VkConfig {
+        self.checked = checked;
+        self
+    }
+
+    pub fn max_iterations(mut self, max_iterations: Option<u32>) -> VkConfig {
+        self.max_iterations = max_iterations;
+        self
+    }
+
+    pub fn validate(mut self, validate: bool) -> VkConfig {
+        self.validate = validate;
+        self
+    }
+
+    pub fn optimization(mut self, optimization: Optimization) -> VkConfig {
+        self.optimization = optimization;
+        self
+    }
+
+    pub fn verbose(mut self, verbose: bool) -> VkConfig {
+        self.verbose = verbose;
+        self
+    }
+
+    pub fn from_env() -> Result<VkConfig, Error> {
+        VkConfig::default().env_overrides()
+    }
+
+    pub fn env_overrides(mut self) -> Result<VkConfig, Error> {
+        if let Some(device) = env_override("WYVERN_VK_DEVICE")? {
+            self.device = device;
+        }
+        if let Some(checked) = env_flag("WYVERN_VK_CHECKED")? {
+            self.checked = checked;
+        }
+        if let Some(max_iterations) = env_override("WYVERN_VK_MAX_ITERATIONS")? {
+            self.max_iterations = Some(max_iterations);
+        }
+        if let Some(validate) = env_flag("WYVERN_VK_VALIDATE")? {
+            self.validate = validate;
+        }
+        if let Some(optimization) = env_override("WYVERN_VK_OPTIMIZATION")? {
+            self.optimization = optimization;
+        }
+        if let Some(verbose) = env_flag("WYVERN_VK_VERBOSE")? {
+            self.verbose = verbose;
+        }
+        Ok(self)
+    }
 }
 
 impl Executor for VkExecutor {
-    type Config = ();
-    type Error = String;
+    type Config = VkConfig;
+    type Error = Error;
     type Resource = VkResource;
     type Executable = VkExecutable;
 
-    fn new(_config: ()) -> Result<VkExecutor, String> {
-        let instance = Instance::new(None, &InstanceExtensions::none(), None)
-            .map_err(|x| format!("{:?}", x))?;
+    fn new(config: VkConfig) -> Result<VkExecutor, Error> {
+        let instance =
+            Instance::new(None, &InstanceExtensions::none(), None).map_err(device_error)?;
         let physical_device = PhysicalDevice::enumerate(&instance)
-            .next()
-            .ok_or("No Vulkan device found")?;
-        let work_size = physical_device
-            .limits()
-            .max_compute_work_group_invocations();
-        eprintln!("Using Vulkan device: {}", physical_device.name());
+            .nth(config.device)
+            .ok_or_else(|| Error::Device(format!("Vulkan device {} not found", config.device)))?;
+        let device_limits = physical_device.limits();
+        let limits = WorkLimits {
+            max_groups: device_limits.max_compute_work_group_count()[0],
+            max_group_size: device_limits.max_compute_work_group_size()[0]
+                .min(device_limits.max_compute_work_group_invocations()),
+        };
+        if config.verbose {
+            eprintln!("Using Vulkan device: {}", physical_device.name());
+        }
         let version = match physical_device.api_version() {
             Version {
                 major: 1, minor: 0, ..
@@ -68,12 +207,14 @@ impl Executor for VkExecutor {
             _ => VkVersion::Vulkan11,
         };
         if version == VkVersion::Vulkan10 {
-            unimplemented!("Vulkan 1.0 is not currently supported");
+            return Err(Error::Device(
+                "Vulkan 1.0 is not currently supported".into(),
+            ));
         }
         let queue = physical_device
             .queue_families()
             .find(|&q| q.supports_compute())
-            .ok_or("No compute queue found")?;
+            .ok_or_else(|| Error::Device("No compute queue found".into()))?;
         let (device, mut queues) = {
             Device::new(
                 physical_device,
@@ -81,92 +222,39 @@ impl Executor for VkExecutor {
                 &DeviceExtensions::none(),
                 [(queue, 0.5)].iter().cloned(),
             )
-            .map_err(|x| format!("{:?}", x))?
+            .map_err(device_error)?
         };
-        let queue = queues.next().ok_or("No queue found")?;
+        let queue = queues
+            .next()
+            .ok_or_else(|| Error::Device("No queue found".into()))?;
         Ok(VkExecutor {
             instance,
             queue,
             device,
             version,
-            work_size,
+            limits,
+            config,
         })
     }
 
-    fn compile(&self, program: Program) -> Result<VkExecutable, String> {
-        let (mut binary, bindings) = generate(&program, self.version)?;
-        fn u32tou8(v: &[u32]) -> Vec<u8> {
-            use byteorder::{ByteOrder, LittleEndian};
-            let mut result = Vec::new();
-            for i in v {
-                let mut buf = [0; 4];
-                LittleEndian::write_u32(&mut buf, *i);
-                for j in &buf {
-                    result.push(*j);
-                }
-            }
-            result
-        }
-        fn u8tou32(v: &[u8]) -> Vec<u32> {
-            use byteorder::{ByteOrder, LittleEndian};
-            assert!(v.len() % 4 == 0);
-            let mut result = Vec::new();
-            for i in 0..(v.len() / 4) {
-                result.push(LittleEndian::read_u32(&v[(4 * i)..(4 * (i + 1))]));
-            }
-            result
-        }
-        let mut file_on_disk = NamedTempFile::new().map_err(|x| format!("{:?}", x))?;
-        file_on_disk
-            .write_all(&u32tou8(&binary))
-            .map_err(|x| format!("{:?}", x))?;
-        if let Ok(output) = Command::new("spirv-val").arg(file_on_disk.path()).output() {
-            if !output.status.success() {
-                let file_name = file_on_disk
-                    .path()
-                    .parent()
-                    .unwrap()
-                    .join("wyvern_vulkan_dump.spv");
-                file_on_disk.persist(&file_name).unwrap();
-                panic!("Internal bug: spirv-val failed ({:?})!", file_name);
-            }
-            if let Ok(output) = Command::new("spirv-opt")
-                .arg(file_on_disk.path())
-                .arg("-O")
-                .arg("-o")
-                .arg("-")
-                .output()
-            {
-                if output.status.success() {
-                    binary = u8tou32(&output.stdout);
-                    eprintln!("SPIR-V binary optimized!");
-                }
-            }
-        }
-        let module = unsafe {
-            ShaderModule::from_words(self.device.clone(), &binary)
-                .map_err(|x| format!("{:?}", x))?
-        };
+    fn compile(&self, program: Program) -> Result<VkExecutable, Error> {
+        let start = Instant::now();
+        let (program, phases) = split(program)?;
+        let default_work_size = WorkSize::new(DEFAULT_GROUPS, DEFAULT_GROUP_SIZE)
+            .clamped(self.limits.max_groups, self.limits.max_group_size);
+        let (module, pipeline, bindings) = build_pipeline(
+            &self.device,
+            &program,
+            self.version,
+            default_work_size.group_size,
+            self.config,
+        )?;
         let num_bindings = bindings.iter().map(|x| x.0 + 1).max().unwrap_or(0);
         let layout = ModuleLayout {
             bindings: bindings.clone(),
             num_bindings,
             version: self.version,
         };
-        let pipeline = Arc::new({
-            ComputePipeline::new(
-                self.device.clone(),
-                &unsafe { module.compute_entry_point(&CString::new("main").unwrap(), layout) },
-                &(),
-                None,
-            )
-            .map_err(|x| format!("{:?}", x))?
-        });
-        let layout = ModuleLayout {
-            bindings: bindings.clone(),
-            num_bindings,
-            version: self.version,
-        };
         let pool = UnsafeDescriptorPool::new(
             self.device.clone(),
             &DescriptorsCount {
@@ -177,32 +265,134 @@ impl Executor for VkExecutor {
             1,
             true,
         )
-        .unwrap();
+        .map_err(device_error)?;
+        let mut pipelines = HashMap::new();
+        pipelines.insert(default_work_size.group_size, (module, pipeline));
         Ok(VkExecutable {
             pool,
-            module,
             bindings,
             program,
-            pipeline: pipeline.clone(),
+            phases,
+            pipelines,
             assoc: vec![None; num_bindings as usize],
             layout,
             device: self.device.clone(),
             version: self.version,
             queue: self.queue.clone(),
-            work_size: self.work_size,
+            limits: self.limits,
+            config: self.config,
+            default_work_size,
+            work_size: None,
+            compile_time: start.elapsed(),
         })
     }
 
-    fn new_resource(&self) -> Result<Arc<VkResource>, String> {
-        Ok(Arc::new(VkResource {
-            id: thread_rng().gen(),
-            resource: Arc::new(Mutex::new(ResourceType::Empty)),
-            device: self.device.clone(),
-            version: self.version,
-        }))
+    fn new_resource(&self) -> Result<Arc<VkResource>, Error> {
+        Ok(create_resource(self.device.clone(), self.version))
     }
 }
 
+pub(crate) fn create_resource(device: Arc<Device>, version: VkVersion) -> Arc<VkResource> {
+    Arc::new(VkResource {
+        id: thread_rng().gen(),
+        resource: Arc::new(Mutex::new(ResourceType::Empty)),
+        device,
+        version,
+    })
+}
+
+pub(crate) fn build_pipeline(
+    device: &Arc<Device>,
+    program: &Program,
+    version: VkVersion,
+    local_size: u32,
+    config: VkConfig,
+) -> Result<(Arc<ShaderModule>, VkPipeline, Vec<Binding>), Error> {
+    let (mut binary, bindings) = generate(
+        program,
+        version,
+        local_size,
+        config.checked,
+        config.max_iterations,
+    )
+    .map_err(Error::Compilation)?;
+    fn u32tou8(v: &[u32]) -> Vec<u8> {
+        use byteorder::{ByteOrder, LittleEndian};
+        let mut result = Vec::new();
+        for i in v {
+            let mut buf = [0; 4];
+            LittleEndian::write_u32(&mut buf, *i);
+            for j in &buf {
+                result.push(*j);
+            }
+        }
+        result
+    }
+    fn u8tou32(v: &[u8]) -> Vec<u32> {
+        use byteorder::{ByteOrder, LittleEndian};
+        assert!(v.len() % 4 == 0);
+        let mut result = Vec::new();
+        for i in 0..(v.len() / 4) {
+            result.push(LittleEndian::read_u32(&v[(4 * i)..(4 * (i + 1))]));
+        }
+        result
+    }
+    let mut file_on_disk = NamedTempFile::new().map_err(device_error)?;
+    file_on_disk
+        .write_all(&u32tou8(&binary))
+        .map_err(device_error)?;
+    if config.validate {
+        if let Ok(output) = Command::new("spirv-val").arg(file_on_disk.path()).output() {
+            if !output.status.success() {
+                let file_name = file_on_disk
+                    .path()
+                    .parent()
+                    .unwrap()
+                    .join("wyvern_vulkan_dump.spv");
+                file_on_disk.persist(&file_name).map_err(device_error)?;
+                return Err(Error::Compilation(format!(
+                    "Internal bug: spirv-val failed ({:?})!",
+                    file_name
+                )));
+            }
+        }
+    }
+    if let Some(flag) = config.optimization.flag() {
+        if let Ok(output) = Command::new("spirv-opt")
+            .arg(file_on_disk.path())
+            .arg(flag)
+            .arg("-o")
+            .arg("-")
+            .output()
+        {
+            if output.status.success() {
+                binary = u8tou32(&output.stdout);
+                if config.verbose {
+                    eprintln!("SPIR-V binary optimized!");
+                }
+            }
+        }
+    }
+    let module =
+        unsafe { ShaderModule::from_words(device.clone(), &binary).map_err(device_error)? };
+    let num_bindings = bindings.iter().map(|x| x.0 + 1).max().unwrap_or(0);
+    let layout = ModuleLayout {
+        bindings: bindings.clone(),
+        num_bindings,
+        version,
+    };
+    let pipeline = Arc::new({
+        ComputePipeline::new(
+            device.clone(),
+            &unsafe { module.compute_entry_point(&CString::new("main").unwrap(), layout) },
+            &(),
+            None,
+        )
+        .map_err(device_error)?
+    });
+    Ok((module, pipeline, bindings))
+}
+
 #[derive(Clone)]
 pub(crate) struct ModuleLayout {
     bindings: Vec<Binding>,
@@ -21,11 +21,13 @@ use std::collections::{HashMap, HashSet};
 use wcore::executor::IO;
 use wcore::program::StorageType;
 use wcore::program::{ConstantScalar, DataType, LabelId, Op, Program, TokenId, TokenType};
+use wcore::semantics::SHIFT_MASK;
 
 #[derive(Debug, Clone)]
 pub enum BindType {
     Public(IO, String),
     Private(u32, DataType),
+    Diagnostic,
 }
 
 pub type Binding = (u32, BindType, bool);
@@ -38,8 +40,13 @@ pub enum VkVersion {
 
 #[allow(non_snake_case)]
 #[cfg_attr(feature = "cargo-clippy", allow(cyclomatic_complexity))]
-pub fn generate(program: &Program, version: VkVersion) -> Result<(Vec<u32>, Vec<Binding>), String> {
-    const LOCAL_SIZE: u32 = 128;
+pub fn generate(
+    program: &Program,
+    version: VkVersion,
+    local_size: u32,
+    checked: bool,
+    max_iterations: Option<u32>,
+) -> Result<(Vec<u32>, Vec<Binding>), String> {
     let mut next_binding = 0;
     let mut bindings = Vec::new();
     let mut b = Builder::new();
@@ -67,7 +74,7 @@ pub fn generate(program: &Program, version: VkVersion) -> Result<(Vec<u32>, Vec<
         "main",
         &[global_invocation_id, num_work_groups],
     );
-    b.execution_mode(main_function, ExecutionMode::LocalSize, &[LOCAL_SIZE, 1, 1]);
+    b.execution_mode(main_function, ExecutionMode::LocalSize, &[local_size, 1, 1]);
     struct Types {
         type_void: Word,
         type_bool: Word,
@@ -150,7 +157,7 @@ pub fn generate(program: &Program, version: VkVersion) -> Result<(Vec<u32>, Vec<
     let SCOPE_DEVICE = b.constant_u32(ty.type_u32, 1);
     let SCOPE_WORKGROUP = b.constant_u32(ty.type_u32, 2);
     let SEMANTIC_ACQUIRERELEASE = b.constant_u32(ty.type_u32, 0x8 | 0x40);
-    let LOCAL_SIZE_WORD = b.constant_u32(ty.type_u32, LOCAL_SIZE);
+    let LOCAL_SIZE_WORD = b.constant_u32(ty.type_u32, local_size);
     let cn = Constants {
         CONSTANT_0,
         CONSTANT_1,
@@ -174,6 +181,7 @@ pub fn generate(program: &Program, version: VkVersion) -> Result<(Vec<u32>, Vec<
     let mut label_map = HashMap::new();
     let mut in_set = HashMap::new();
     let mut out_set = HashMap::new();
+    let mut inout_set = HashMap::new();
     for symbol in program.symbol.keys() {
         token_map.insert(*symbol, b.id());
     }
@@ -183,11 +191,15 @@ pub fn generate(program: &Program, version: VkVersion) -> Result<(Vec<u32>, Vec<
     for k in program.output.keys() {
         out_set.insert(program.output[k], k.clone());
     }
+    for k in program.inout.keys() {
+        inout_set.insert(program.inout[k], k.clone());
+    }
     let mut st_set = HashSet::new();
     for t in program.storage.keys() {
         let input = in_set.contains_key(t);
         let output = out_set.contains_key(t);
-        match (program.storage[&t], input || output) {
+        let inout = inout_set.contains_key(t);
+        match (program.storage[&t], input || output || inout) {
             (StorageType::Variable(tty), true) => {
                 let binding_number = next_binding;
                 next_binding += 1;
@@ -252,6 +264,13 @@ pub fn generate(program: &Program, version: VkVersion) -> Result<(Vec<u32>, Vec<
                         false,
                     ))
                 }
+                if inout {
+                    bindings.push((
+                        binding_number,
+                        BindType::Public(IO::InOut, inout_set[t].clone()),
+                        false,
+                    ))
+                }
             }
             (StorageType::SharedArray(tty, ms), io) => {
                 st_set.insert(*t);
@@ -335,6 +354,13 @@ pub fn generate(program: &Program, version: VkVersion) -> Result<(Vec<u32>, Vec<
                             true,
                         ))
                     }
+                    if inout {
+                        bindings.push((
+                            binding_number,
+                            BindType::Public(IO::InOut, inout_set[t].clone()),
+                            true,
+                        ))
+                    }
                 } else {
                     bindings.push((binding_number, BindType::Private(ms, tty), true));
                 }
@@ -342,6 +368,42 @@ pub fn generate(program: &Program, version: VkVersion) -> Result<(Vec<u32>, Vec<
             _ => {}
         };
     }
+    let diagnostic = if checked || max_iterations.is_some() {
+        let binding_number = next_binding;
+        let struct_type = b.type_struct(&[ty.type_u32; 4]);
+        let struct_type_pointer = b.type_pointer(None, stclass, struct_type);
+        let struct_instance = b.variable(struct_type_pointer, None, stclass, None);
+        for i in 0..4 {
+            b.member_decorate(
+                struct_type,
+                i,
+                Decoration::Offset,
+                &[Operand::LiteralInt32(4 * i)],
+            );
+        }
+        b.decorate(
+            struct_type,
+            match version {
+                VkVersion::Vulkan10 => Decoration::BufferBlock,
+                VkVersion::Vulkan11 => Decoration::Block,
+            },
+            &[],
+        );
+        b.decorate(
+            struct_instance,
+            Decoration::DescriptorSet,
+            &[Operand::LiteralInt32(0)],
+        );
+        b.decorate(
+            struct_instance,
+            Decoration::Binding,
+            &[Operand::LiteralInt32(binding_number)],
+        );
+        bindings.push((binding_number, BindType::Diagnostic, true));
+        Some(struct_instance)
+    } else {
+        None
+    };
     let _main_function = b
         .begin_function(
             ty.type_void,
@@ -354,8 +416,14 @@ pub fn generate(program: &Program, version: VkVersion) -> Result<(Vec<u32>, Vec<
         LabelId(0),
         b.begin_basic_block(None).map_err(|x| format!("{:?}", x))?,
     );
+    let io_set: HashSet<TokenId> = in_set
+        .keys()
+        .chain(out_set.keys())
+        .chain(inout_set.keys())
+        .cloned()
+        .collect();
     for t in program.storage.keys() {
-        if in_set.contains_key(&t) || out_set.contains_key(&t) {
+        if io_set.contains(t) {
             if let StorageType::Variable(tty) = program.storage[&t] {
                 let new_token = b
                     .access_chain(
@@ -413,6 +481,18 @@ pub fn generate(program: &Program, version: VkVersion) -> Result<(Vec<u32>, Vec<
             StorageType::SharedArray(_, _) => {}
         };
     }
+    let budget = match (diagnostic, max_iterations) {
+        (Some(diagnostic), Some(max_iterations)) => {
+            let counter = b.variable(
+                ty.type_funu32,
+                None,
+                StorageClass::Function,
+                Some(cn.CONSTANT_0),
+            );
+            Some((diagnostic, counter, max_iterations))
+        }
+        _ => None,
+    };
     let global_invocation_id_var = b
         .access_chain(ty.type_inu32, None, global_invocation_id, &[cn.CONSTANT_0])
         .map_err(|x| format!("{:?}", x))?;
@@ -436,6 +516,66 @@ pub fn generate(program: &Program, version: VkVersion) -> Result<(Vec<u32>, Vec<
         worker_id: global_invocation_id_word,
         num_workers: num_workers_word,
     };
+    fn check(
+        b: &mut Builder,
+        ty: &Types,
+        cn: &Constants,
+        diagnostic: Word,
+        ok: Word,
+        op_id: u32,
+        index: Word,
+        len: Word,
+    ) -> Result<(Word, Word, Word), String> {
+        let lok = b.id();
+        let lbad = b.id();
+        let lfirst = b.id();
+        let lrecorded = b.id();
+        let lmerge = b.id();
+        b.selection_merge(lmerge, SelectionControl::NONE)
+            .map_err(|x| format!("{:?}", x))?;
+        b.branch_conditional(ok, lok, lbad, &[])
+            .map_err(|x| format!("{:?}", x))?;
+        b.begin_basic_block(Some(lbad))
+            .map_err(|x| format!("{:?}", x))?;
+        let count = b
+            .access_chain(ty.type_stu32, None, diagnostic, &[cn.CONSTANT_0])
+            .map_err(|x| format!("{:?}", x))?;
+        let previous = b
+            .atomic_iadd(
+                ty.type_u32,
+                None,
+                count,
+                cn.SCOPE_DEVICE,
+                cn.CONSTANT_0,
+                cn.CONSTANT_1,
+            )
+            .map_err(|x| format!("{:?}", x))?;
+        let first = b
+            .iequal(ty.type_bool, None, previous, cn.CONSTANT_0)
+            .map_err(|x| format!("{:?}", x))?;
+        b.selection_merge(lrecorded, SelectionControl::NONE)
+            .map_err(|x| format!("{:?}", x))?;
+        b.branch_conditional(first, lfirst, lrecorded, &[])
+            .map_err(|x| format!("{:?}", x))?;
+        b.begin_basic_block(Some(lfirst))
+            .map_err(|x| format!("{:?}", x))?;
+        let op_id = b.constant_u32(ty.type_u32, op_id);
+        for (i, &value) in [op_id, index, len].iter().enumerate() {
+            let field = b.constant_u32(ty.type_u32, i as u32 + 1);
+            let pointer = b
+                .access_chain(ty.type_stu32, None, diagnostic, &[field])
+                .map_err(|x| format!("{:?}", x))?;
+            b.store(pointer, value, None, &[])
+                .map_err(|x| format!("{:?}", x))?;
+        }
+        b.branch(lrecorded).map_err(|x| format!("{:?}", x))?;
+        b.begin_basic_block(Some(lrecorded))
+            .map_err(|x| format!("{:?}", x))?;
+        b.branch(lmerge).map_err(|x| format!("{:?}", x))?;
+        b.begin_basic_block(Some(lok))
+            .map_err(|x| format!("{:?}", x))?;
+        Ok((lok, lrecorded, lmerge))
+    }
     fn compile(
         operations: &[Op],
         b: &mut Builder,
@@ -446,8 +586,10 @@ pub fn generate(program: &Program, version: VkVersion) -> Result<(Vec<u32>, Vec<
         token_map: &mut HashMap<TokenId, Word>,
         label_map: &mut HashMap<LabelId, Word>,
         st_set: &HashSet<TokenId>,
-        in_set: &HashMap<TokenId, String>,
-        out_set: &HashMap<TokenId, String>,
+        io_set: &HashSet<TokenId>,
+        op_id: &mut u32,
+        diagnostic: Option<Word>,
+        budget: Option<(Word, Word, u32)>,
     ) -> Result<(), String> {
         let get_const_type = |x: TokenId| match program.symbol[&x] {
             TokenType::Constant(DataType::Bool) => ty.type_bool,
@@ -471,7 +613,25 @@ pub fn generate(program: &Program, version: VkVersion) -> Result<(Vec<u32>, Vec<
             (TokenType::Array(DataType::F32), true) => ty.type_stf32,
             _ => unreachable!(),
         };
+        let array_len = |b: &mut Builder, array: Word, storage: bool| -> Result<Word, String> {
+            let size_pointer = b
+                .access_chain(
+                    if storage {
+                        ty.type_stu32
+                    } else {
+                        ty.type_funu32
+                    },
+                    None,
+                    array,
+                    &[cn.CONSTANT_0],
+                )
+                .map_err(|x| format!("{:?}", x))?;
+            b.load(ty.type_u32, None, size_pointer, None, &[])
+                .map_err(|x| format!("{:?}", x))
+        };
         for op in operations {
+            let id = *op_id;
+            *op_id += 1;
             match *op {
                 Op::MemoryBarrier => {
                     b.memory_barrier(cn.SCOPE_DEVICE, cn.SEMANTIC_ACQUIRERELEASE)
@@ -485,6 +645,9 @@ pub fn generate(program: &Program, version: VkVersion) -> Result<(Vec<u32>, Vec<
                     )
                     .map_err(|x| format!("{:?}", x))?;
                 }
+                Op::GlobalBarrier => {
+                    return Err("GlobalBarrier must be split before code generation".into());
+                }
                 Op::WorkerId(r) => {
                     token_map.insert(r, w.worker_id);
                 }
@@ -518,11 +681,61 @@ pub fn generate(program: &Program, version: VkVersion) -> Result<(Vec<u32>, Vec<
                     };
                 }
                 Op::U32fromF32(r, a) => {
-                    b.convert_fto_u(ty.type_u32, Some(token_map[&r]), token_map[&a])
+                    let value = b
+                        .convert_fto_u(ty.type_u32, None, token_map[&a])
+                        .map_err(|x| format!("{:?}", x))?;
+                    let low = b.constant_f32(ty.type_f32, 0.0);
+                    let high = b.constant_f32(ty.type_f32, 4_294_967_296.0);
+                    let max = b.constant_u32(ty.type_u32, u32::MAX);
+                    let nan = b
+                        .is_nan(ty.type_bool, None, token_map[&a])
+                        .map_err(|x| format!("{:?}", x))?;
+                    let below = b
+                        .ford_less_than_equal(ty.type_bool, None, token_map[&a], low)
+                        .map_err(|x| format!("{:?}", x))?;
+                    let below = b
+                        .logical_or(ty.type_bool, None, nan, below)
+                        .map_err(|x| format!("{:?}", x))?;
+                    let above = b
+                        .ford_greater_than_equal(ty.type_bool, None, token_map[&a], high)
                         .map_err(|x| format!("{:?}", x))?;
+                    let value = b
+                        .select(ty.type_u32, None, above, max, value)
+                        .map_err(|x| format!("{:?}", x))?;
+                    b.select(
+                        ty.type_u32,
+                        Some(token_map[&r]),
+                        below,
+                        cn.CONSTANT_0,
+                        value,
+                    )
+                    .map_err(|x| format!("{:?}", x))?;
                 }
                 Op::I32fromF32(r, a) => {
-                    b.convert_fto_s(ty.type_i32, Some(token_map[&r]), token_map[&a])
+                    let value = b
+                        .convert_fto_s(ty.type_i32, None, token_map[&a])
+                        .map_err(|x| format!("{:?}", x))?;
+                    let low = b.constant_f32(ty.type_f32, -2_147_483_648.0);
+                    let high = b.constant_f32(ty.type_f32, 2_147_483_648.0);
+                    let min = b.constant_u32(ty.type_i32, i32::MIN as u32);
+                    let max = b.constant_u32(ty.type_i32, i32::MAX as u32);
+                    let zero = b.constant_u32(ty.type_i32, 0);
+                    let nan = b
+                        .is_nan(ty.type_bool, None, token_map[&a])
+                        .map_err(|x| format!("{:?}", x))?;
+                    let below = b
+                        .ford_less_than_equal(ty.type_bool, None, token_map[&a], low)
+                        .map_err(|x| format!("{:?}", x))?;
+                    let above = b
+                        .ford_greater_than_equal(ty.type_bool, None, token_map[&a], high)
+                        .map_err(|x| format!("{:?}", x))?;
+                    let value = b
+                        .select(ty.type_i32, None, above, max, value)
+                        .map_err(|x| format!("{:?}", x))?;
+                    let value = b
+                        .select(ty.type_i32, None, below, min, value)
+                        .map_err(|x| format!("{:?}", x))?;
+                    b.select(ty.type_i32, Some(token_map[&r]), nan, zero, value)
                         .map_err(|x| format!("{:?}", x))?;
                 }
                 Op::F32fromU32(r, a) => {
@@ -637,66 +850,88 @@ pub fn generate(program: &Program, version: VkVersion) -> Result<(Vec<u32>, Vec<
                         DataType::Bool => unreachable!(),
                     };
                 }
-                Op::Div(r, a, d) => {
+                Op::Div(r, a, d) | Op::Rem(r, a, d) => {
+                    let div = matches!(*op, Op::Div(..));
                     match get_const_datatype(r) {
                         DataType::U32 => {
-                            b.udiv(
-                                ty.type_u32,
-                                Some(token_map[&r]),
-                                token_map[&a],
-                                token_map[&d],
-                            )
-                            .map_err(|x| format!("{:?}", x))?;
-                        }
-                        DataType::I32 => {
-                            b.sdiv(
-                                ty.type_i32,
-                                Some(token_map[&r]),
-                                token_map[&a],
-                                token_map[&d],
-                            )
-                            .map_err(|x| format!("{:?}", x))?;
-                        }
-                        DataType::F32 => {
-                            b.fdiv(
-                                ty.type_f32,
-                                Some(token_map[&r]),
-                                token_map[&a],
-                                token_map[&d],
-                            )
-                            .map_err(|x| format!("{:?}", x))?;
-                        }
-                        DataType::Bool => unreachable!(),
-                    };
-                }
-                Op::Rem(r, a, d) => {
-                    match get_const_datatype(r) {
-                        DataType::U32 => {
-                            b.umod(
-                                ty.type_u32,
-                                Some(token_map[&r]),
-                                token_map[&a],
-                                token_map[&d],
-                            )
-                            .map_err(|x| format!("{:?}", x))?;
+                            let zero = b
+                                .iequal(ty.type_bool, None, token_map[&d], cn.CONSTANT_0)
+                                .map_err(|x| format!("{:?}", x))?;
+                            let divisor = b
+                                .select(ty.type_u32, None, zero, cn.CONSTANT_1, token_map[&d])
+                                .map_err(|x| format!("{:?}", x))?;
+                            let (value, by_zero) = if div {
+                                (
+                                    b.udiv(ty.type_u32, None, token_map[&a], divisor)
+                                        .map_err(|x| format!("{:?}", x))?,
+                                    b.constant_u32(ty.type_u32, u32::MAX),
+                                )
+                            } else {
+                                (
+                                    b.umod(ty.type_u32, None, token_map[&a], divisor)
+                                        .map_err(|x| format!("{:?}", x))?,
+                                    token_map[&a],
+                                )
+                            };
+                            b.select(ty.type_u32, Some(token_map[&r]), zero, by_zero, value)
+                                .map_err(|x| format!("{:?}", x))?;
                         }
                         DataType::I32 => {
-                            b.smod(
-                                ty.type_i32,
-                                Some(token_map[&r]),
-                                token_map[&a],
-                                token_map[&d],
-                            )
-                            .map_err(|x| format!("{:?}", x))?;
+                            let constant_0 = b.constant_u32(ty.type_i32, 0);
+                            let constant_1 = b.constant_u32(ty.type_i32, 1);
+                            let constant_m1 = b.constant_u32(ty.type_i32, -1_i32 as u32);
+                            let zero = b
+                                .iequal(ty.type_bool, None, token_map[&d], constant_0)
+                                .map_err(|x| format!("{:?}", x))?;
+                            let minus_one = b
+                                .iequal(ty.type_bool, None, token_map[&d], constant_m1)
+                                .map_err(|x| format!("{:?}", x))?;
+                            let special = b
+                                .logical_or(ty.type_bool, None, zero, minus_one)
+                                .map_err(|x| format!("{:?}", x))?;
+                            let divisor = b
+                                .select(ty.type_i32, None, special, constant_1, token_map[&d])
+                                .map_err(|x| format!("{:?}", x))?;
+                            let (value, by_minus_one, by_zero) = if div {
+                                (
+                                    b.sdiv(ty.type_i32, None, token_map[&a], divisor)
+                                        .map_err(|x| format!("{:?}", x))?,
+                                    b.snegate(ty.type_i32, None, token_map[&a])
+                                        .map_err(|x| format!("{:?}", x))?,
+                                    constant_m1,
+                                )
+                            } else {
+                                (
+                                    b.srem(ty.type_i32, None, token_map[&a], divisor)
+                                        .map_err(|x| format!("{:?}", x))?,
+                                    constant_0,
+                                    token_map[&a],
+                                )
+                            };
+                            let value = b
+                                .select(ty.type_i32, None, minus_one, by_minus_one, value)
+                                .map_err(|x| format!("{:?}", x))?;
+                            b.select(ty.type_i32, Some(token_map[&r]), zero, by_zero, value)
+                                .map_err(|x| format!("{:?}", x))?;
                         }
                         DataType::F32 => {
-                            b.fmod(
-                                ty.type_f32,
-                                Some(token_map[&r]),
-                                token_map[&a],
-                                token_map[&d],
-                            )
-                            .map_err(|x| format!("{:?}", x))?;
+                            if div {
+                                b.fdiv(
+                                    ty.type_f32,
+                                    Some(token_map[&r]),
+                                    token_map[&a],
+                                    token_map[&d],
+                                )
+                                .map_err(|x| format!("{:?}", x))?;
+                            } else {
+                                b.frem(
+                                    ty.type_f32,
+                                    Some(token_map[&r]),
+                                    token_map[&a],
+                                    token_map[&d],
+                                )
+                                .map_err(|x| format!("{:?}", x))?;
+                            }
                         }
                         DataType::Bool => unreachable!(),
                     };
@@ -732,52 +967,51 @@ pub fn generate(program: &Program, version: VkVersion) -> Result<(Vec<u32>, Vec<
                         }
                     };
                 }
-                Op::Shl(r, a, d) => {
-                    match get_const_datatype(r) {
-                        DataType::U32 => {
+                Op::Shl(r, a, d) | Op::Shr(r, a, d) => {
+                    let amount_type = get_const_type(d);
+                    let mask = b.constant_u32(amount_type, SHIFT_MASK);
+                    let amount = b
+                        .bitwise_and(amount_type, None, token_map[&d], mask)
+                        .map_err(|x| format!("{:?}", x))?;
+                    match (op, get_const_datatype(r)) {
+                        (&Op::Shl(..), DataType::U32) => {
                             b.shift_left_logical(
                                 ty.type_u32,
                                 Some(token_map[&r]),
                                 token_map[&a],
-                                token_map[&d],
+                                amount,
                             )
                             .map_err(|x| format!("{:?}", x))?;
                         }
-                        DataType::I32 => {
+                        (&Op::Shl(..), DataType::I32) => {
                             b.shift_left_logical(
                                 ty.type_i32,
                                 Some(token_map[&r]),
                                 token_map[&a],
-                                token_map[&d],
+                                amount,
                             )
                             .map_err(|x| format!("{:?}", x))?;
                         }
-                        DataType::F32 => unreachable!(),
-                        DataType::Bool => unreachable!(),
-                    };
-                }
-                Op::Shr(r, a, d) => {
-                    match get_const_datatype(r) {
-                        DataType::U32 => {
+                        (_, DataType::U32) => {
                             b.shift_right_logical(
                                 ty.type_u32,
                                 Some(token_map[&r]),
                                 token_map[&a],
-                                token_map[&d],
+                                amount,
                             )
                             .map_err(|x| format!("{:?}", x))?;
                         }
-                        DataType::I32 => {
-                            b.shift_right_logical(
+                        (_, DataType::I32) => {
+                            b.shift_right_arithmetic(
                                 ty.type_i32,
                                 Some(token_map[&r]),
                                 token_map[&a],
-                                token_map[&d],
+                                amount,
                             )
                             .map_err(|x| format!("{:?}", x))?;
                         }
-                        DataType::F32 => unreachable!(),
-                        DataType::Bool => unreachable!(),
+                        (_, DataType::F32) => unreachable!(),
+                        (_, DataType::Bool) => unreachable!(),
                     };
                 }
                 Op::BitAnd(r, a, d) => {
@@ -1090,8 +1324,8 @@ pub fn generate(program: &Program, version: VkVersion) -> Result<(Vec<u32>, Vec<
                     let l0 = *label_map.entry(l0).or_insert_with(|| b.id());
                     let lend = *label_map.entry(lend).or_insert_with(|| b.id());
                     compile(
-                        cond_op, b, program, ty, cn, w, token_map, label_map, st_set, in_set,
-                        out_set,
+                        cond_op, b, program, ty, cn, w, token_map, label_map, st_set, io_set,
+                        op_id, diagnostic, budget,
                     )?;
                     b.selection_merge(lend, SelectionControl::NONE)
                         .map_err(|x| format!("{:?}", x))?;
@@ -1100,7 +1334,8 @@ pub fn generate(program: &Program, version: VkVersion) -> Result<(Vec<u32>, Vec<
                     b.begin_basic_block(Some(l0))
                         .map_err(|x| format!("{:?}", x))?;
                     compile(
-                        a0, b, program, ty, cn, w, token_map, label_map, st_set, in_set, out_set,
+                        a0, b, program, ty, cn, w, token_map, label_map, st_set, io_set, op_id,
+                        diagnostic, budget,
                     )?;
                     b.branch(lend).map_err(|x| format!("{:?}", x))?;
                     b.begin_basic_block(Some(lend))
@@ -1111,8 +1346,8 @@ pub fn generate(program: &Program, version: VkVersion) -> Result<(Vec<u32>, Vec<
                     let l1 = *label_map.entry(l1).or_insert_with(|| b.id());
                     let lend = *label_map.entry(lend).or_insert_with(|| b.id());
                     compile(
-                        cond_op, b, program, ty, cn, w, token_map, label_map, st_set, in_set,
-                        out_set,
+                        cond_op, b, program, ty, cn, w, token_map, label_map, st_set, io_set,
+                        op_id, diagnostic, budget,
                     )?;
                     b.selection_merge(lend, SelectionControl::NONE)
                         .map_err(|x| format!("{:?}", x))?;
@@ -1121,13 +1356,15 @@ pub fn generate(program: &Program, version: VkVersion) -> Result<(Vec<u32>, Vec<
                     b.begin_basic_block(Some(l0))
                         .map_err(|x| format!("{:?}", x))?;
                     compile(
-                        a0, b, program, ty, cn, w, token_map, label_map, st_set, in_set, out_set,
+                        a0, b, program, ty, cn, w, token_map, label_map, st_set, io_set, op_id,
+                        diagnostic, budget,
                     )?;
                     b.branch(lend).map_err(|x| format!("{:?}", x))?;
                     b.begin_basic_block(Some(l1))
                         .map_err(|x| format!("{:?}", x))?;
                     compile(
-                        a1, b, program, ty, cn, w, token_map, label_map, st_set, in_set, out_set,
+                        a1, b, program, ty, cn, w, token_map, label_map, st_set, io_set, op_id,
+                        diagnostic, budget,
                     )?;
                     b.branch(lend).map_err(|x| format!("{:?}", x))?;
                     b.begin_basic_block(Some(lend))
@@ -1148,15 +1385,46 @@ pub fn generate(program: &Program, version: VkVersion) -> Result<(Vec<u32>, Vec<
                     b.begin_basic_block(Some(lcond))
                         .map_err(|x| format!("{:?}", x))?;
                     compile(
-                        cond_op, b, program, ty, cn, w, token_map, label_map, st_set, in_set,
-                        out_set,
+                        cond_op, b, program, ty, cn, w, token_map, label_map, st_set, io_set,
+                        op_id, diagnostic, budget,
                     )?;
-                    b.branch_conditional(token_map[&cond], l0, lend, &[])
+                    let cond = match budget {
+                        Some((diagnostic, counter, max_iterations)) => {
+                            let max_iterations = b.constant_u32(ty.type_u32, max_iterations);
+                            let count = b
+                                .load(ty.type_u32, None, counter, None, &[])
+                                .map_err(|x| format!("{:?}", x))?;
+                            let within = b
+                                .uless_than(ty.type_bool, None, count, max_iterations)
+                                .map_err(|x| format!("{:?}", x))?;
+                            let exit = b
+                                .logical_not(ty.type_bool, None, token_map[&cond])
+                                .map_err(|x| format!("{:?}", x))?;
+                            let ok = b
+                                .logical_or(ty.type_bool, None, exit, within)
+                                .map_err(|x| format!("{:?}", x))?;
+                            let (_, _, lmerge) =
+                                check(b, ty, cn, diagnostic, ok, id, count, max_iterations)?;
+                            b.branch(lmerge).map_err(|x| format!("{:?}", x))?;
+                            b.begin_basic_block(Some(lmerge))
+                                .map_err(|x| format!("{:?}", x))?;
+                            let next = b
+                                .iadd(ty.type_u32, None, count, cn.CONSTANT_1)
+                                .map_err(|x| format!("{:?}", x))?;
+                            b.store(counter, next, None, &[])
+                                .map_err(|x| format!("{:?}", x))?;
+                            b.logical_and(ty.type_bool, None, token_map[&cond], within)
+                                .map_err(|x| format!("{:?}", x))?
+                        }
+                        None => token_map[&cond],
+                    };
+                    b.branch_conditional(cond, l0, lend, &[])
                         .map_err(|x| format!("{:?}", x))?;
                     b.begin_basic_block(Some(l0))
                         .map_err(|x| format!("{:?}", x))?;
                     compile(
-                        a0, b, program, ty, cn, w, token_map, label_map, st_set, in_set, out_set,
+                        a0, b, program, ty, cn, w, token_map, label_map, st_set, io_set, op_id,
+                        diagnostic, budget,
                     )?;
                     b.branch(lcontinue).map_err(|x| format!("{:?}", x))?;
                     b.begin_basic_block(Some(lcontinue))
@@ -1180,7 +1448,7 @@ pub fn generate(program: &Program, version: VkVersion) -> Result<(Vec<u32>, Vec<
                         .map_err(|x| format!("{:?}", x))?;
                 }
                 Op::ArrayNew(r, s, _, _, _) => {
-                    if in_set.contains_key(&r) || out_set.contains_key(&r) {
+                    if io_set.contains(&r) {
                         continue;
                     }
                     let size_pointer = b
@@ -1195,7 +1463,28 @@ pub fn generate(program: &Program, version: VkVersion) -> Result<(Vec<u32>, Vec<
                             &[cn.CONSTANT_0],
                         )
                         .map_err(|x| format!("{:?}", x))?;
-                    b.store(size_pointer, token_map[&s], None, &[])
+                    let size = match diagnostic {
+                        Some(diagnostic) => {
+                            let max_size = match program.storage[&r] {
+                                StorageType::SharedArray(_, ms)
+                                | StorageType::PrivateArray(_, ms) => ms,
+                                StorageType::Variable(_) => unreachable!(),
+                            };
+                            let max_size = b.constant_u32(ty.type_u32, max_size);
+                            let ok = b
+                                .uless_than_equal(ty.type_bool, None, token_map[&s], max_size)
+                                .map_err(|x| format!("{:?}", x))?;
+                            let (_, _, lmerge) =
+                                check(b, ty, cn, diagnostic, ok, id, token_map[&s], max_size)?;
+                            b.branch(lmerge).map_err(|x| format!("{:?}", x))?;
+                            b.begin_basic_block(Some(lmerge))
+                                .map_err(|x| format!("{:?}", x))?;
+                            b.select(ty.type_u32, None, ok, token_map[&s], max_size)
+                                .map_err(|x| format!("{:?}", x))?
+                        }
+                        None => token_map[&s],
+                    };
+                    b.store(size_pointer, size, None, &[])
                         .map_err(|x| format!("{:?}", x))?;
                 }
                 Op::ArrayLen(r, v) => {
@@ -1221,6 +1510,16 @@ pub fn generate(program: &Program, version: VkVersion) -> Result<(Vec<u32>, Vec<
                     .map_err(|x| format!("{:?}", x))?;
                 }
                 Op::ArrayStore(v, i, a) => {
+                    let checked = match diagnostic {
+                        Some(diagnostic) => {
+                            let len = array_len(b, token_map[&v], st_set.contains(&v))?;
+                            let ok = b
+                                .uless_than(ty.type_bool, None, token_map[&i], len)
+                                .map_err(|x| format!("{:?}", x))?;
+                            Some(check(b, ty, cn, diagnostic, ok, id, token_map[&i], len)?)
+                        }
+                        None => None,
+                    };
                     let pointer = b
                         .access_chain(
                             get_array_type(v, st_set.contains(&v)),
@@ -1231,8 +1530,23 @@ pub fn generate(program: &Program, version: VkVersion) -> Result<(Vec<u32>, Vec<
                         .map_err(|x| format!("{:?}", x))?;
                     b.store(pointer, token_map[&a], None, &[])
                         .map_err(|x| format!("{:?}", x))?;
+                    if let Some((_, _, lmerge)) = checked {
+                        b.branch(lmerge).map_err(|x| format!("{:?}", x))?;
+                        b.begin_basic_block(Some(lmerge))
+                            .map_err(|x| format!("{:?}", x))?;
+                    }
                 }
                 Op::ArrayLoad(r, v, i) => {
+                    let checked = match diagnostic {
+                        Some(diagnostic) => {
+                            let len = array_len(b, token_map[&v], st_set.contains(&v))?;
+                            let ok = b
+                                .uless_than(ty.type_bool, None, token_map[&i], len)
+                                .map_err(|x| format!("{:?}", x))?;
+                            Some(check(b, ty, cn, diagnostic, ok, id, token_map[&i], len)?)
+                        }
+                        None => None,
+                    };
                     let pointer = b
                         .access_chain(
                             get_array_type(v, st_set.contains(&v)),
@@ -1241,8 +1555,32 @@ pub fn generate(program: &Program, version: VkVersion) -> Result<(Vec<u32>, Vec<
                             &[cn.CONSTANT_1, token_map[&i]],
                         )
                         .map_err(|x| format!("{:?}", x))?;
-                    b.load(get_const_type(r), Some(token_map[&r]), pointer, None, &[])
-                        .map_err(|x| format!("{:?}", x))?;
+                    match checked {
+                        Some((lok, lrecorded, lmerge)) => {
+                            let value = b
+                                .load(get_const_type(r), None, pointer, None, &[])
+                                .map_err(|x| format!("{:?}", x))?;
+                            b.branch(lmerge).map_err(|x| format!("{:?}", x))?;
+                            b.begin_basic_block(Some(lmerge))
+                                .map_err(|x| format!("{:?}", x))?;
+                            let zero = match get_const_datatype(r) {
+                                DataType::Bool => b.constant_false(ty.type_bool),
+                                DataType::U32 => cn.CONSTANT_0,
+                                DataType::I32 => b.constant_u32(ty.type_i32, 0),
+                                DataType::F32 => b.constant_f32(ty.type_f32, 0.0),
+                            };
+                            b.phi(
+                                get_const_type(r),
+                                Some(token_map[&r]),
+                                &[(value, lok), (zero, lrecorded)],
+                            )
+                            .map_err(|x| format!("{:?}", x))?;
+                        }
+                        None => {
+                            b.load(get_const_type(r), Some(token_map[&r]), pointer, None, &[])
+                                .map_err(|x| format!("{:?}", x))?;
+                        }
+                    }
                 }
             };
         }
@@ -1258,8 +1596,10 @@ pub fn generate(program: &Program, version: VkVersion) -> Result<(Vec<u32>, Vec<
         &mut token_map,
         &mut label_map,
         &st_set,
-        &in_set,
-        &out_set,
+        &io_set,
+        &mut 0,
+        if checked { diagnostic } else { None },
+        budget,
     )?;
     b.ret().map_err(|x| format!("{:?}", x))?;
     b.end_function().map_err(|x| format!("{:?}", x))?;
@@ -12,16 +12,20 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use executor::device_error;
 use generator::VkVersion;
 use std::f32;
 use std::fmt::{Debug, Formatter};
 use std::hash::{Hash, Hasher};
+use std::slice;
 use std::sync::{Arc, Mutex};
 use vulkano::buffer::cpu_access::CpuAccessibleBuffer;
-use vulkano::buffer::BufferUsage;
+use vulkano::buffer::{BufferAccess, BufferUsage};
 use vulkano::device::Device;
-use wcore::executor::Resource;
+use wcore::error::Error;
+use wcore::executor::{access_error, Resource};
 use wcore::program::{ConstantScalar, ConstantVector, DataType, TokenType, TokenValue};
+use wcore::types::{cast_slice, cast_slice_mut, Type};
 
 #[derive(Clone)]
 pub(crate) enum ResourceType {
@@ -42,10 +46,158 @@ pub struct VkResource {
     pub(crate) version: VkVersion,
 }
 
+impl ResourceType {
+    pub(crate) fn token_type(&self) -> TokenType {
+        match *self {
+            ResourceType::Empty => TokenType::Null,
+            ResourceType::U32(_) => TokenType::Variable(DataType::U32),
+            ResourceType::I32(_) => TokenType::Variable(DataType::I32),
+            ResourceType::F32(_) => TokenType::Variable(DataType::F32),
+            ResourceType::VU32(_) => TokenType::Array(DataType::U32),
+            ResourceType::VI32(_) => TokenType::Array(DataType::I32),
+            ResourceType::VF32(_) => TokenType::Array(DataType::F32),
+        }
+    }
+
+    pub(crate) fn len(&self) -> Result<usize, Error> {
+        Ok(match *self {
+            ResourceType::Empty => 0,
+            ResourceType::U32(_) | ResourceType::I32(_) | ResourceType::F32(_) => 1,
+            ResourceType::VU32(ref v) => v.read().map_err(device_error)?[0].decode(),
+            ResourceType::VI32(ref v) => v.read().map_err(device_error)?[0].decode(),
+            ResourceType::VF32(ref v) => v.read().map_err(device_error)?[0].decode(),
+        })
+    }
+
+    pub(crate) fn size(&self) -> u64 {
+        (match *self {
+            ResourceType::Empty => 0,
+            ResourceType::U32(ref x) => x.size(),
+            ResourceType::I32(ref x) => x.size(),
+            ResourceType::F32(ref x) => x.size(),
+            ResourceType::VU32(ref x) => x.size(),
+            ResourceType::VI32(ref x) => x.size(),
+            ResourceType::VF32(ref x) => x.size(),
+        }) as u64
+    }
+}
+
+trait Header: Type {
+    fn encode(len: usize) -> Self;
+    fn decode(self) -> usize;
+}
+
+impl Header for u32 {
+    fn encode(len: usize) -> u32 {
+        len as u32
+    }
+
+    fn decode(self) -> usize {
+        self as usize
+    }
+}
+
+impl Header for i32 {
+    fn encode(len: usize) -> i32 {
+        len as i32
+    }
+
+    fn decode(self) -> usize {
+        self as usize
+    }
+}
+
+impl Header for f32 {
+    fn encode(len: usize) -> f32 {
+        f32::from_bits(len as u32)
+    }
+
+    fn decode(self) -> usize {
+        self.to_bits() as usize
+    }
+}
+
+fn unsupported() -> Error {
+    Error::Validation("Invalid TokenValue type".into())
+}
+
+fn scalar<U: Type>(device: &Arc<Device>, value: U) -> Result<Arc<CpuAccessibleBuffer<U>>, Error> {
+    CpuAccessibleBuffer::from_data(device.clone(), BufferUsage::all(), true, value)
+        .map_err(device_error)
+}
+
+fn array<U: Header>(
+    device: &Arc<Device>,
+    data: &[U],
+    len: usize,
+    capacity: usize,
+) -> Result<Arc<CpuAccessibleBuffer<[U]>>, Error> {
+    CpuAccessibleBuffer::from_iter(
+        device.clone(),
+        BufferUsage::all(),
+        true,
+        (0..capacity + 1).map(|i| match i {
+            0 => U::encode(len),
+            i if i <= data.len() => data[i - 1],
+            _ => U::default(),
+        }),
+    )
+    .map_err(device_error)
+}
+
+fn reshape<U: Header>(
+    device: &Arc<Device>,
+    buffer: &CpuAccessibleBuffer<[U]>,
+    len: Option<usize>,
+    capacity: usize,
+) -> Result<Option<Arc<CpuAccessibleBuffer<[U]>>>, Error> {
+    let mut data = buffer.write().map_err(device_error)?;
+    let old = data[0].decode();
+    let len = len.unwrap_or(old);
+    let capacity = capacity.max(len);
+    if capacity < data.len() {
+        for x in data.iter_mut().take(len + 1).skip(old + 1) {
+            *x = U::default();
+        }
+        data[0] = U::encode(len);
+        return Ok(None);
+    }
+    array(device, &data[1..][..old.min(len)], len, capacity).map(Some)
+}
+
 impl VkResource {
     pub(crate) fn get_handle(&self) -> Arc<Mutex<ResourceType>> {
         self.resource.clone()
     }
+
+    fn reshape<T: Type>(&self, len: Option<usize>, capacity: usize) -> Result<(), Error> {
+        let mut resource = self.resource.lock().unwrap();
+        let reshaped = match (&*resource, T::data_type()) {
+            (&ResourceType::VU32(ref v), DataType::U32) => {
+                reshape(&self.device, v, len, capacity)?.map(ResourceType::VU32)
+            }
+            (&ResourceType::VI32(ref v), DataType::I32) => {
+                reshape(&self.device, v, len, capacity)?.map(ResourceType::VI32)
+            }
+            (&ResourceType::VF32(ref v), DataType::F32) => {
+                reshape(&self.device, v, len, capacity)?.map(ResourceType::VF32)
+            }
+            (_, data_type) => {
+                let len = len.unwrap_or(0);
+                let capacity = capacity.max(len);
+                Some(match data_type {
+                    DataType::U32 => ResourceType::VU32(array(&self.device, &[], len, capacity)?),
+                    DataType::I32 => ResourceType::VI32(array(&self.device, &[], len, capacity)?),
+                    DataType::F32 => ResourceType::VF32(array(&self.device, &[], len, capacity)?),
+                    DataType::Bool => return Err(unsupported()),
+                })
+            }
+        };
+        if let Some(reshaped) = reshaped {
+            *resource = reshaped;
+        }
+        Ok(())
+    }
 }
 
 impl Resource for VkResource {
@@ -54,64 +206,115 @@ impl Resource for VkResource {
     }
 
     fn token_type(&self) -> TokenType {
-        match *self.resource.lock().unwrap() {
-            ResourceType::Empty => TokenType::Null,
-            ResourceType::U32(_) => TokenType::Variable(DataType::U32),
-            ResourceType::I32(_) => TokenType::Variable(DataType::I32),
-            ResourceType::F32(_) => TokenType::Variable(DataType::F32),
-            ResourceType::VU32(_) => TokenType::Array(DataType::U32),
-            ResourceType::VI32(_) => TokenType::Array(DataType::I32),
-            ResourceType::VF32(_) => TokenType::Array(DataType::F32),
+        self.resource.lock().unwrap().token_type()
+    }
+
+    fn set_data(&self, value: TokenValue) -> Result<(), Error> {
+        let mut resource = self.resource.lock().unwrap();
+        let scalar = match (&*resource, value) {
+            (&ResourceType::U32(ref v), TokenValue::Scalar(ConstantScalar::U32(x))) => {
+                *v.write().map_err(device_error)? = x;
+                None
+            }
+            (&ResourceType::I32(ref v), TokenValue::Scalar(ConstantScalar::I32(x))) => {
+                *v.write().map_err(device_error)? = x;
+                None
+            }
+            (&ResourceType::F32(ref v), TokenValue::Scalar(ConstantScalar::F32(x))) => {
+                *v.write().map_err(device_error)? = x;
+                None
+            }
+            (_, TokenValue::Scalar(ConstantScalar::U32(x))) => {
+                Some(ResourceType::U32(scalar(&self.device, x)?))
+            }
+            (_, TokenValue::Scalar(ConstantScalar::I32(x))) => {
+                Some(ResourceType::I32(scalar(&self.device, x)?))
+            }
+            (_, TokenValue::Scalar(ConstantScalar::F32(x))) => {
+                Some(ResourceType::F32(scalar(&self.device, x)?))
+            }
+            (_, TokenValue::Vector(vector)) => {
+                drop(resource);
+                return match vector {
+                    ConstantVector::U32(x) => self.write_slice(&x),
+                    ConstantVector::I32(x) => self.write_slice(&x),
+                    ConstantVector::F32(x) => self.write_slice(&x),
+                    ConstantVector::Bool(_) => Err(unsupported()),
+                };
+            }
+            _ => return Err(unsupported()),
+        };
+        if let Some(scalar) = scalar {
+            *resource = scalar;
         }
+        Ok(())
+    }
+
+    fn map<T: Type, R, F: FnOnce(&[T]) -> R>(&self, f: F) -> Result<R, Error> {
+        let result = match *self.resource.lock().unwrap() {
+            ResourceType::Empty => None,
+            ResourceType::U32(ref v) => {
+                cast_slice(slice::from_ref(&*v.read().map_err(device_error)?)).map(f)
+            }
+            ResourceType::I32(ref v) => {
+                cast_slice(slice::from_ref(&*v.read().map_err(device_error)?)).map(f)
+            }
+            ResourceType::F32(ref v) => {
+                cast_slice(slice::from_ref(&*v.read().map_err(device_error)?)).map(f)
+            }
+            ResourceType::VU32(ref v) => {
+                let v = v.read().map_err(device_error)?;
+                cast_slice(&v[1..][..v[0].decode()]).map(f)
+            }
+            ResourceType::VI32(ref v) => {
+                let v = v.read().map_err(device_error)?;
+                cast_slice(&v[1..][..v[0].decode()]).map(f)
+            }
+            ResourceType::VF32(ref v) => {
+                let v = v.read().map_err(device_error)?;
+                cast_slice(&v[1..][..v[0].decode()]).map(f)
+            }
+        };
+        result.ok_or_else(|| access_error::<T>(self.token_type()))
     }
 
-    fn set_data(&self, value: TokenValue) {
-        let resource = match value {
-            TokenValue::Scalar(ConstantScalar::U32(x)) => ResourceType::U32(
-                CpuAccessibleBuffer::from_data(self.device.clone(), BufferUsage::all(), true, x)
-                    .unwrap(),
-            ),
-            TokenValue::Scalar(ConstantScalar::I32(x)) => ResourceType::I32(
-                CpuAccessibleBuffer::from_data(self.device.clone(), BufferUsage::all(), true, x)
-                    .unwrap(),
-            ),
-            TokenValue::Scalar(ConstantScalar::F32(x)) => ResourceType::F32(
-                CpuAccessibleBuffer::from_data(self.device.clone(), BufferUsage::all(), true, x)
-                    .unwrap(),
-            ),
-            TokenValue::Vector(ConstantVector::U32(mut x)) => ResourceType::VU32({
-                x.insert(0, x.len() as u32);
-                CpuAccessibleBuffer::from_iter(
-                    self.device.clone(),
-                    BufferUsage::all(),
-                    true,
-                    x.into_iter(),
-                )
-                .unwrap()
-            }),
-            TokenValue::Vector(ConstantVector::I32(mut x)) => ResourceType::VI32({
-                x.insert(0, x.len() as i32);
-                CpuAccessibleBuffer::from_iter(
-                    self.device.clone(),
-                    BufferUsage::all(),
-                    true,
-                    x.into_iter(),
-                )
-                .unwrap()
-            }),
-            TokenValue::Vector(ConstantVector::F32(mut x)) => ResourceType::VF32({
-                x.insert(0, f32::from_bits(x.len() as u32));
-                CpuAccessibleBuffer::from_iter(
-                    self.device.clone(),
-                    BufferUsage::all(),
-                    true,
-                    x.into_iter(),
-                )
-                .unwrap()
-            }),
-            _ => panic!("Invalid TokenValue type"),
+    fn map_mut<T: Type, R, F: FnOnce(&mut [T]) -> R>(&self, f: F) -> Result<R, Error> {
+        let result = match *self.resource.lock().unwrap() {
+            ResourceType::Empty => None,
+            ResourceType::U32(ref v) => {
+                cast_slice_mut(slice::from_mut(&mut *v.write().map_err(device_error)?)).map(f)
+            }
+            ResourceType::I32(ref v) => {
+                cast_slice_mut(slice::from_mut(&mut *v.write().map_err(device_error)?)).map(f)
+            }
+            ResourceType::F32(ref v) => {
+                cast_slice_mut(slice::from_mut(&mut *v.write().map_err(device_error)?)).map(f)
+            }
+            ResourceType::VU32(ref v) => {
+                let mut v = v.write().map_err(device_error)?;
+                let len = v[0].decode();
+                cast_slice_mut(&mut v[1..][..len]).map(f)
+            }
+            ResourceType::VI32(ref v) => {
+                let mut v = v.write().map_err(device_error)?;
+                let len = v[0].decode();
+                cast_slice_mut(&mut v[1..][..len]).map(f)
+            }
+            ResourceType::VF32(ref v) => {
+                let mut v = v.write().map_err(device_error)?;
+                let len = v[0].decode();
+                cast_slice_mut(&mut v[1..][..len]).map(f)
+            }
         };
-        *self.resource.lock().unwrap() = resource;
+        result.ok_or_else(|| access_error::<T>(self.token_type()))
+    }
+
+    fn resize<T: Type>(&self, len: usize) -> Result<(), Error> {
+        self.reshape::<T>(Some(len), len)
+    }
+
+    fn reserve<T: Type>(&self, capacity: usize) -> Result<(), Error> {
+        self.reshape::<T>(None, capacity)
     }
 
     fn get_data(&self) -> TokenValue {
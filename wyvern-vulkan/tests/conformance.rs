@@ -0,0 +1,63 @@
+// Copyright 2018 | Dario Ostuni <dario.ostuni@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+extern crate wyvern_core as wcore;
+extern crate wyvern_vulkan;
+
+use wcore::conformance;
+use wcore::executor::Executor;
+use wyvern_vulkan::executor::{VkConfig, VkExecutor};
+
+fn executor() -> VkExecutor {
+    VkExecutor::new(VkConfig::default().checked(true)).unwrap()
+}
+
+#[test]
+fn inout() {
+    conformance::inout(&executor());
+}
+
+#[test]
+fn parameters() {
+    conformance::parameters(&executor());
+}
+
+#[test]
+fn pipeline() {
+    conformance::pipeline(&executor());
+}
+
+#[test]
+fn run_until() {
+    conformance::run_until(&executor());
+}
+
+#[test]
+fn batch() {
+    conformance::batch(&executor());
+}
+
+#[test]
+fn submit() {
+    conformance::submit(&executor());
+}
+
+#[test]
+fn group_barrier() {
+    conformance::group_barrier(&executor());
+}
+
+#[test]
+fn global_barrier() {
+    conformance::global_barrier(&executor());
+}